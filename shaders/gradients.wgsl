@@ -0,0 +1,88 @@
+// Gradients of the density, for a slab of z slices of the volume.
+// Texels are rgba16float: xyz is the gradient in density change per voxel, w its magnitude.
+// Anisotropic volumes are scaled as if every voxel had the size of the smallest side.
+
+#include "include/volume_info.wgsl"
+
+struct Slab {
+    first_slice: u32,
+    depth: u32,
+    // Texels per buffer row, rows are padded for the buffer to texture copy
+    row_stride: u32,
+    // 1 for a 3x3x3 Sobel filter, 0 for central differences
+    use_sobel: u32,
+}
+
+@group(0) @binding(0)
+var volume_texture: texture_3d<f32>;
+@group(0) @binding(1)
+var volume_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> volume_info: VolumeInfo;
+
+@group(1) @binding(0)
+var<storage, read_write> gradients: array<vec2<u32>>;
+@group(1) @binding(1)
+var<uniform> slab: Slab;
+
+fn density_at(voxel: vec3<i32>) -> f32 {
+    let dims = vec3<i32>(textureDimensions(volume_texture));
+    let sample = textureLoad(volume_texture, clamp(voxel, vec3<i32>(0), dims - 1), 0).r;
+    return clamp(sample * volume_info.value_scale + volume_info.value_offset, 0.0, 1.0);
+}
+
+fn central_difference(voxel: vec3<i32>) -> vec3<f32> {
+    let dx = vec3<i32>(1, 0, 0);
+    let dy = vec3<i32>(0, 1, 0);
+    let dz = vec3<i32>(0, 0, 1);
+    return 0.5 * vec3<f32>(
+        density_at(voxel + dx) - density_at(voxel - dx),
+        density_at(voxel + dy) - density_at(voxel - dy),
+        density_at(voxel + dz) - density_at(voxel - dz),
+    );
+}
+
+// Derivative along each axis, smoothed with 1 2 1 weights along the two others.
+// Normalized to the same scale as the central difference.
+fn sobel(voxel: vec3<i32>) -> vec3<f32> {
+    var gradient = vec3<f32>(0.0);
+    for (var z = -1; z <= 1; z++) {
+        for (var y = -1; y <= 1; y++) {
+            for (var x = -1; x <= 1; x++) {
+                let offset = vec3<i32>(x, y, z);
+                let smoothing = vec3<f32>(2 - abs(offset));
+                let weights = vec3<f32>(offset) * vec3<f32>(
+                    smoothing.y * smoothing.z,
+                    smoothing.x * smoothing.z,
+                    smoothing.x * smoothing.y,
+                );
+                gradient += weights * density_at(voxel + offset);
+            }
+        }
+    }
+    return gradient / 32.0;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let dims = textureDimensions(volume_texture);
+    if global_id.x >= dims.x || global_id.y >= dims.y || global_id.z >= slab.depth {
+        return;
+    }
+
+    let voxel = vec3<i32>(global_id) + vec3<i32>(0, 0, i32(slab.first_slice));
+    var gradient: vec3<f32>;
+    if slab.use_sobel == 1 {
+        gradient = sobel(voxel);
+    } else {
+        gradient = central_difference(voxel);
+    }
+    let voxel_size = volume_info.extent / vec3<f32>(dims);
+    gradient *= min(min(voxel_size.x, voxel_size.y), voxel_size.z) / voxel_size;
+
+    let index = (global_id.z * dims.y + global_id.y) * slab.row_stride + global_id.x;
+    gradients[index] = vec2<u32>(
+        pack2x16float(gradient.xy),
+        pack2x16float(vec2<f32>(gradient.z, length(gradient)))
+    );
+}
@@ -1,45 +1,47 @@
+#define CLIPPING
+#include "include/ray.wgsl"
+#include "include/accumulation.wgsl"
+#include "include/volume_info.wgsl"
 
-struct CameraUniforms {
-    view_matrix: mat4x4<f32>,
-    projection_matrix: mat4x4<f32>,
-    inverse_view_proj: mat4x4<f32>,
-    camera_position: vec3<f32>,
-}
+const ISOSURFACE_REFINEMENT_STEPS: i32 = 8;
 
-struct Parameters {
-  density_threshold: f32,
-  use_cone_importance_check: u32,
-  use_importance_coloring: u32,
-  use_opacity: u32,
-  use_importance_rendering: u32,
-  use_gaussian_smoothing: u32,
-  importance_check_ahead_steps: u32,
-  raymarching_step_size: f32,
+struct OccupancyInfo {
+    // Size of a macro cell in texture coordinates
+    cell_size: vec3<f32>,
 }
 
-@group(0) @binding(0)
-var<uniform> camera: CameraUniforms;
-@group(0) @binding(1) 
-var<uniform> parameters: Parameters;
-
-@group(1) @binding(0)
-var output_texture: texture_storage_2d<rgba8unorm, write>;
-@group(1) @binding(1)
-var debug_texture: texture_storage_2d<rgba8unorm, write>;
-
 @group(2) @binding(0)
 var volume_texture: texture_3d<f32>;
 @group(2) @binding(1)
 var volume_sampler: sampler;
 @group(2) @binding(2)
-var transfer_function_texture: texture_1d<f32>;
+var<uniform> volume_info: VolumeInfo;
 @group(2) @binding(3)
-var transfer_function_sampler: sampler;
+var transfer_function_texture: texture_1d<f32>;
 @group(2) @binding(4)
-var importances_texture: texture_3d<f32>;
+var transfer_function_sampler: sampler;
 @group(2) @binding(5)
+var importances_texture: texture_3d<f32>;
+@group(2) @binding(6)
 var importances_sampler: sampler;
-
+@group(2) @binding(7)
+var transfer_function_2d_texture: texture_2d<f32>;
+@group(2) @binding(8)
+var transfer_function_2d_sampler: sampler;
+@group(2) @binding(9)
+var gradients_texture: texture_3d<f32>;
+@group(2) @binding(10)
+var gradients_sampler: sampler;
+@group(2) @binding(11)
+var occupancy_texture: texture_3d<f32>;
+@group(2) @binding(12)
+var<uniform> occupancy_info: OccupancyInfo;
+
+
+fn sample_density(pos: vec3<f32>) -> f32 {
+    let sample = textureSampleLevel(volume_texture, volume_sampler, pos, 0.0).r;
+    return clamp(sample * volume_info.value_scale + volume_info.value_offset, 0.0, 1.0);
+}
 
 fn gaussian_weight(x: f32, sigma: f32) -> f32 {
     return exp(-(x * x) / (2.0 * sigma * sigma));
@@ -59,13 +61,13 @@ fn sample_volume_smoothed(pos: vec3<f32>, ray_dir: vec3<f32>, sigma: f32) -> f32
         let offset = f32(i) * step;
         let sample_pos = pos + ray_dir * offset;
         
-        // Skip samples outside the volume
-        if any(sample_pos < vec3<f32>(0.0)) || any(sample_pos > vec3<f32>(1.0)) {
+        // Skip samples that are clipped away
+        if is_clipped(sample_pos) {
             continue;
         }
 
         let weight = gaussian_weight(offset, sigma);
-        let sample = textureSampleLevel(volume_texture, volume_sampler, sample_pos, 0.0).r;
+        let sample = sample_density(sample_pos);
 
         sum += sample * weight;
         weight_sum += weight;
@@ -74,6 +76,14 @@ fn sample_volume_smoothed(pos: vec3<f32>, ray_dir: vec3<f32>, sigma: f32) -> f32
     return sum / weight_sum;
 }
 
+fn sample_ray_density(pos: vec3<f32>, ray_dir: vec3<f32>) -> f32 {
+    if parameters.use_gaussian_smoothing == 1 {
+        let sigma = 1.5; // higher = more smoothing
+        return sample_volume_smoothed(pos, ray_dir, sigma);
+    }
+    return sample_density(pos);
+}
+
 
 fn has_non_zero_component(color: vec3<f32>) -> bool {
     let epsilon: f32 = 0.0001;
@@ -119,7 +129,7 @@ fn has_important_object_ahead_cone(current_pos: vec3<f32>, main_direction: vec3<
         for (var i = 0; i < check_steps; i++) {
             pos += sample_direction * step;
 
-            if any(pos < vec3<f32>(0.0)) || any(pos > vec3<f32>(1.0)) {
+            if is_clipped(pos) {
                 break;
             }
 
@@ -145,6 +155,9 @@ fn has_important_object_ahead_straight(current_pos: vec3<f32>, ray_direction: ve
 
     for (var i = 0; i < check_steps; i++) {
         pos += ray_direction * step;
+        if is_clipped(pos) {
+            break;
+        }
         let importance = textureSampleLevel(
             importances_texture,
             importances_sampler,
@@ -159,55 +172,326 @@ fn has_important_object_ahead_straight(current_pos: vec3<f32>, ray_direction: ve
     return false;
 }
 
-fn ray_box_intersection(ray_origin: vec3<f32>, ray_direction: vec3<f32>) -> vec2<f32> {
-    let box_min = vec3<f32>(0.0);
-    let box_max = vec3<f32>(1.0);
+// The volume's bounding box is centered at (0.5, 0.5, 0.5), the camera's target.
+// Rays are marched in texture space, so that non cubic volumes keep their proportions.
+fn to_volume_space(world_pos: vec3<f32>) -> vec3<f32> {
+    let box_min = vec3<f32>(0.5) - volume_info.extent * 0.5;
+    return (world_pos - box_min) / volume_info.extent;
+}
+
+// Density change per voxel along each axis, from the precomputed gradients or central differences.
+// Anisotropic volumes are scaled as if every voxel had the size of the smallest side.
+fn density_gradient(pos: vec3<f32>) -> vec3<f32> {
+    if parameters.use_precomputed_gradients == 1 {
+        return textureSampleLevel(gradients_texture, gradients_sampler, pos, 0.0).xyz;
+    }
 
-    let t1 = (box_min - ray_origin) / ray_direction;
-    let t2 = (box_max - ray_origin) / ray_direction;
+    let dims = vec3<f32>(textureDimensions(volume_texture));
+    let voxel = 1.0 / dims;
+    let dx = vec3<f32>(voxel.x, 0.0, 0.0);
+    let dy = vec3<f32>(0.0, voxel.y, 0.0);
+    let dz = vec3<f32>(0.0, 0.0, voxel.z);
+    let gradient = 0.5 * vec3<f32>(
+        sample_density(pos + dx) - sample_density(pos - dx),
+        sample_density(pos + dy) - sample_density(pos - dy),
+        sample_density(pos + dz) - sample_density(pos - dz),
+    );
 
-    let tmin = min(t1, t2);
-    let tmax = max(t1, t2);
+    let voxel_size = volume_info.extent / dims;
+    return gradient * min(min(voxel_size.x, voxel_size.y), voxel_size.z) / voxel_size;
+}
 
-    let entry_point = max(max(tmin.x, tmin.y), tmin.z);
-    let exit_point = min(min(tmax.x, tmax.y), tmax.z);
+// Distance along the ray at which it leaves the macro cell containing `pos`, or a negative
+// value when the cell may contribute to the image
+fn empty_cell_exit_distance(ray_origin: vec3<f32>, ray_direction: vec3<f32>, pos: vec3<f32>) -> f32 {
+    let grid_dims = textureDimensions(occupancy_texture);
+    let cell = min(vec3<u32>(max(pos, vec3<f32>(0.0)) / occupancy_info.cell_size), grid_dims - 1u);
+    if textureLoad(occupancy_texture, cell, 0).r > 0.0 {
+        return -1.0;
+    }
 
-    return vec2<f32>(
-        max(entry_point, 0.0),
-        max(exit_point, 0.0)
-    );
+    let cell_min = vec3<f32>(cell) * occupancy_info.cell_size;
+    let cell_max = cell_min + occupancy_info.cell_size;
+    let exit_planes = select(cell_min, cell_max, ray_direction > vec3<f32>(0.0));
+    // Axes the ray is parallel to never leave the cell
+    let safe_direction = select(ray_direction, vec3<f32>(1e-20), abs(ray_direction) < vec3<f32>(1e-20));
+    let t = select((exit_planes - ray_origin) / safe_direction, vec3<f32>(3.4e38), abs(ray_direction) < vec3<f32>(1e-20));
+    return min(min(t.x, t.y), t.z);
 }
 
-fn compute_gradient(volume: texture_3d<f32>, s: sampler, pos: vec3<f32>) -> vec3<f32> {
-    let offset = vec3<f32>(0.01, 0.01, 0.01);
-    let grad_x = (textureSampleLevel(volume, s, pos + vec3<f32>(offset.x, 0.0, 0.0), 0.0).r - textureSampleLevel(volume, s, pos - vec3<f32>(offset.x, 0.0, 0.0), 0.0).r) / (2.0 * offset.x);
-    let grad_y = (textureSampleLevel(volume, s, pos + vec3<f32>(0.0, offset.y, 0.0), 0.0).r - textureSampleLevel(volume, s, pos - vec3<f32>(0.0, offset.y, 0.0), 0.0).r) / (2.0 * offset.y);
-    let grad_z = (textureSampleLevel(volume, s, pos + vec3<f32>(0.0, 0.0, offset.z), 0.0).r - textureSampleLevel(volume, s, pos - vec3<f32>(0.0, 0.0, offset.z), 0.0).r) / (2.0 * offset.z);
+// Distance of the first whole step past the macro cell at `distance` when that cell is empty,
+// `distance` itself otherwise
+fn skip_empty_cell(ray_origin: vec3<f32>, ray_direction: vec3<f32>, distance: f32, step_size: f32) -> f32 {
+    // Smoothed densities blend in samples of the neighboring cells
+    if parameters.use_empty_space_skipping == 0 || parameters.use_gaussian_smoothing == 1 {
+        return distance;
+    }
 
-    return normalize(vec3<f32>(grad_x, grad_y, grad_z));
+    let exit_distance = empty_cell_exit_distance(ray_origin, ray_direction, ray_origin + ray_direction * distance);
+    if exit_distance < 0.0 {
+        return distance;
+    }
+    return distance + max(ceil((exit_distance - distance) / step_size), 1.0) * step_size;
+}
+
+fn sample_transfer_function(density: f32, gradient: vec3<f32>) -> vec4<f32> {
+    if parameters.use_transfer_function_2d == 1 {
+        let gradient_magnitude = clamp(length(gradient) / parameters.max_gradient_magnitude, 0.0, 1.0);
+        return textureSampleLevel(
+            transfer_function_2d_texture,
+            transfer_function_2d_sampler,
+            vec2<f32>(density, gradient_magnitude),
+            0.0
+        );
+    }
+
+    return textureSampleLevel(
+        transfer_function_texture,
+        transfer_function_sampler,
+        density,
+        0.0
+    );
 }
 
 fn blinn_phong_shade(
     pos: vec3<f32>,
     color: vec3<f32>,
-    volume: texture_3d<f32>,
-    s: sampler
+    gradient: vec3<f32>
 ) -> vec3<f32> {
-    let gradient_normal = compute_gradient(volume, s, pos);
+    if length(gradient) > 0.0 {
+        let gradient_normal = normalize(gradient);
+        let eye_direction = normalize(to_volume_space(camera.camera_position) - pos);
+
+        var diffuse = vec3<f32>(0.0);
+        var specular = vec3<f32>(0.0);
+        // The headlight comes after the other lights
+        let light_count = lighting.light_count + lighting.headlight;
+        for (var i = 0u; i < light_count; i++) {
+            var light_direction = eye_direction;
+            var light_color = vec3<f32>(1.0);
+            if i < lighting.light_count {
+                let light = lighting.light_vectors[i];
+                // Point lights have w = 1, their direction depends on the sample
+                light_direction = normalize(light.xyz - pos * light.w);
+                light_color = lighting.light_colors[i].rgb;
+            }
+            let halfway_vector = normalize(eye_direction + light_direction);
+            diffuse += light_color * max(0.0, dot(gradient_normal, light_direction));
+            specular += light_color * pow(max(0.0, dot(halfway_vector, gradient_normal)), lighting.shininess);
+        }
 
-    if length(gradient_normal) > 0.0 {
-        let light_direction = normalize(vec3<f32>(1.0, 1.0, 1.0));
-        let eye_direction = normalize(camera.camera_position - pos);
-        let halfway_vector = normalize(eye_direction + light_direction);
+        return color * (lighting.ambient + lighting.diffuse * diffuse) + lighting.specular * specular;
+    }
+
+    return color;
+}
 
-        let ambient = 0.2;
-        let diffuse = max(0.0, dot(gradient_normal, light_direction));
-        let specular = pow(max(0.0, dot(halfway_vector, gradient_normal)), 24.0);
+// Maximum, minimum or average density along the ray in gray levels, samples below the threshold
+// are background. Importance MIP only considers the samples of important segments.
+fn project(ray_origin: vec3<f32>, ray_direction: vec3<f32>, intersection: vec2<f32>) -> vec4<f32> {
+    let step_size = parameters.raymarching_step_size;
+    var max_density = 0.0;
+    var min_density = 1.0;
+    var density_sum = 0.0;
+    var sample_count = 0u;
 
-        return color * (ambient + 0.7 * diffuse) + vec3<f32>(1.0, 1.0, 1.0) * 0.4 * specular;
+    var current_distance = intersection.x;
+    while current_distance < intersection.y {
+        let next_distance = skip_empty_cell(ray_origin, ray_direction, current_distance, step_size);
+        if next_distance > current_distance {
+            current_distance = next_distance;
+            continue;
+        }
+
+        let current_pos = ray_origin + ray_direction * current_distance;
+        current_distance += step_size;
+
+        let density = sample_ray_density(current_pos, ray_direction);
+        if density < parameters.density_threshold {
+            continue;
+        }
+        if parameters.render_mode == RENDER_MODE_IMPORTANCE_MIP {
+            let importance = textureSampleLevel(importances_texture, importances_sampler, current_pos, 0.0).r;
+            if importance < 0.5 {
+                continue;
+            }
+        }
+
+        max_density = max(max_density, density);
+        min_density = min(min_density, density);
+        density_sum += density;
+        sample_count += 1u;
     }
 
-    return color;
+    if sample_count == 0u {
+        return vec4<f32>(0.0);
+    }
+
+    var intensity = max_density;
+    if parameters.render_mode == RENDER_MODE_MINIP {
+        intensity = min_density;
+    } else if parameters.render_mode == RENDER_MODE_AVERAGE {
+        intensity = density_sum / f32(sample_count);
+    }
+    return vec4<f32>(vec3<f32>(intensity), 1.0);
+}
+
+// Distance between `near` and `far`, where the density crosses `isovalue`, found by bisection
+fn refine_isosurface_hit(ray_origin: vec3<f32>, ray_direction: vec3<f32>, isovalue: f32, near: f32, far: f32) -> f32 {
+    var inside_near = sample_ray_density(ray_origin + ray_direction * near, ray_direction) >= isovalue;
+    var a = near;
+    var b = far;
+    for (var i = 0; i < ISOSURFACE_REFINEMENT_STEPS; i++) {
+        let middle = (a + b) * 0.5;
+        let inside = sample_ray_density(ray_origin + ray_direction * middle, ray_direction) >= isovalue;
+        if inside == inside_near {
+            a = middle;
+        } else {
+            b = middle;
+        }
+    }
+    return (a + b) * 0.5;
+}
+
+// Every isovalue crossed between two samples is composited front to back, each surface with
+// its own color and opacity, lit on the side facing the camera
+fn render_isosurfaces(ray_origin: vec3<f32>, ray_direction: vec3<f32>, intersection: vec2<f32>) -> vec4<f32> {
+    let step_size = parameters.raymarching_step_size;
+    let count = i32(parameters.isosurface_count);
+    if count == 0 {
+        return vec4<f32>(0.0);
+    }
+    var accumulated_color = vec3<f32>(0.0);
+    var accumulated_alpha = 0.0;
+
+    var previous_distance = intersection.x;
+    var previous_density = sample_ray_density(ray_origin + ray_direction * previous_distance, ray_direction);
+    var current_distance = intersection.x + step_size;
+    while previous_distance < intersection.y && accumulated_alpha < 0.99 {
+        current_distance = min(current_distance, intersection.y);
+
+        // Empty cells are below every isovalue, so is the previous sample when nothing
+        // is crossed before the jump
+        if previous_density < parameters.isovalues[0] {
+            let next_distance = skip_empty_cell(ray_origin, ray_direction, current_distance, step_size);
+            if next_distance > current_distance {
+                // The sample before the landing one is still within the empty cell
+                previous_distance = next_distance - step_size;
+                previous_density = sample_ray_density(ray_origin + ray_direction * previous_distance, ray_direction);
+                current_distance = next_distance;
+                continue;
+            }
+        }
+
+        let density = sample_ray_density(ray_origin + ray_direction * current_distance, ray_direction);
+
+        // Along a rising density the lower isovalues are crossed first
+        let rising = density >= previous_density;
+        for (var j = 0; j < count; j++) {
+            let i = select(count - 1 - j, j, rising);
+            let isovalue = parameters.isovalues[i];
+            if (previous_density >= isovalue) == (density >= isovalue) {
+                continue;
+            }
+
+            let hit_distance = refine_isosurface_hit(ray_origin, ray_direction, isovalue, previous_distance, current_distance);
+            let hit_pos = ray_origin + ray_direction * hit_distance;
+            let gradient = density_gradient(hit_pos);
+            let facing_gradient = select(gradient, -gradient, dot(gradient, ray_direction) > 0.0);
+
+            let color = parameters.isosurface_colors[i];
+            let shaded_color = blinn_phong_shade(hit_pos, color.rgb, facing_gradient);
+            let opacity_contrib = (1.0 - accumulated_alpha) * color.a;
+            accumulated_color += shaded_color * opacity_contrib;
+            accumulated_alpha += opacity_contrib;
+        }
+
+        previous_distance = current_distance;
+        previous_density = density;
+        current_distance += step_size;
+    }
+
+    return vec4<f32>(accumulated_color, accumulated_alpha);
+}
+
+// Axial (z), coronal (y) and sagittal (x) slices through the probe, in square panes stacked
+// from the top. Slices keep their proportions, the probe is marked with a cross-hair.
+fn render_slice_pane(pixel: vec2<u32>, pane_size: u32) -> vec4<f32> {
+    let pane = pixel.y / pane_size;
+    if pane >= 3u {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    let pane_pixel = vec2<u32>(pixel.x, pixel.y - pane * pane_size);
+    if pane_pixel.x == 0u || pane_pixel.y == 0u {
+        return vec4<f32>(0.3, 0.3, 0.3, 1.0);
+    }
+
+    // Volume axes shown horizontally and vertically, must match `SliceViews::pane_position`
+    let axis = 2u - pane;
+    var horizontal = 0u;
+    var vertical = 1u;
+    var flip_vertical = true;
+    if axis == 1u {
+        vertical = 2u;
+        flip_vertical = false;
+    } else if axis == 0u {
+        horizontal = 2u;
+    }
+    let extent = volume_info.extent;
+    let scale = max(extent[horizontal], extent[vertical]);
+    let probe = parameters.probe_position;
+
+    let uv = (vec2<f32>(pane_pixel) + 0.5) / f32(pane_size);
+    var probe_vertical = probe[vertical];
+    if flip_vertical {
+        probe_vertical = 1.0 - probe_vertical;
+    }
+    let probe_uv = (vec2<f32>(probe[horizontal], probe_vertical) - 0.5)
+        * vec2<f32>(extent[horizontal], extent[vertical]) / scale + 0.5;
+    let to_probe = abs(uv - probe_uv) * f32(pane_size);
+    if min(to_probe.x, to_probe.y) < 0.5 {
+        return vec4<f32>(1.0, 0.85, 0.1, 1.0);
+    }
+
+    let h = (uv.x - 0.5) * scale / extent[horizontal] + 0.5;
+    var w = (uv.y - 0.5) * scale / extent[vertical] + 0.5;
+    if h < 0.0 || h > 1.0 || w < 0.0 || w > 1.0 {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    if flip_vertical {
+        w = 1.0 - w;
+    }
+    var pos = probe;
+    pos[horizontal] = h;
+    pos[vertical] = w;
+
+    let density = sample_density(pos);
+    var color = vec3<f32>(density);
+    if parameters.use_slice_transfer_function == 1 {
+        let transfer = sample_transfer_function(density, density_gradient(pos));
+        color = mix(color, transfer.rgb, transfer.a);
+    }
+    if parameters.show_slice_segments == 1 {
+        let importance = textureSampleLevel(importances_texture, importances_sampler, pos, 0.0).r;
+        if importance > 0.0 {
+            color = mix(color, importance_to_color(importance).rgb, 0.4);
+        }
+    }
+    return vec4<f32>(color, 1.0);
+}
+
+// Whether a pixel of the 3D view belongs to the cross marking the probe
+fn is_probe_marker(pixel: vec2<f32>, view_size: vec2<f32>, ndc_x_scale: f32) -> bool {
+    let box_min = vec3<f32>(0.5) - volume_info.extent * 0.5;
+    let world = box_min + parameters.probe_position * volume_info.extent;
+    let clip = camera.projection_matrix * camera.view_matrix * vec4<f32>(world, 1.0);
+    if clip.w <= 0.0 {
+        return false;
+    }
+    let ndc = clip.xy / clip.w;
+    let marker = vec2<f32>((ndc.x / ndc_x_scale + 1.0) * 0.5, (1.0 - ndc.y) * 0.5) * view_size;
+    let offset = abs(pixel - marker);
+    return (offset.x < 1.0 && offset.y < 7.0) || (offset.y < 1.0 && offset.x < 7.0);
 }
 
 @compute @workgroup_size(16, 16, 1)
@@ -218,26 +502,61 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         return;
     }
 
+    let color = render_pixel(global_id.xy, output_dims);
+    textureStore(output_texture, global_id.xy, accumulate(global_id.xy, output_dims, color));
+}
+
+fn render_pixel(pixel: vec2<u32>, output_dims: vec2<u32>) -> vec4<f32> {
+    // The slice panes take a column on the right, the 3D view shows the middle of the frame
+    // in the rest, without stretching it
+    var view_width = output_dims.x;
+    if parameters.show_slices == 1 {
+        let pane_size = min(output_dims.y / 3u, output_dims.x / 3u);
+        view_width = output_dims.x - pane_size;
+        if pixel.x >= view_width {
+            return render_slice_pane(vec2<u32>(pixel.x - view_width, pixel.y), pane_size);
+        }
+    }
+    let ndc_x_scale = f32(view_width) / f32(output_dims.x);
+
+    if parameters.show_slices == 1 && is_probe_marker(
+        vec2<f32>(pixel),
+        vec2<f32>(f32(view_width), f32(output_dims.y)),
+        ndc_x_scale
+    ) {
+        return vec4<f32>(1.0, 0.85, 0.1, 1.0);
+    }
+
     let screen_coord = vec2<f32>(
-        f32(global_id.x) / f32(output_dims.x),
-        f32(global_id.y) / f32(output_dims.y)
+        f32(pixel.x) / f32(view_width),
+        f32(pixel.y) / f32(output_dims.y)
     );
 
     let ndc_coord = vec2<f32>(
-        screen_coord.x * 2.0 - 1.0,
+        (screen_coord.x * 2.0 - 1.0) * ndc_x_scale,
         1.0 - screen_coord.y * 2.0
     );
 
-    let ray_origin = camera.camera_position;
     let inverse_view_proj = camera.inverse_view_proj;
     let world_pos = inverse_view_proj * vec4<f32>(ndc_coord, 0.0, 1.0);
-    let ray_direction = normalize(world_pos.xyz / world_pos.w - camera.camera_position);
+    let world_ray_direction = normalize(world_pos.xyz / world_pos.w - camera.camera_position);
+
+    // Not normalized, distances along the ray are still measured in world units
+    let ray_origin = to_volume_space(camera.camera_position);
+    let ray_direction = world_ray_direction / volume_info.extent;
 
-    let intersection = ray_box_intersection(ray_origin, ray_direction);
+    var intersection = ray_box_intersection(ray_origin, ray_direction);
 
     if intersection.y <= intersection.x {
-        textureStore(output_texture, vec2<u32>(global_id.x, global_id.y), vec4<f32>(0.0, 0.0, 0.0, 1.0));
-        return;
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    intersection.x += ray_jitter(pixel) * parameters.raymarching_step_size;
+
+    if parameters.render_mode == RENDER_MODE_ISOSURFACE {
+        return render_isosurfaces(ray_origin, ray_direction, intersection);
+    }
+    if parameters.render_mode != RENDER_MODE_COMPOSITE {
+        return project(ray_origin, ray_direction, intersection);
     }
 
     let base_step_size = parameters.raymarching_step_size;
@@ -248,15 +567,15 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 
     var current_distance = intersection.x;
     while current_distance < intersection.y && accumulated_alpha < 0.95 {
-        let current_pos = ray_origin + ray_direction * current_distance;
-
-        var density = 0.0;
-        if parameters.use_gaussian_smoothing == 1 {
-            let sigma = 1.5; // higher = more smoothing
-            density = sample_volume_smoothed(current_pos, ray_direction, sigma);
-        } else {
-            density = textureSampleLevel(volume_texture, volume_sampler, current_pos, 0.0).r;
+        let next_distance = skip_empty_cell(ray_origin, ray_direction, current_distance, base_step_size);
+        if next_distance > current_distance {
+            current_distance = next_distance;
+            current_step_size = base_step_size;
+            continue;
         }
+
+        let current_pos = ray_origin + ray_direction * current_distance;
+        let density = sample_ray_density(current_pos, ray_direction);
         let importance = textureSampleLevel(importances_texture, importances_sampler, current_pos, 0.0).r;
 
          // Adapt step size based on density
@@ -273,6 +592,7 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
             continue;
         }
 
+        let gradient = density_gradient(current_pos);
         var color_and_alpha: vec4<f32>;
         var use_alpha = parameters.use_opacity == 1;
 
@@ -294,21 +614,10 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
                 }
             }
 
-            let transfer_color = textureSampleLevel(
-                transfer_function_texture,
-                transfer_function_sampler,
-                density,
-                0.0
-            );
-            color_and_alpha = transfer_color;
+            color_and_alpha = sample_transfer_function(density, gradient);
         }
 
-        let shaded_color = blinn_phong_shade(
-            current_pos,
-            color_and_alpha.rgb,
-            volume_texture,
-            volume_sampler
-        );
+        let shaded_color = blinn_phong_shade(current_pos, color_and_alpha.rgb, gradient);
 
         if use_alpha {
             let alpha = 1.0 - pow(1.0 - color_and_alpha.a, current_step_size * 100.0);
@@ -325,6 +634,5 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         current_distance += current_step_size;
     }
 
-    textureStore(output_texture, vec2<u32>(global_id.x, global_id.y),
-        vec4<f32>(accumulated_color, accumulated_alpha));
+    return vec4<f32>(accumulated_color, accumulated_alpha);
 }
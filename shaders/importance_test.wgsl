@@ -1,44 +1,14 @@
-struct CameraUniforms {
-    view_matrix: mat4x4<f32>,
-    projection_matrix: mat4x4<f32>,
-    inverse_view_proj: mat4x4<f32>,
-    camera_position: vec3<f32>,
-}
-
-@group(0) @binding(0)
-var<uniform> camera: CameraUniforms;
-
-@group(1) @binding(0)
-var output_texture: texture_storage_2d<rgba8unorm, write>;
+#include "include/ray.wgsl"
 
 @group(2) @binding(0)
 var volume_texture: texture_3d<f32>;
 @group(2) @binding(1)
 var volume_sampler: sampler;
-@group(2) @binding(4)
-var importances_texture: texture_3d<f32>;
 @group(2) @binding(5)
+var importances_texture: texture_3d<f32>;
+@group(2) @binding(6)
 var importances_sampler: sampler;
 
-fn ray_box_intersection(ray_origin: vec3<f32>, ray_direction: vec3<f32>) -> vec2<f32> {
-    let box_min = vec3<f32>(0.0);
-    let box_max = vec3<f32>(1.0);
-
-    let t1 = (box_min - ray_origin) / ray_direction;
-    let t2 = (box_max - ray_origin) / ray_direction;
-
-    let tmin = min(t1, t2);
-    let tmax = max(t1, t2);
-
-    let entry_point = max(max(tmin.x, tmin.y), tmin.z);
-    let exit_point = min(min(tmax.x, tmax.y), tmax.z);
-
-    return vec2<f32>(
-        max(entry_point, 0.0),
-        max(exit_point, 0.0)
-    );
-}
-
 @compute @workgroup_size(16, 16, 1)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let output_dims = textureDimensions(output_texture);
@@ -0,0 +1,16 @@
+// Progressive refinement: pixels are the average of the frames rendered since the view last
+// changed, see `GpuAccumulation`.
+
+#include "base_bindings.wgsl"
+
+// Adds this frame's color of a pixel to its average and returns the average
+fn accumulate(pixel: vec2<u32>, output_dims: vec2<u32>, color: vec4<f32>) -> vec4<f32> {
+    let index = pixel.y * output_dims.x + pixel.x;
+    var average = color;
+    if accumulation_info.accumulated_frames > 0u {
+        let previous = accumulation[index];
+        average = previous + (color - previous) / f32(accumulation_info.accumulated_frames + 1u);
+    }
+    accumulation[index] = average;
+    return average;
+}
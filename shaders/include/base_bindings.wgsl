@@ -0,0 +1,93 @@
+// Bind groups 0 and 1, created by `BaseDemo` for every demo: the camera, the render
+// parameters, the clipping and the lights, then the output textures and the accumulated frames. The
+// output is HDR, colors above 1 are kept until the render pipeline tone maps them.
+
+#include "camera.wgsl"
+
+struct Parameters {
+  density_threshold: f32,
+  use_cone_importance_check: u32,
+  use_importance_coloring: u32,
+  use_opacity: u32,
+  use_importance_rendering: u32,
+  use_gaussian_smoothing: u32,
+  importance_check_ahead_steps: u32,
+  raymarching_step_size: f32,
+  use_transfer_function_2d: u32,
+  use_precomputed_gradients: u32,
+  // Gradient magnitude, in density change per voxel, at the top of the 2D transfer function
+  max_gradient_magnitude: f32,
+  use_empty_space_skipping: u32,
+  render_mode: u32,
+  isosurface_count: u32,
+  show_slices: u32,
+  use_slice_transfer_function: u32,
+  // Sorted by increasing density
+  isovalues: vec4<f32>,
+  isosurface_colors: array<vec4<f32>, 4>,
+  // Where the slices cross, in volume coordinates
+  probe_position: vec3<f32>,
+  show_slice_segments: u32,
+  // Start the rays at a random fraction of a step
+  use_jittering: u32,
+}
+
+const RENDER_MODE_COMPOSITE: u32 = 0u;
+const RENDER_MODE_MIP: u32 = 1u;
+const RENDER_MODE_MINIP: u32 = 2u;
+const RENDER_MODE_AVERAGE: u32 = 3u;
+const RENDER_MODE_IMPORTANCE_MIP: u32 = 4u;
+const RENDER_MODE_ISOSURFACE: u32 = 5u;
+
+// In volume coordinates. Planes are a unit normal and an offset from the volume's center,
+// they remove the side their normal points to. The slab adds two planes after the others.
+struct Clipping {
+    box_min: vec3<f32>,
+    plane_count: u32,
+    box_max: vec3<f32>,
+    planes: array<vec4<f32>, 6>,
+}
+
+// Blinn-Phong lights and material, in volume coordinates
+struct Lighting {
+    // xyz is the direction the light comes from or its position, w is 0 for directional
+    // lights and 1 for point lights
+    light_vectors: array<vec4<f32>, 4>,
+    // Color times intensity
+    light_colors: array<vec4<f32>, 4>,
+    light_count: u32,
+    // Adds a white light coming from the camera
+    headlight: u32,
+    ambient: f32,
+    diffuse: f32,
+    specular: f32,
+    shininess: f32,
+}
+
+struct AccumulationInfo {
+    frame_index: u32,
+    // Frames in `accumulation`, 0 when this frame starts a new average
+    accumulated_frames: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniforms;
+@group(0) @binding(1)
+var<uniform> parameters: Parameters;
+@group(0) @binding(2)
+var<uniform> clipping: Clipping;
+@group(0) @binding(3)
+var<uniform> lighting: Lighting;
+// Tiled over the output, see `ray_jitter`
+@group(0) @binding(4)
+var blue_noise: texture_2d<f32>;
+
+@group(1) @binding(0)
+var output_texture: texture_storage_2d<rgba16float, write>;
+@group(1) @binding(1)
+var debug_texture: texture_storage_2d<rgba16float, write>;
+// Average of the frames since the view last changed, a pixel after the other
+@group(1) @binding(2)
+var<storage, read_write> accumulation: array<vec4<f32>>;
+@group(1) @binding(3)
+var<uniform> accumulation_info: AccumulationInfo;
@@ -0,0 +1,6 @@
+struct CameraUniforms {
+    view_matrix: mat4x4<f32>,
+    projection_matrix: mat4x4<f32>,
+    inverse_view_proj: mat4x4<f32>,
+    camera_position: vec3<f32>,
+}
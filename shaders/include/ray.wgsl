@@ -0,0 +1,76 @@
+// Rays through the volume, in volume coordinates where its bounding box spans 0..1.
+// With CLIPPING defined, rays also stop at the clip box and the clipping planes.
+
+#include "base_bindings.wgsl"
+
+// Outside of the volume, or removed by the clip box or a clipping plane
+fn is_clipped(pos: vec3<f32>) -> bool {
+#ifdef CLIPPING
+    if any(pos < max(clipping.box_min, vec3<f32>(0.0))) || any(pos > min(clipping.box_max, vec3<f32>(1.0))) {
+        return true;
+    }
+    for (var i = 0u; i < clipping.plane_count; i++) {
+        let plane = clipping.planes[i];
+        if dot(pos - vec3<f32>(0.5), plane.xyz) > plane.w {
+            return true;
+        }
+    }
+    return false;
+#else
+    return any(pos < vec3<f32>(0.0)) || any(pos > vec3<f32>(1.0));
+#endif
+}
+
+// Part of the ray within the volume that the clip box and the clipping planes keep
+fn ray_box_intersection(ray_origin: vec3<f32>, ray_direction: vec3<f32>) -> vec2<f32> {
+#ifdef CLIPPING
+    let box_min = clamp(clipping.box_min, vec3<f32>(0.0), vec3<f32>(1.0));
+    let box_max = clamp(clipping.box_max, vec3<f32>(0.0), vec3<f32>(1.0));
+#else
+    let box_min = vec3<f32>(0.0);
+    let box_max = vec3<f32>(1.0);
+#endif
+
+    let t1 = (box_min - ray_origin) / ray_direction;
+    let t2 = (box_max - ray_origin) / ray_direction;
+
+    let tmin = min(t1, t2);
+    let tmax = max(t1, t2);
+
+    var entry_point = max(max(tmin.x, tmin.y), tmin.z);
+    var exit_point = min(min(tmax.x, tmax.y), tmax.z);
+
+#ifdef CLIPPING
+    for (var i = 0u; i < clipping.plane_count; i++) {
+        let plane = clipping.planes[i];
+        let facing = dot(ray_direction, plane.xyz);
+        let distance = plane.w - dot(ray_origin - vec3<f32>(0.5), plane.xyz);
+        if facing > 0.0 {
+            exit_point = min(exit_point, distance / facing);
+        } else if facing < 0.0 {
+            entry_point = max(entry_point, distance / facing);
+        } else if distance < 0.0 {
+            // Parallel to the plane, on the removed side
+            exit_point = entry_point;
+        }
+    }
+#endif
+
+    return vec2<f32>(
+        max(entry_point, 0.0),
+        max(exit_point, 0.0)
+    );
+}
+
+// Fraction of a step the ray of a pixel starts at, 0 without jittering. The offsets come from
+// a tile of blue noise: neighbouring pixels get very different offsets, and the banding of
+// large steps turns into fine noise that averaging frames removes.
+fn ray_jitter(pixel: vec2<u32>) -> f32 {
+    if parameters.use_jittering == 0 {
+        return 0.0;
+    }
+    let noise = textureLoad(blue_noise, pixel % textureDimensions(blue_noise), 0).r;
+    // A new offset every frame: adding the golden ratio keeps a pixel's offsets evenly spread
+    // over the frames, while every frame stays blue noise. Wrapped to stay precise in an f32.
+    return fract(noise + 0.618034 * f32(accumulation_info.frame_index % 4096u));
+}
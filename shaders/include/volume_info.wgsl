@@ -0,0 +1,7 @@
+struct VolumeInfo {
+    // Size of the volume's bounding box, the largest side is 1
+    extent: vec3<f32>,
+    // Map texture samples to the 0..1 value window the transfer function is indexed with
+    value_scale: f32,
+    value_offset: f32,
+}
@@ -1,12 +1,34 @@
-// Render the input texture to the screen
+// Render the input texture to the screen. It may have another resolution than the screen,
+// with a render scale, and is filtered bilinearly by the sampler. The input is HDR, it's
+// exposed, tone mapped and gamma corrected here.
 
 @group(0) @binding(0)
 var input_texture: texture_2d<f32>;
 @group(0) @binding(1)
 var input_sampler: sampler;
 
+struct ToneMapping {
+    // 2^exposure
+    scale: f32,
+    inverse_gamma: f32,
+    curve: u32,
+}
+
+const TONE_MAPPING_NONE: u32 = 0u;
+const TONE_MAPPING_REINHARD: u32 = 1u;
+const TONE_MAPPING_ACES: u32 = 2u;
+
+@group(1) @binding(0)
+var<uniform> tone_mapping: ToneMapping;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // 0..1 over the input texture, from the top left
+    @location(0) uv: vec2<f32>,
+}
+
 @vertex
-fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
 
   // Draw a full-screen quad
   //
@@ -33,11 +55,32 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<
         vec2<f32>(-1.0, 1.0)
     );
 
-    return vec4<f32>(positions[vertex_index], 0.0, 1.0);
+    let position = positions[vertex_index];
+    var out: VertexOutput;
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    out.uv = vec2<f32>(position.x + 1.0, 1.0 - position.y) * 0.5;
+    return out;
+}
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    return (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14);
 }
 
 @fragment
-fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
-    let uv = frag_coord.xy / vec2<f32>(textureDimensions(input_texture));
-    return textureSample(input_texture, input_sampler, uv);
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let input = textureSample(input_texture, input_sampler, in.uv);
+    var color = max(input.rgb * tone_mapping.scale, vec3<f32>(0.0));
+    switch tone_mapping.curve {
+        case TONE_MAPPING_REINHARD: {
+            color = color / (1.0 + color);
+        }
+        case TONE_MAPPING_ACES: {
+            color = aces(color);
+        }
+        default: {}
+    }
+    color = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+    color = pow(color, vec3<f32>(tone_mapping.inverse_gamma));
+    return vec4<f32>(color, input.a);
 }
@@ -1,4 +1,20 @@
-use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use cgmath::Point3;
+use clap::{
+    builder::{PossibleValue, PossibleValuesParser, TypedValueParser},
+    Args, Parser, Subcommand,
+};
+use color_eyre::eyre::eyre;
+
+use crate::{
+    clipping::{ClipBox, ClipPlane},
+    demos::{default_demo, find_demo, DemoEntry, DEMOS},
+    gpu_resources::gradients::GradientFilter,
+    state::{Isosurface, RenderMode, StateParameters, ToneMapping},
+    volume::{Rescale, ValueWindow},
+    Result,
+};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -9,12 +25,36 @@ struct RawArgs {
     /// Enable debug logging
     #[clap(short, long, default_value = "false")]
     debug: bool,
+    /// JSON transfer function preset to start with
+    #[clap(long, global = true)]
+    transfer_function: Option<PathBuf>,
+    /// Volume to open, NRRD or raw named like `<name>_<x>x<y>x<z>_<type>.raw`. The teapot
+    /// from `assets/` is opened when missing.
+    #[clap(long, global = true)]
+    volume: Option<PathBuf>,
+    /// Segment labels of the volume, NRRD or one byte per voxel. The segments are described
+    /// by the JSON file with the same name.
+    #[clap(long, global = true)]
+    labels: Option<PathBuf>,
+    /// Maps the stored voxels to real values, as slope,intercept. Read from the NRRD header
+    /// when missing.
+    #[clap(long, global = true, allow_hyphen_values = true, value_parser = parse_rescale)]
+    rescale: Option<Rescale>,
+    /// Real values mapped to the transfer function's 0..=1 range, as min,max. The volume's
+    /// value range when missing.
+    #[clap(long, global = true, allow_hyphen_values = true, value_parser = parse_window)]
+    window: Option<ValueWindow>,
 }
 
 #[derive(Debug)]
 pub(crate) struct ParsedArgs {
     pub command: Command,
     pub log_level: tracing::Level,
+    pub transfer_function: Option<PathBuf>,
+    pub volume: Option<PathBuf>,
+    pub labels: Option<PathBuf>,
+    pub rescale: Option<Rescale>,
+    pub window: Option<ValueWindow>,
 }
 
 impl ParsedArgs {
@@ -28,29 +68,355 @@ impl ParsedArgs {
         ParsedArgs {
             command: args.command.unwrap_or_default(),
             log_level,
+            transfer_function: args.transfer_function,
+            volume: args.volume,
+            labels: args.labels,
+            rescale: args.rescale,
+            window: args.window,
         }
     }
 }
 
+/// `a,b`
+fn parse_pair(value: &str) -> Result<(f32, f32)> {
+    match value.split(',').map(str::trim).collect::<Vec<_>>()[..] {
+        [a, b] => Ok((a.parse()?, b.parse()?)),
+        _ => Err(eyre!(
+            "Expected 2 values separated by a comma, got {:?}",
+            value
+        )),
+    }
+}
+
+fn parse_rescale(value: &str) -> std::result::Result<Rescale, String> {
+    parse_pair(value)
+        .and_then(|(slope, intercept)| Rescale::new(slope, intercept))
+        .map_err(|e| e.to_string())
+}
+
+fn parse_window(value: &str) -> std::result::Result<ValueWindow, String> {
+    parse_pair(value)
+        .and_then(|(min, max)| {
+            let window = ValueWindow::new(min, max);
+            window.validate()?;
+            Ok(window)
+        })
+        .map_err(|e| e.to_string())
+}
+
 #[derive(Subcommand, Clone, Debug)]
 pub enum Command {
-    /// Run the demo
-    #[clap(subcommand)]
-    Run(Demo),
+    /// Run a demo
+    Run(RunArgs),
+
+    /// Run benchmarks on a demo
+    Benchmark(BenchmarkArgs),
+
+    /// Render a single frame offscreen and save it as an image
+    Render(Box<RenderArgs>),
 
-    /// Run benchmarks on all demos
-    Benchmark,
+    /// Check every shader against the bind group layouts and uniform structs it's used with,
+    /// without a GPU
+    CheckShaders,
 }
 
 impl Default for Command {
     fn default() -> Self {
-        Command::Run(Demo::default())
+        Command::Run(RunArgs::default())
     }
 }
 
-#[derive(Subcommand, Debug, Default, Clone)]
-pub enum Demo {
-    /// A simple demo
-    #[default]
-    Simple,
+#[derive(Args, Debug, Clone)]
+pub struct RunArgs {
+    /// Demo to run
+    #[clap(value_parser = demo_parser(), default_value = default_demo().name)]
+    pub demo: &'static DemoEntry,
+}
+
+impl Default for RunArgs {
+    fn default() -> Self {
+        Self {
+            demo: default_demo(),
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct BenchmarkArgs {
+    /// Demo to benchmark
+    #[clap(long, value_parser = demo_parser(), default_value = default_demo().name)]
+    pub demo: &'static DemoEntry,
+}
+
+/// Names of the registered demos, listed with their descriptions in the help
+fn demo_parser() -> impl TypedValueParser<Value = &'static DemoEntry> {
+    PossibleValuesParser::new(
+        DEMOS
+            .iter()
+            .map(|demo| PossibleValue::new(demo.name).help(demo.description)),
+    )
+    .map(|name| find_demo(&name).expect("only registered demos are accepted"))
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RenderArgs {
+    /// Demo to render
+    #[clap(long, value_parser = demo_parser(), default_value = default_demo().name)]
+    pub demo: &'static DemoEntry,
+    /// Where to save the rendered image
+    #[clap(short, long, default_value = "render.png")]
+    pub output: PathBuf,
+    /// Width of the rendered image
+    #[clap(long, default_value = "1024")]
+    pub width: u32,
+    /// Height of the rendered image
+    #[clap(long, default_value = "768")]
+    pub height: u32,
+    /// JSON file with the render parameters, the flags below take precedence
+    #[clap(short, long)]
+    pub parameters: Option<PathBuf>,
+    /// Camera position, as x,y,z
+    #[clap(long, value_delimiter = ',')]
+    pub camera_position: Option<Vec<f32>>,
+    #[clap(long)]
+    pub render_mode: Option<RenderMode>,
+    /// Densities of the surfaces drawn by the isosurface render mode, as a,b,..
+    #[clap(long, value_delimiter = ',')]
+    pub isovalues: Option<Vec<f32>>,
+    /// Keeps the inside of a box, as min_x,min_y,min_z,max_x,max_y,max_z in volume coordinates
+    #[clap(long, value_delimiter = ',')]
+    pub clip_box: Option<Vec<f32>>,
+    /// Removes the side a plane's normal points to, as normal_x,normal_y,normal_z,offset
+    /// with the offset from the center of the volume. Can be repeated.
+    #[clap(long, value_delimiter = ',')]
+    pub clip_plane: Vec<f32>,
+    /// Only keeps a slab of this thickness, in volume coordinates. Composited or projected
+    /// depending on the render mode.
+    #[clap(long)]
+    pub slab_thickness: Option<f32>,
+    /// Middle plane of the slab, as normal_x,normal_y,normal_z,offset. The slab faces the
+    /// camera through the center of the volume otherwise.
+    #[clap(long, value_delimiter = ',')]
+    pub slab_plane: Option<Vec<f32>>,
+    /// Add a white light coming from the camera. Other lights and the material are set in the
+    /// parameters file.
+    #[clap(long)]
+    pub headlight: Option<bool>,
+    /// Show axial, coronal and sagittal slices through the probe next to the 3D view
+    #[clap(long)]
+    pub show_slices: Option<bool>,
+    /// Where the slices cross, as x,y,z in volume coordinates
+    #[clap(long, value_delimiter = ',')]
+    pub probe_position: Option<Vec<f32>>,
+    #[clap(long)]
+    pub density_threshold: Option<f32>,
+    #[clap(long)]
+    pub raymarching_step_size: Option<f32>,
+    #[clap(long)]
+    pub importance_check_ahead_steps: Option<u32>,
+    #[clap(long)]
+    pub use_opacity: Option<bool>,
+    #[clap(long)]
+    pub use_importance_coloring: Option<bool>,
+    #[clap(long)]
+    pub use_importance_rendering: Option<bool>,
+    #[clap(long)]
+    pub use_cone_importance_check: Option<bool>,
+    #[clap(long)]
+    pub use_gaussian_smoothing: Option<bool>,
+    #[clap(long)]
+    pub use_transfer_function_2d: Option<bool>,
+    #[clap(long)]
+    pub use_precomputed_gradients: Option<bool>,
+    #[clap(long)]
+    pub gradient_filter: Option<GradientFilter>,
+    #[clap(long)]
+    pub use_empty_space_skipping: Option<bool>,
+    /// Start every ray at a random fraction of a step, use with --accumulate-frames
+    #[clap(long)]
+    pub use_jittering: Option<bool>,
+    /// Curve bringing colors above 1 to the display's range, not applied to EXR images
+    #[clap(long)]
+    pub tone_mapping: Option<ToneMapping>,
+    /// In stops, applied before the tone mapping
+    #[clap(long, allow_hyphen_values = true)]
+    pub exposure: Option<f32>,
+    /// Applied after the tone mapping
+    #[clap(long)]
+    pub gamma: Option<f32>,
+    /// Frames averaged into the image. With jittering, they replace the banding of large
+    /// steps with a smooth image.
+    #[clap(long, default_value = "1")]
+    pub accumulate_frames: u32,
+}
+
+impl RenderArgs {
+    /// Parameters from the `--parameters` file (or the defaults), overridden by the flags
+    pub fn state_parameters(&self) -> Result<StateParameters> {
+        let mut parameters = match &self.parameters {
+            Some(path) => serde_json::from_slice(&std::fs::read(path)?)?,
+            None => StateParameters::default(),
+        };
+
+        match self.camera_position.as_deref() {
+            Some(&[x, y, z]) => parameters.camera_position = Point3::new(x, y, z),
+            Some(p) => return Err(eyre!("Camera position needs 3 values, got {:?}", p)),
+            None => {}
+        }
+        if let Some(v) = self.render_mode {
+            parameters.render_mode = v;
+        }
+        if let Some(values) = &self.isovalues {
+            parameters.isosurfaces = values
+                .iter()
+                .enumerate()
+                .map(|(i, &value)| Isosurface::new(value, i))
+                .collect();
+        }
+        match self.clip_box.as_deref() {
+            Some(&[min_x, min_y, min_z, max_x, max_y, max_z]) => {
+                parameters.clipping.clip_box = ClipBox {
+                    min: [min_x, min_y, min_z],
+                    max: [max_x, max_y, max_z],
+                };
+                parameters.clipping.use_clip_box = true;
+            }
+            Some(b) => return Err(eyre!("Clip box needs 6 values, got {:?}", b)),
+            None => {}
+        }
+        if !self.clip_plane.is_empty() {
+            if !self.clip_plane.len().is_multiple_of(4) {
+                return Err(eyre!(
+                    "Clipping planes need 4 values each, got {:?}",
+                    self.clip_plane
+                ));
+            }
+            parameters.clipping.planes = self
+                .clip_plane
+                .chunks_exact(4)
+                .map(|p| ClipPlane {
+                    normal: [p[0], p[1], p[2]],
+                    offset: p[3],
+                    enabled: true,
+                })
+                .collect();
+        }
+        if let Some(v) = self.slab_thickness {
+            parameters.clipping.slab.thickness = v;
+            parameters.clipping.slab.enabled = true;
+        }
+        match self.slab_plane.as_deref() {
+            Some(&[x, y, z, offset]) => {
+                let slab = &mut parameters.clipping.slab;
+                slab.normal = [x, y, z];
+                slab.offset = offset;
+                slab.follow_camera = false;
+                slab.enabled = true;
+            }
+            Some(p) => return Err(eyre!("Slab plane needs 4 values, got {:?}", p)),
+            None => {}
+        }
+        if let Some(v) = self.show_slices {
+            parameters.slices.enabled = v;
+        }
+        match self.probe_position.as_deref() {
+            Some(&[x, y, z]) => parameters.slices.probe = [x, y, z],
+            Some(p) => return Err(eyre!("Probe position needs 3 values, got {:?}", p)),
+            None => {}
+        }
+        if let Some(v) = self.density_threshold {
+            parameters.density_trheshold = v;
+        }
+        if let Some(v) = self.raymarching_step_size {
+            parameters.raymarching_step_size = v;
+        }
+        if let Some(v) = self.importance_check_ahead_steps {
+            parameters.importance_check_ahead_steps = v;
+        }
+        if let Some(v) = self.use_opacity {
+            parameters.use_opacity = v;
+        }
+        if let Some(v) = self.use_importance_coloring {
+            parameters.use_importance_coloring = v;
+        }
+        if let Some(v) = self.use_importance_rendering {
+            parameters.use_importance_rendering = v;
+        }
+        if let Some(v) = self.use_cone_importance_check {
+            parameters.use_cone_importance_check = v;
+        }
+        if let Some(v) = self.use_gaussian_smoothing {
+            parameters.use_gaussian_smoothing = v;
+        }
+        if let Some(v) = self.use_transfer_function_2d {
+            parameters.use_transfer_function_2d = v;
+        }
+        if let Some(v) = self.use_precomputed_gradients {
+            parameters.use_precomputed_gradients = v;
+        }
+        if let Some(v) = self.gradient_filter {
+            parameters.gradient_filter = v;
+        }
+        if let Some(v) = self.use_empty_space_skipping {
+            parameters.use_empty_space_skipping = v;
+        }
+        if let Some(v) = self.headlight {
+            parameters.lighting.headlight = v;
+        }
+        if let Some(v) = self.use_jittering {
+            parameters.use_jittering = v;
+        }
+        if let Some(v) = self.tone_mapping {
+            parameters.tone_mapping = v;
+        }
+        if let Some(v) = self.exposure {
+            parameters.exposure = v;
+        }
+        if let Some(v) = self.gamma {
+            parameters.gamma = v;
+        }
+        // Renders are at the requested size, the scale only keeps a window responsive
+        parameters.render_scale = 1.0;
+        parameters.dynamic_resolution = false;
+
+        Ok(parameters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+
+    #[test]
+    fn renders_ignore_the_render_scale() {
+        let path =
+            std::env::temp_dir().join(format!("volym_render_scale_{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{ "render_scale": 0.5, "dynamic_resolution": true }"#,
+        )
+        .unwrap();
+        let args = RawArgs::try_parse_from([
+            "volym",
+            "render",
+            "--width",
+            "640",
+            "--height",
+            "480",
+            "--parameters",
+            path.to_str().unwrap(),
+        ]);
+        let Some(Command::Render(args)) = args.unwrap().command else {
+            panic!("Expected the render command");
+        };
+        let parameters = args.state_parameters();
+        std::fs::remove_file(&path).unwrap();
+        let parameters = parameters.unwrap();
+
+        assert_eq!(parameters.render_scale, 1.0);
+        assert!(!parameters.dynamic_resolution);
+        let state = State::with_parameters(640.0 / 480.0, parameters).unwrap();
+        assert_eq!(state.output_size((args.width, args.height)), (640, 480));
+    }
 }
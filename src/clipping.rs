@@ -0,0 +1,269 @@
+/// Clipping planes and an axis aligned clip box, to cut into the volume. Both are given in
+/// volume coordinates, where the volume's bounding box spans 0..=1 on every axis.
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+
+/// Planes that can be drawn at once
+pub const MAX_CLIP_PLANES: usize = 4;
+/// The slab is bounded by two clipping planes on top of [`MAX_CLIP_PLANES`]
+pub const SLAB_PLANES: usize = 2;
+
+/// Removes the side of the plane its normal points to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ClipPlane {
+    pub normal: [f32; 3],
+    /// Signed distance of the plane from the center of the volume, along the normal
+    pub offset: f32,
+    pub enabled: bool,
+}
+
+impl Default for ClipPlane {
+    fn default() -> Self {
+        Self {
+            normal: [1.0, 0.0, 0.0],
+            offset: 0.0,
+            enabled: true,
+        }
+    }
+}
+
+impl ClipPlane {
+    /// `xyz` is the unit normal, `w` the offset. Disabled or degenerate planes keep everything.
+    pub fn to_vec4(self) -> [f32; 4] {
+        let [x, y, z] = self.normal;
+        let length = (x * x + y * y + z * z).sqrt();
+        if !self.enabled || length == 0.0 {
+            return [0.0, 0.0, 0.0, 1.0];
+        }
+        [x / length, y / length, z / length, self.offset]
+    }
+}
+
+/// Only the inside of the box is kept
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ClipBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Default for ClipBox {
+    fn default() -> Self {
+        Self {
+            min: [0.0; 3],
+            max: [1.0; 3],
+        }
+    }
+}
+
+/// Only keeps what lies between two parallel planes, the render mode decides whether the
+/// slab is composited or projected
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Slab {
+    pub enabled: bool,
+    /// Face the camera, the normal is the viewing direction
+    pub follow_camera: bool,
+    pub normal: [f32; 3],
+    /// Signed distance of the slab's middle from the center of the volume, along the normal
+    pub offset: f32,
+    /// Distance between the two planes
+    pub thickness: f32,
+}
+
+impl Default for Slab {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            follow_camera: true,
+            normal: [0.0, 0.0, 1.0],
+            offset: 0.0,
+            thickness: 0.1,
+        }
+    }
+}
+
+impl Slab {
+    /// The planes bounding the slab, for a volume whose bounding box has the size `extent`
+    pub fn planes(&self, camera: &Camera, extent: [f32; 3]) -> [ClipPlane; SLAB_PLANES] {
+        let normal = if self.follow_camera {
+            // Volume coordinates stretch the bounding box to a cube, which tilts the
+            // planes facing the camera unless the normal is scaled with it
+            let direction = (camera.target - camera.position).normalize();
+            [
+                direction.x * extent[0],
+                direction.y * extent[1],
+                direction.z * extent[2],
+            ]
+        } else {
+            self.normal
+        };
+        let [x, y, z] = normal;
+        let length = (x * x + y * y + z * z).sqrt();
+        if !self.enabled || length == 0.0 {
+            let keep_all = ClipPlane {
+                enabled: false,
+                ..Default::default()
+            };
+            return [keep_all; SLAB_PLANES];
+        }
+
+        let normal = normal.map(|n| n / length);
+        let half_thickness = self.thickness * 0.5;
+        [
+            ClipPlane {
+                normal,
+                offset: self.offset + half_thickness,
+                enabled: true,
+            },
+            ClipPlane {
+                normal: normal.map(|n| -n),
+                offset: half_thickness - self.offset,
+                enabled: true,
+            },
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Clipping {
+    /// Up to [`MAX_CLIP_PLANES`]
+    pub planes: Vec<ClipPlane>,
+    pub clip_box: ClipBox,
+    pub use_clip_box: bool,
+    pub slab: Slab,
+    /// Draw the clip box over the rendering, with handles to drag its faces
+    #[serde(skip)]
+    pub show_handles: bool,
+}
+
+impl Clipping {
+    /// Clip box bounds actually applied, the whole volume when the box is off
+    pub fn active_box(&self) -> ClipBox {
+        if self.use_clip_box {
+            self.clip_box
+        } else {
+            ClipBox::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Point3;
+
+    use super::*;
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.into_iter().zip(expected) {
+            assert!(
+                (a - e).abs() < 1e-6,
+                "expected {:?}, got {:?}",
+                expected,
+                actual
+            );
+        }
+    }
+
+    /// The slab's two planes keep `normal · p` within `min..=max`, relative to the center
+    fn kept_range(planes: [ClipPlane; SLAB_PLANES]) -> (f32, f32) {
+        let [front, back] = planes;
+        assert_close(front.normal, back.normal.map(|n| -n));
+        (-back.offset, front.offset)
+    }
+
+    #[test]
+    fn normalizes_plane_normals() {
+        let plane = ClipPlane {
+            normal: [0.0, 3.0, 4.0],
+            offset: -0.2,
+            enabled: true,
+        };
+        assert_eq!(plane.to_vec4(), [0.0, 0.6, 0.8, -0.2]);
+
+        let keep_all = [0.0, 0.0, 0.0, 1.0];
+        let disabled = ClipPlane {
+            enabled: false,
+            ..plane
+        };
+        assert_eq!(disabled.to_vec4(), keep_all);
+        let degenerate = ClipPlane {
+            normal: [0.0; 3],
+            ..plane
+        };
+        assert_eq!(degenerate.to_vec4(), keep_all);
+    }
+
+    #[test]
+    fn fixed_slab_is_thickness_wide_around_its_plane() {
+        let camera = Camera::default_with_aspect_and_pos(1.0, Point3::new(0.5, 0.5, 3.5));
+        let slab = Slab {
+            enabled: true,
+            follow_camera: false,
+            normal: [2.0, 0.0, 0.0],
+            offset: 0.1,
+            thickness: 0.3,
+        };
+        let planes = slab.planes(&camera, [1.0, 0.5, 1.0]);
+
+        assert_close(planes[0].normal, [1.0, 0.0, 0.0]);
+        assert!(planes.iter().all(|p| p.enabled));
+        let (min, max) = kept_range(planes);
+        assert!((min - -0.05).abs() < 1e-6, "{}", min);
+        assert!((max - 0.25).abs() < 1e-6, "{}", max);
+    }
+
+    #[test]
+    fn camera_slab_faces_the_camera_through_the_center() {
+        let camera = Camera::default_with_aspect_and_pos(1.0, Point3::new(1.5, 1.5, 0.5));
+        let slab = Slab {
+            enabled: true,
+            thickness: 0.2,
+            ..Slab::default()
+        };
+
+        // Along the viewing direction, stretched with the volume coordinates
+        let planes = slab.planes(&camera, [1.0, 1.0, 1.0]);
+        let diagonal = -(0.5f32.sqrt());
+        assert_close(planes[0].normal, [diagonal, diagonal, 0.0]);
+        assert_eq!(kept_range(planes), (-0.1, 0.1));
+
+        let planes = slab.planes(&camera, [1.0, 0.5, 1.0]);
+        let length = 1.25f32.sqrt();
+        assert_close(planes[0].normal, [-1.0 / length, -0.5 / length, 0.0]);
+        assert_eq!(kept_range(planes), (-0.1, 0.1));
+    }
+
+    #[test]
+    fn disabled_slab_keeps_everything() {
+        let camera = Camera::default_with_aspect_and_pos(1.0, Point3::new(0.5, 0.5, 3.5));
+        let planes = Slab::default().planes(&camera, [1.0; 3]);
+        assert!(planes.iter().all(|p| !p.enabled));
+
+        // A camera on the target has no viewing direction to face
+        let camera = Camera::default_with_aspect_and_pos(1.0, Point3::new(0.5, 0.5, 0.5));
+        let slab = Slab {
+            enabled: true,
+            follow_camera: false,
+            normal: [0.0; 3],
+            ..Slab::default()
+        };
+        assert!(slab.planes(&camera, [1.0; 3]).iter().all(|p| !p.enabled));
+    }
+
+    #[test]
+    fn clip_box_applies_only_when_used() {
+        let mut clipping = Clipping {
+            clip_box: ClipBox {
+                min: [0.1, 0.2, 0.3],
+                max: [0.9, 0.8, 0.7],
+            },
+            ..Clipping::default()
+        };
+        assert_eq!(clipping.active_box(), ClipBox::default());
+        clipping.use_clip_box = true;
+        assert_eq!(clipping.active_box(), clipping.clip_box);
+    }
+}
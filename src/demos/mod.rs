@@ -1,17 +1,83 @@
+use color_eyre::eyre::eyre;
+
 use crate::gpu_context::GpuContext;
 use crate::gpu_resources::texture::GpuWriteTexture2D;
+use crate::shader::{ShaderReload, WatchedShader};
 use crate::state::State;
+use crate::volume::{histogram::Histogram, ValueWindow};
 use crate::Result;
 
 pub mod pipeline;
 pub mod simple;
 
-pub trait ComputeDemo: Sized {
+pub trait ComputeDemo {
     fn init(
         ctx: &GpuContext,
         state: &State,
         output_texture_view: &GpuWriteTexture2D,
-    ) -> Result<Self>;
+    ) -> Result<Self>
+    where
+        Self: Sized;
     fn update_gpu_state(&self, ctx: &GpuContext, state: &State) -> Result<()>;
     fn compute_pass(&self, ctx: &GpuContext) -> Result<()>;
+    /// Switches to a new output texture, after the window was resized. Resources sized after
+    /// the output and the bind groups using them are rebuilt.
+    fn resize(&self, ctx: &GpuContext, output_texture: &GpuWriteTexture2D) -> Result<()>;
+    /// Value distribution of the rendered volume, shown in the GUI
+    fn histogram(&self) -> Option<&Histogram> {
+        None
+    }
+    /// Real values the transfer function is applied to, see
+    /// [`crate::transfer_function::TransferFunction::mapped_to_window`]
+    fn value_window(&self) -> Option<ValueWindow> {
+        None
+    }
+    /// Size of the rendered volume's bounding box, the largest side is 1
+    fn volume_extent(&self) -> [f32; 3] {
+        [1.0; 3]
+    }
+    /// Rebuilds the pipelines whose shader files changed since they were loaded
+    fn reload_shaders(&self, _ctx: &GpuContext) -> Vec<ShaderReload> {
+        Vec::new()
+    }
+    /// The demo's shaders, set up like its pipelines load them, to check them without a device
+    fn shaders() -> Vec<WatchedShader>
+    where
+        Self: Sized;
+}
+
+/// A [`ComputeDemo`] that can be picked by name, from the CLI and the GUI
+#[derive(Debug)]
+pub struct DemoEntry {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub init: fn(&GpuContext, &State, &GpuWriteTexture2D) -> Result<Box<dyn ComputeDemo>>,
+    pub shaders: fn() -> Vec<WatchedShader>,
+}
+
+/// Every demo, the first one runs when none is given
+pub const DEMOS: &[DemoEntry] = &[DemoEntry {
+    name: "simple",
+    description: "Importance driven rendering of a segmented volume",
+    init: init_boxed::<simple::Simple>,
+    shaders: simple::Simple::shaders,
+}];
+
+pub fn default_demo() -> &'static DemoEntry {
+    &DEMOS[0]
+}
+
+pub fn find_demo(name: &str) -> Result<&'static DemoEntry> {
+    DEMOS.iter().find(|demo| demo.name == name).ok_or_else(|| {
+        let names: Vec<_> = DEMOS.iter().map(|demo| demo.name).collect();
+        eyre!("Unknown demo {:?}, expected one of {:?}", name, names)
+    })
+}
+
+fn init_boxed<Demo: ComputeDemo + 'static>(
+    ctx: &GpuContext,
+    state: &State,
+    output_texture: &GpuWriteTexture2D,
+) -> Result<Box<dyn ComputeDemo>> {
+    Ok(Box::new(Demo::init(ctx, state, output_texture)?))
 }
@@ -1,12 +1,17 @@
 /// Compute pipeline that does the he'a avy lifting and outputs to a texture
-use std::path::PathBuf;
+use std::{
+    cell::{Cell, RefCell},
+    path::PathBuf,
+};
 
 use crate::{
     gpu_resources::{
-        camera::GpuCamera, debug_matrix::GpuDebugMatrix, parameters::GpuParameters,
-        texture::GpuWriteTexture2D, BindGroupLayoutEntryUnbound, ToBindGroupEntries,
-        ToBindGroupLayoutEntries, ToGpuResources,
+        accumulation::GpuAccumulation, blue_noise::GpuBlueNoise, camera::GpuCamera,
+        clipping::GpuClipping, debug_matrix::GpuDebugMatrix, lights::GpuLights,
+        parameters::GpuParameters, texture::GpuWriteTexture2D, BindGroupLayoutEntryUnbound,
+        ToBindGroupEntries, ToBindGroupLayoutEntries, ToGpuResources,
     },
+    shader::{catch_validation_errors, ShaderReload, WatchedShader},
     state::State,
     Result,
 };
@@ -18,48 +23,79 @@ use crate::gpu_context::GpuContext;
 
 #[derive(Debug)]
 pub struct DemoPipeline {
-    pub pipeline: wgpu::ComputePipeline,
+    shader: WatchedShader,
+    layout: wgpu::PipelineLayout,
+    /// Replaced when the shader changes and still builds
+    pipeline: RefCell<wgpu::ComputePipeline>,
 }
 
 pub(crate) struct DemoPipelineConfig<'a> {
-    pub shader_path: PathBuf,
+    pub shader: WatchedShader,
     pub bind_group_layouts: &'a [&'a wgpu::BindGroupLayout],
 }
 
 impl DemoPipeline {
-    pub fn with_config(ctx: &GpuContext, config: &DemoPipelineConfig) -> Result<Self> {
-        let shader_contents = std::fs::read_to_string(&config.shader_path)?;
-        let shader = ctx
-            .device
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some(config.shader_path.to_str().unwrap()),
-                source: wgpu::ShaderSource::Wgsl(shader_contents.into()),
-            });
-        info!("Loaded shader module from {:?}", config.shader_path);
-
-        let pipeline_layout = ctx
+    pub fn with_config(ctx: &GpuContext, config: DemoPipelineConfig) -> Result<Self> {
+        let shader = config.shader;
+        let layout = ctx
             .device
             .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Compute Pipeline Layout"),
                 bind_group_layouts: config.bind_group_layouts,
                 push_constant_ranges: &[],
             });
+        let pipeline = Self::create_pipeline(ctx, &shader, &layout)?;
+
+        Ok(DemoPipeline {
+            shader,
+            layout,
+            pipeline: RefCell::new(pipeline),
+        })
+    }
+
+    fn create_pipeline(
+        ctx: &GpuContext,
+        shader: &WatchedShader,
+        layout: &wgpu::PipelineLayout,
+    ) -> Result<wgpu::ComputePipeline> {
+        let module = shader.load(ctx)?;
+        info!("Loaded shader module from {:?}", shader.path());
 
         info!("Creating compute pipeline");
-        let pipeline = ctx
-            .device
-            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("Compute Pipeline"),
-                layout: Some(&pipeline_layout),
-                module: &shader,
-                entry_point: "main",
-                compilation_options: Default::default(),
-            });
+        catch_validation_errors(ctx, || {
+            ctx.device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some("Compute Pipeline"),
+                    layout: Some(layout),
+                    module: &module,
+                    entry_point: "main",
+                    compilation_options: Default::default(),
+                })
+        })
+    }
 
-        Ok(DemoPipeline { pipeline })
+    /// Rebuilds the pipeline when the shader file changed, a shader that fails to build
+    /// leaves the last good pipeline in place
+    pub fn reload_if_changed(&self, ctx: &GpuContext) -> Option<ShaderReload> {
+        if !self.shader.changed() {
+            return None;
+        }
+        let result = Self::create_pipeline(ctx, &self.shader, &self.layout).map(|pipeline| {
+            self.pipeline.replace(pipeline);
+        });
+        Some(ShaderReload {
+            path: self.shader.path().to_path_buf(),
+            result,
+        })
     }
 
-    pub fn compute_pass(&self, ctx: &GpuContext, bind_groups: &[&wgpu::BindGroup]) {
+    /// Runs the shader once for every pixel of an output of `size`
+    pub fn compute_pass(
+        &self,
+        ctx: &GpuContext,
+        bind_groups: &[&wgpu::BindGroup],
+        size: wgpu::Extent3d,
+    ) {
         let mut encoder = ctx
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -68,28 +104,25 @@ impl DemoPipeline {
 
         // Compute pass
         {
+            let pipeline = self.pipeline.borrow();
             let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
                 label: Some("Compute Pass"),
                 timestamp_writes: None,
             });
-            compute_pass.set_pipeline(self.as_ref());
+            compute_pass.set_pipeline(&pipeline);
 
             for (i, bind_group) in bind_groups.iter().enumerate() {
                 compute_pass.set_bind_group(i as u32, bind_group, &[]);
             }
 
-            // size.width + 15 ensures that any leftover pixels (less than a full workgroup 16x16)
-            // still require an additional workgro
-            compute_pass.dispatch_workgroups(
-                (ctx.size.width + 15) / 16,
-                (ctx.size.height + 15) / 16,
-                1,
-            );
+            // Rounding up gives the leftover pixels (less than a full workgroup 16x16) an
+            // additional workgroup
+            compute_pass.dispatch_workgroups(size.width.div_ceil(16), size.height.div_ceil(16), 1);
             debug!(
                 target = "compute_pass",
                 "dispatch_workgroups: {}, {}, {}",
-                (ctx.size.width + 15) / 16,
-                (ctx.size.height + 15) / 16,
+                size.width.div_ceil(16),
+                size.height.div_ceil(16),
                 1
             );
         }
@@ -102,12 +135,6 @@ impl DemoPipeline {
     }
 }
 
-impl AsRef<wgpu::ComputePipeline> for DemoPipeline {
-    fn as_ref(&self) -> &wgpu::ComputePipeline {
-        &self.pipeline
-    }
-}
-
 // TODO: Should this be a trait? - probably not
 #[derive(Debug)]
 pub struct BaseDemo {
@@ -116,48 +143,77 @@ pub struct BaseDemo {
     // Resources for state
     camera: GpuCamera,
     parameters: GpuParameters,
-    pub debug_matrix: GpuDebugMatrix,
+    clipping: GpuClipping,
+    lights: GpuLights,
+    _blue_noise: GpuBlueNoise,
+    /// Replaced, like the outputs bind group, when the output texture is resized
+    pub debug_matrix: RefCell<GpuDebugMatrix>,
+    accumulation: RefCell<GpuAccumulation>,
     //output_texture: GpuStoreTexture2D,
+    /// Size of the output texture, the compute pass covers it
+    output_size: Cell<wgpu::Extent3d>,
 
     // Bind groups
     base_inputs_group: wgpu::BindGroup,
-    base_outputs_group: wgpu::BindGroup,
+    base_outputs_layout: wgpu::BindGroupLayout,
+    base_outputs_group: RefCell<wgpu::BindGroup>,
     extra_bind_groups: Vec<wgpu::BindGroup>,
 }
 
 #[derive(Debug)]
 pub struct BaseDemoConfig<'a> {
-    pub shader_path: PathBuf,
+    /// Made with [`BaseDemo::shader`]
+    pub shader: WatchedShader,
     pub output_texture: &'a GpuWriteTexture2D,
     pub extra_bind_groups: Vec<wgpu::BindGroup>,
     pub extra_layouts: Vec<BindGroupLayout>,
 }
 
 impl BaseDemo {
+    /// Bind group 0 of every demo
+    const INPUTS_LAYOUT_ENTRIES: &'static [&'static [BindGroupLayoutEntryUnbound]] = &[
+        GpuCamera::BIND_GROUP_LAYOUT_ENTRIES,
+        GpuParameters::BIND_GROUP_LAYOUT_ENTRIES,
+        GpuClipping::BIND_GROUP_LAYOUT_ENTRIES,
+        GpuLights::BIND_GROUP_LAYOUT_ENTRIES,
+        GpuBlueNoise::BIND_GROUP_LAYOUT_ENTRIES,
+    ];
+    /// Bind group 1 of every demo
+    const OUTPUTS_LAYOUT_ENTRIES: &'static [&'static [BindGroupLayoutEntryUnbound]] = &[
+        GpuWriteTexture2D::BIND_GROUP_LAYOUT_ENTRIES,
+        GpuDebugMatrix::BIND_GROUP_LAYOUT_ENTRIES,
+        GpuAccumulation::BIND_GROUP_LAYOUT_ENTRIES,
+    ];
+
+    /// A demo's shader, checked against the base bind groups followed by the demo's own.
+    /// `defines` turns features on for the shader's `#ifdef`s.
+    pub fn shader(
+        path: PathBuf,
+        defines: &[&str],
+        extra_layout_entries: &[&[&[BindGroupLayoutEntryUnbound]]],
+    ) -> WatchedShader {
+        let mut bind_groups = vec![Self::INPUTS_LAYOUT_ENTRIES, Self::OUTPUTS_LAYOUT_ENTRIES];
+        bind_groups.extend_from_slice(extra_layout_entries);
+        WatchedShader::new(path, defines).with_bind_groups(&bind_groups)
+    }
+
     pub fn init(ctx: &GpuContext, state: &State, config: BaseDemoConfig) -> Result<Self> {
         info!("Initializing Simple Demo");
 
         let camera = GpuCamera::new(ctx, state);
         let parameters = GpuParameters::new(ctx, state);
-        let debug_matrix = GpuDebugMatrix::new(ctx, state);
+        let clipping = GpuClipping::new(ctx, state);
+        let lights = GpuLights::new(ctx, state);
+        let blue_noise = GpuBlueNoise::new(ctx)?;
+        let output_size = config.output_texture.texture.size();
+        let debug_matrix = GpuDebugMatrix::new(ctx, output_size);
+        let accumulation = GpuAccumulation::new(ctx, output_size);
 
-        let base_inputs_layout = layout_from_unbound_entries(
-            ctx,
-            "Base Inputs Layout",
-            &[
-                GpuCamera::BIND_GROUP_LAYOUT_ENTRIES,
-                GpuParameters::BIND_GROUP_LAYOUT_ENTRIES,
-            ],
-        );
+        let base_inputs_layout =
+            layout_from_unbound_entries(ctx, "Base Inputs Layout", Self::INPUTS_LAYOUT_ENTRIES);
 
-        let base_outputs_layout = layout_from_unbound_entries(
-            ctx,
-            "Base Outputs Layout",
-            &[
-                GpuWriteTexture2D::BIND_GROUP_LAYOUT_ENTRIES,
-                GpuDebugMatrix::BIND_GROUP_LAYOUT_ENTRIES,
-            ],
-        );
+        let base_outputs_layout =
+            layout_from_unbound_entries(ctx, "Base Outputs Layout", Self::OUTPUTS_LAYOUT_ENTRIES);
 
         let mut bind_group_layouts = vec![&base_inputs_layout, &base_outputs_layout];
         let extra_layouts = &config.extra_layouts;
@@ -169,8 +225,8 @@ impl BaseDemo {
         // Should be created at the BaseDemo level, passing extra's
         let compute_pipeline = DemoPipeline::with_config(
             ctx,
-            &DemoPipelineConfig {
-                shader_path: config.shader_path.clone(),
+            DemoPipelineConfig {
+                shader: config.shader,
                 bind_group_layouts: bind_group_layouts.as_slice(),
             },
         )?;
@@ -179,16 +235,20 @@ impl BaseDemo {
             ctx,
             "Base Inputs Bind Group",
             &base_inputs_layout,
-            &[camera.to_gpu_resources(), parameters.to_gpu_resources()],
+            &[
+                camera.to_gpu_resources(),
+                parameters.to_gpu_resources(),
+                clipping.to_gpu_resources(),
+                lights.to_gpu_resources(),
+                blue_noise.to_gpu_resources(),
+            ],
         );
-        let base_outputs_group = bindgroup_from_resources(
+        let base_outputs_group = Self::outputs_group(
             ctx,
-            "Base Outputs Bind Group",
             &base_outputs_layout,
-            &[
-                config.output_texture.to_gpu_resources(),
-                debug_matrix.to_gpu_resources(),
-            ],
+            config.output_texture,
+            &debug_matrix,
+            &accumulation,
         );
 
         let extra_bind_groups = config.extra_bind_groups;
@@ -197,29 +257,95 @@ impl BaseDemo {
             compute_pipeline,
             camera,
             parameters,
-            debug_matrix,
+            clipping,
+            lights,
+            _blue_noise: blue_noise,
+            debug_matrix: RefCell::new(debug_matrix),
+            accumulation: RefCell::new(accumulation),
             //output_texture: config.output_texture,
+            output_size: Cell::new(output_size),
             base_inputs_group,
-            base_outputs_group,
+            base_outputs_layout,
+            base_outputs_group: RefCell::new(base_outputs_group),
             extra_bind_groups,
         })
     }
 
+    fn outputs_group(
+        ctx: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        output_texture: &GpuWriteTexture2D,
+        debug_matrix: &GpuDebugMatrix,
+        accumulation: &GpuAccumulation,
+    ) -> wgpu::BindGroup {
+        bindgroup_from_resources(
+            ctx,
+            "Base Outputs Bind Group",
+            layout,
+            &[
+                output_texture.to_gpu_resources(),
+                debug_matrix.to_gpu_resources(),
+                accumulation.to_gpu_resources(),
+            ],
+        )
+    }
+
+    /// Renders to a new output texture from now on, recreating the debug matrix and the
+    /// accumulated frames at its size
+    pub fn resize(&self, ctx: &GpuContext, output_texture: &GpuWriteTexture2D) {
+        let output_size = output_texture.texture.size();
+        let debug_matrix = GpuDebugMatrix::new(ctx, output_size);
+        let accumulation = GpuAccumulation::new(ctx, output_size);
+        self.base_outputs_group.replace(Self::outputs_group(
+            ctx,
+            &self.base_outputs_layout,
+            output_texture,
+            &debug_matrix,
+            &accumulation,
+        ));
+        self.debug_matrix.replace(debug_matrix);
+        self.accumulation.replace(accumulation);
+        self.output_size.set(output_size);
+    }
+
+    /// Drops the accumulated frames, for changes the base resources don't see, like a new
+    /// transfer function
+    pub fn reset_accumulation(&self) {
+        self.accumulation.borrow().reset();
+    }
+
     pub fn update_gpu_state(&self, ctx: &GpuContext, state: &State) -> Result<()> {
-        self.camera.update(ctx, state)?;
-        self.parameters.update(ctx, state)?;
+        // Every one is updated, any change starts a new average
+        let camera_changed = self.camera.update(ctx, state)?;
+        let parameters_changed = self.parameters.update(ctx, state)?;
+        let clipping_changed = self.clipping.update(ctx, state)?;
+        let lights_changed = self.lights.update(ctx, state)?;
+        let accumulation = self.accumulation.borrow();
+        if camera_changed || parameters_changed || clipping_changed || lights_changed {
+            accumulation.reset();
+        }
+        accumulation.update(ctx, state.use_accumulation);
         Ok(())
     }
 
+    pub fn reload_shaders(&self, ctx: &GpuContext) -> Option<ShaderReload> {
+        let reload = self.compute_pipeline.reload_if_changed(ctx);
+        if reload.as_ref().is_some_and(|r| r.result.is_ok()) {
+            self.reset_accumulation();
+        }
+        reload
+    }
+
     pub fn compute_pass(&self, ctx: &GpuContext) -> Result<()> {
-        let mut bind_groups = vec![&self.base_inputs_group, &self.base_outputs_group];
+        let base_outputs_group = self.base_outputs_group.borrow();
+        let mut bind_groups = vec![&self.base_inputs_group, &*base_outputs_group];
 
         for bind_group in &self.extra_bind_groups {
             bind_groups.push(bind_group);
         }
 
         self.compute_pipeline
-            .compute_pass(ctx, bind_groups.as_slice());
+            .compute_pass(ctx, bind_groups.as_slice(), self.output_size.get());
 
         Ok(())
     }
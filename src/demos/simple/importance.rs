@@ -1,19 +1,27 @@
 use egui_wgpu::wgpu;
 use std::path::Path;
 
+use color_eyre::eyre::eyre;
 use serde::Deserialize;
 use tracing::info;
 
 use crate::{
     gpu_context::GpuContext,
     gpu_resources::{flip_3d_texture_y, BindGroupLayoutEntryUnbound, FlipMode, ToGpuResources},
+    volume::{
+        nrrd::{is_nrrd, Nrrd},
+        ByteOrder, Volume, VolumeDescriptor, VoxelType,
+    },
     Result,
 };
 
 #[derive(Debug, Deserialize)]
 pub struct SegmentInfo {
+    // Part of the segments file, segments are told apart by `label_value`
+    #[allow(dead_code)]
     pub id: String,
     pub name: String,
+    #[allow(dead_code)]
     pub index: u8,
     pub label_value: u8,
     pub importance: u8,
@@ -35,45 +43,34 @@ impl GpuImportances {
                 multisampled: false,
             },
             count: None,
+            uniform_layout: None,
         },
         BindGroupLayoutEntryUnbound {
             visibility: wgpu::ShaderStages::COMPUTE,
             ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
             count: None,
+            uniform_layout: None,
         },
     ];
     pub fn init(
-        data_path: &Path,
-        info_path: &Path,
+        labels: &Volume,
+        segments: &[SegmentInfo],
         flip_mode: FlipMode,
         ctx: &GpuContext,
     ) -> Result<Self> {
         info!("Loading Importances");
+        let (width, height, depth) = labels.descriptor.dims;
 
         let data = {
-            let data = std::fs::read(data_path)?;
-            let info: Vec<SegmentInfo> = serde_json::from_slice(&std::fs::read(info_path)?)?;
-            let mut data = map_segments_to_importance(data, info);
-
-            // center the volume to be 256x256x256
-            let desired_len = 256 * 256 * 256;
-
-            if data.len() < desired_len {
-                info!(
-                    "Importances' size is {}, which is less than 256x256x256, padding with zeros",
-                    data.len()
-                );
-                data.resize(desired_len, 0);
-            } else {
-                info!(
-                    "Importances' size is {}, which is greater than 256x256x256, truncating",
-                    data.len()
-                );
-                data.truncate(desired_len);
-            }
+            let data = labels.values().map(|label| label as u8).collect();
+            let mut data = map_segments_to_importance(data, segments);
 
             if flip_mode == FlipMode::Y {
-                flip_3d_texture_y(&mut data, (256, 256, 256));
+                flip_3d_texture_y(
+                    &mut data,
+                    (width as usize, height as usize, depth as usize),
+                    1,
+                );
             }
             data
         };
@@ -91,9 +88,9 @@ impl GpuImportances {
         }
 
         let size = wgpu::Extent3d {
-            width: 256,
-            height: 256,
-            depth_or_array_layers: 256,
+            width,
+            height,
+            depth_or_array_layers: depth,
         };
 
         let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
@@ -113,8 +110,8 @@ impl GpuImportances {
             &data,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(256),
-                rows_per_image: Some(256),
+                bytes_per_row: Some(width),
+                rows_per_image: Some(height),
             },
             size,
         );
@@ -138,14 +135,62 @@ impl GpuImportances {
 }
 
 impl ToGpuResources for GpuImportances {
-    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource> {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
         vec![
             wgpu::BindingResource::TextureView(&self.texture_view),
             wgpu::BindingResource::Sampler(&self.sampler),
         ]
     }
 }
-fn map_segments_to_importance(data: Vec<u8>, info: Vec<SegmentInfo>) -> Vec<u8> {
+
+/// Label volume of the segments, with the dimensions of the volume they segment
+pub fn load_labels(data_path: &Path, descriptor: VolumeDescriptor) -> Result<Volume> {
+    // Raw labels are one byte per voxel, no matter the type of the volume they segment
+    let labels = if is_nrrd(data_path) {
+        let nrrd = Nrrd::from_file(data_path)?;
+        for (key, value) in nrrd
+            .key_values
+            .iter()
+            .filter(|(k, _)| k.starts_with("Segment"))
+        {
+            info!("{}: {}", key, value);
+        }
+        nrrd.volume
+    } else {
+        let descriptor = VolumeDescriptor {
+            voxel_type: VoxelType::Uint8,
+            ..descriptor
+        };
+        Volume::from_raw_file(data_path, descriptor)?
+    };
+    if labels.descriptor.dims != descriptor.dims {
+        return Err(eyre!(
+            "Importances have dimensions {:?}, but the volume has {:?}",
+            labels.descriptor.dims,
+            descriptor.dims
+        ));
+    }
+    Ok(labels)
+}
+
+/// Labels of a volume without segments, every voxel is background
+pub fn empty_labels(descriptor: VolumeDescriptor) -> Volume {
+    let descriptor = VolumeDescriptor {
+        voxel_type: VoxelType::Uint8,
+        byte_order: ByteOrder::Little,
+        ..descriptor
+    };
+    Volume {
+        data: vec![0; descriptor.len_in_bytes()],
+        descriptor,
+    }
+}
+
+pub fn load_segment_info(info_path: &Path) -> Result<Vec<SegmentInfo>> {
+    Ok(serde_json::from_slice(&std::fs::read(info_path)?)?)
+}
+
+fn map_segments_to_importance(data: Vec<u8>, info: &[SegmentInfo]) -> Vec<u8> {
     // map each byte of data - which corresponds to label_value - to it's importance
 
     data.into_iter()
@@ -1,17 +1,26 @@
-use std::path::Path;
+use std::{
+    cell::{Cell, RefCell},
+    path::Path,
+};
 
 use importance::GpuImportances;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::{
     demos::pipeline::{layout_from_unbound_entries, BaseDemoConfig},
     gpu_context::GpuContext,
     gpu_resources::{
-        texture::GpuWriteTexture2D, transfer_function::GPUTransferFunction, volume::GpuVolume,
-        FlipMode, ToGpuResources,
+        gradients::GpuGradients,
+        occupancy::GpuOccupancyGrid,
+        texture::GpuWriteTexture2D,
+        transfer_function::{GPUTransferFunction, GPUTransferFunction2D},
+        volume::GpuVolume,
+        BindGroupLayoutEntryUnbound, FlipMode, ToGpuResources,
     },
-    state::State,
-    transfer_function::TransferFunction,
+    shader::{ShaderReload, WatchedShader},
+    state::{RenderMode, State},
+    transfer_function::{two_dimensional::TransferFunction2D, TransferFunction},
+    volume::{histogram::Histogram, ValueWindow, Volume},
     Result,
 };
 
@@ -29,7 +38,55 @@ pub struct Simple {
 
     // Resources for state
     _volume: GpuVolume,
-    _transfer_function: GPUTransferFunction,
+    transfer_function: GPUTransferFunction,
+    // Last uploaded transfer function, to know when the GUI edited it
+    uploaded_transfer_function: RefCell<TransferFunction>,
+    transfer_function_2d: GPUTransferFunction2D,
+    uploaded_transfer_function_2d: RefCell<TransferFunction2D>,
+    // Number of uploads of each transfer function, the occupancy grid is classified again
+    // only when the one it was classified with changed
+    transfer_function_uploads: Cell<u64>,
+    transfer_function_2d_uploads: Cell<u64>,
+    _gradients: GpuGradients,
+    occupancy_grid: GpuOccupancyGrid,
+    // What the occupancy grid was last classified with
+    classified_occupancy: RefCell<Option<OccupancyClassification>>,
+    histogram: Histogram,
+    window: ValueWindow,
+    volume_extent: [f32; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct OccupancyClassification {
+    density_threshold: f32,
+    /// Transfer function whose opacities decide what is visible, `None` when the opacity
+    /// doesn't
+    opacities: Option<ClassifyingFunction>,
+}
+
+/// A transfer function, by the number of uploads it had when the grid was classified
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClassifyingFunction {
+    TransferFunction(u64),
+    TransferFunction2D(u64),
+}
+
+impl Simple {
+    /// Bind group 2, the volume and what it's classified with
+    const EXTRA_LAYOUT_ENTRIES: &'static [&'static [BindGroupLayoutEntryUnbound]] = &[
+        GpuVolume::BIND_GROUP_LAYOUT_ENTRIES,
+        GPUTransferFunction::BIND_GROUP_LAYOUT_ENTRIES,
+        GpuImportances::BIND_GROUP_LAYOUT_ENTRIES,
+        GPUTransferFunction2D::BIND_GROUP_LAYOUT_ENTRIES,
+        GpuGradients::BIND_GROUP_LAYOUT_ENTRIES,
+        GpuOccupancyGrid::BIND_GROUP_LAYOUT_ENTRIES,
+    ];
+
+    fn shader() -> WatchedShader {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("shaders/importance_driven_volume_rendering.wgsl");
+        BaseDemo::shader(path, &[], &[Self::EXTRA_LAYOUT_ENTRIES])
+    }
 }
 
 impl ComputeDemo for Simple {
@@ -37,51 +94,81 @@ impl ComputeDemo for Simple {
         info!("Initializing Simple Demo");
 
         // Volume
-        let volume_path = &(format!(
-            //"{}/assets/bonsai_256x256x256_uint8.raw",
-            "{}/assets/boston_teapot_256x256x178_uint8.raw",
-            env!("CARGO_MANIFEST_DIR")
-        ));
-        let volume = GpuVolume::init(volume_path.as_ref(), FlipMode::Y, ctx)?;
-
-        let segments_info_path = &(format!(
-            "{}/assets/boston_teapot_256x256x178_uint8_segments.json",
-            env!("CARGO_MANIFEST_DIR")
-        ));
-
-        let importances_path = &(format!(
-            "{}/assets/boston_teapot_256x256x178_uint8_segments.raw",
-            env!("CARGO_MANIFEST_DIR")
-        ));
-        let importances = GpuImportances::init(
-            importances_path.as_ref(),
-            segments_info_path.as_ref(),
-            FlipMode::Y,
-            ctx,
-        )?;
+        let default_volume = |name: &str| {
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("assets")
+                .join(name)
+        };
+        let (volume_path, labels_path) = match (&state.volume, &state.labels) {
+            (Some(volume), labels) => (volume.clone(), labels.clone()),
+            (None, labels) => (
+                //default_volume("bonsai_256x256x256_uint8.raw"),
+                default_volume("boston_teapot_256x256x178_uint8.raw"),
+                Some(labels.clone().unwrap_or_else(|| {
+                    default_volume("boston_teapot_256x256x178_uint8_segments.raw")
+                })),
+            ),
+        };
+        let mut volume = Volume::from_file(&volume_path)?;
+        if let Some(rescale) = state.rescale {
+            volume.descriptor.rescale = rescale;
+        }
+        let volume_descriptor = volume.descriptor;
+        let window = state
+            .window
+            .unwrap_or_else(|| ValueWindow::from_volume(&volume));
+        info!(
+            "Rescale {:?}, window {:?}",
+            volume_descriptor.rescale, window
+        );
+
+        let (labels, segments) = match labels_path {
+            Some(labels_path) => {
+                let labels = importance::load_labels(&labels_path, volume_descriptor)?;
+                let segments_info_path = labels_path.with_extension("json");
+                let segments = if segments_info_path.exists() {
+                    importance::load_segment_info(&segments_info_path)?
+                } else {
+                    warn!(
+                        "No segment info at {:?}, every segment is unimportant",
+                        segments_info_path
+                    );
+                    Vec::new()
+                };
+                (labels, segments)
+            }
+            None => (importance::empty_labels(volume_descriptor), Vec::new()),
+        };
+
+        let mut histogram = Histogram::new(&volume, window, Some(&labels))?;
+        for segment in &segments {
+            histogram
+                .segment_names
+                .insert(segment.label_value, segment.name.clone());
+        }
+
+        let occupancy_grid = GpuOccupancyGrid::init(&volume, window, FlipMode::Y, ctx);
+        let volume = GpuVolume::init(&volume, window, FlipMode::Y, ctx)?;
+        let importances = GpuImportances::init(&labels, &segments, FlipMode::Y, ctx)?;
+        let gradients = GpuGradients::init(&volume, state.gradient_filter, ctx)?;
 
         // TF
-        let transfer_function = TransferFunction::default();
+        let max_density = window
+            .max_density(&volume_descriptor)
+            .min(ctx.device.limits().max_texture_dimension_1d - 1);
+        let transfer_function = state
+            .transfer_function
+            .clone()
+            .mapped_to_window(window)
+            .with_max_density(max_density);
         let gpu_transfer_function =
             GPUTransferFunction::new_texture_1d_rgbt(&transfer_function, &ctx.device, &ctx.queue);
+        let gpu_transfer_function_2d =
+            GPUTransferFunction2D::new(&state.transfer_function_2d, &ctx.device, &ctx.queue);
 
         // Shader
-        let shader_path = Path::new(
-            &(format!(
-                "{}/shaders/importance_driven_volume_rendering.wgsl",
-                env!("CARGO_MANIFEST_DIR")
-            )),
-        )
-        .to_path_buf();
-        let extra_layout = layout_from_unbound_entries(
-            ctx,
-            "Extra Layout",
-            &[
-                GpuVolume::BIND_GROUP_LAYOUT_ENTRIES,
-                GPUTransferFunction::BIND_GROUP_LAYOUT_ENTRIES,
-                GpuImportances::BIND_GROUP_LAYOUT_ENTRIES,
-            ],
-        );
+        let extra_layout =
+            layout_from_unbound_entries(ctx, "Extra Layout", Self::EXTRA_LAYOUT_ENTRIES);
         let extra_bind_group = bindgroup_from_resources(
             ctx,
             "Extra Bind Group",
@@ -90,11 +177,14 @@ impl ComputeDemo for Simple {
                 volume.to_gpu_resources(),
                 gpu_transfer_function.to_gpu_resources(),
                 importances.to_gpu_resources(),
+                gpu_transfer_function_2d.to_gpu_resources(),
+                gradients.to_gpu_resources(),
+                occupancy_grid.to_gpu_resources(),
             ],
         );
 
         let config = BaseDemoConfig {
-            shader_path,
+            shader: Self::shader(),
             output_texture,
             extra_bind_groups: vec![extra_bind_group],
             extra_layouts: vec![extra_layout],
@@ -105,17 +195,125 @@ impl ComputeDemo for Simple {
         Ok(Self {
             base,
             _volume: volume,
-            _transfer_function: gpu_transfer_function,
+            transfer_function: gpu_transfer_function,
+            uploaded_transfer_function: RefCell::new(transfer_function),
+            transfer_function_2d: gpu_transfer_function_2d,
+            uploaded_transfer_function_2d: RefCell::new(state.transfer_function_2d.clone()),
+            _gradients: gradients,
+            occupancy_grid,
+            transfer_function_uploads: Cell::new(0),
+            transfer_function_2d_uploads: Cell::new(0),
+            classified_occupancy: RefCell::new(None),
+            histogram,
+            window,
+            volume_extent: volume_descriptor.extent(),
         })
     }
 
     fn update_gpu_state(&self, ctx: &GpuContext, state: &State) -> Result<()> {
-        self.base.update_gpu_state(ctx, state)?;
-        Ok(())
+        let mut uploaded = self.uploaded_transfer_function.borrow_mut();
+        if !uploaded.has_same_control_points(&state.transfer_function) {
+            *uploaded = state
+                .transfer_function
+                .clone()
+                .mapped_to_window(self.window)
+                .with_max_density(uploaded.max_density);
+            self.transfer_function.update(&uploaded, &ctx.queue);
+            self.transfer_function_uploads
+                .set(self.transfer_function_uploads.get() + 1);
+            self.base.reset_accumulation();
+        }
+
+        let mut uploaded_2d = self.uploaded_transfer_function_2d.borrow_mut();
+        if uploaded_2d.widgets != state.transfer_function_2d.widgets {
+            *uploaded_2d = state.transfer_function_2d.clone();
+            self.transfer_function_2d.update(&uploaded_2d, &ctx.queue);
+            self.transfer_function_2d_uploads
+                .set(self.transfer_function_2d_uploads.get() + 1);
+            self.base.reset_accumulation();
+        }
+
+        let classification = match state.render_mode {
+            // Cells below every isovalue can't hold a surface
+            RenderMode::Isosurface => OccupancyClassification {
+                density_threshold: state
+                    .isosurfaces
+                    .iter()
+                    .map(|isosurface| isosurface.value)
+                    .fold(1.0, f32::min),
+                opacities: None,
+            },
+            // Projections show every sample above the threshold
+            mode if mode.is_projection() => OccupancyClassification {
+                density_threshold: state.density_threshold,
+                opacities: None,
+            },
+            _ => {
+                // So do importance coloring and first hit rendering
+                let opacities = match (
+                    state.use_opacity && !state.use_importance_coloring,
+                    state.use_transfer_function_2d,
+                ) {
+                    (false, _) => None,
+                    (true, false) => Some(ClassifyingFunction::TransferFunction(
+                        self.transfer_function_uploads.get(),
+                    )),
+                    (true, true) => Some(ClassifyingFunction::TransferFunction2D(
+                        self.transfer_function_2d_uploads.get(),
+                    )),
+                };
+                OccupancyClassification {
+                    density_threshold: state.density_threshold,
+                    opacities,
+                }
+            }
+        };
+        let mut classified = self.classified_occupancy.borrow_mut();
+        if *classified != Some(classification) {
+            // Evaluating the 2D function's widgets for every texel is slow, only done here
+            let opacities = classification.opacities.map(|function| match function {
+                ClassifyingFunction::TransferFunction(_) => uploaded.texel_opacities(),
+                ClassifyingFunction::TransferFunction2D(_) => uploaded_2d.texel_opacities(),
+            });
+            self.occupancy_grid.update(
+                classification.density_threshold,
+                opacities.as_deref(),
+                &ctx.queue,
+            );
+            *classified = Some(classification);
+        }
+
+        // Last, the transfer functions above may have reset the accumulated frames
+        self.base.update_gpu_state(ctx, state)
     }
 
     fn compute_pass(&self, ctx: &GpuContext) -> Result<()> {
         self.base.compute_pass(ctx)?;
         Ok(())
     }
+
+    fn resize(&self, ctx: &GpuContext, output_texture: &GpuWriteTexture2D) -> Result<()> {
+        self.base.resize(ctx, output_texture);
+        Ok(())
+    }
+
+    fn histogram(&self) -> Option<&Histogram> {
+        Some(&self.histogram)
+    }
+
+    fn value_window(&self) -> Option<ValueWindow> {
+        Some(self.window)
+    }
+
+    fn volume_extent(&self) -> [f32; 3] {
+        self.volume_extent
+    }
+
+    fn reload_shaders(&self, ctx: &GpuContext) -> Vec<ShaderReload> {
+        self.base.reload_shaders(ctx).into_iter().collect()
+    }
+
+    fn shaders() -> Vec<WatchedShader> {
+        vec![Self::shader()]
+    }
 }
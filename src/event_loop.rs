@@ -21,19 +21,20 @@ use crate::{
 
 pub trait EventLoopEx {
     type EventLoopUserMsg: std::fmt::Debug;
+    #[allow(clippy::too_many_arguments)]
     fn run_volym(
         &mut self,
         settings: RunSettings,
         ctx: GpuContext,
         state: &mut State,
         render_pipeline: &RenderPipeline,
-        demo: &impl ComputeDemo,
+        demo: &dyn ComputeDemo,
         egui: &mut GuiContext,
         user_event_handler: impl FnMut(
             Self::EventLoopUserMsg,
             &EventLoopWindowTarget<Self::EventLoopUserMsg>,
         ),
-        render_iput_texture: &GpuReadTexture2D,
+        render_input_texture: GpuReadTexture2D,
     ) -> Result<(u32, Duration)>;
 }
 
@@ -46,18 +47,20 @@ impl<T: std::fmt::Debug> EventLoopEx for EventLoop<T> {
         mut ctx: GpuContext,
         state: &mut State,
         render_pipeline: &RenderPipeline,
-        demo: &impl ComputeDemo,
+        demo: &dyn ComputeDemo,
         egui: &mut GuiContext,
         mut user_event_handler: impl FnMut(
             Self::EventLoopUserMsg,
             &EventLoopWindowTarget<Self::EventLoopUserMsg>,
         ),
-        render_input_texture: &GpuReadTexture2D,
+        mut render_input_texture: GpuReadTexture2D,
     ) -> Result<(u32, Duration)> {
-        let mut first_update = Instant::now();
+        let first_update = Instant::now();
         let mut last_update = first_update;
         let mut frame_count: u32 = 0;
         let mut total_frames: u32 = 0;
+        // The loop closure moves what it captures, it counts through this
+        let frames = &mut total_frames;
 
         self.run_on_demand(move |event, control_flow| {
             debug!(target = "Render loop", "received event {:?}", event);
@@ -66,12 +69,16 @@ impl<T: std::fmt::Debug> EventLoopEx for EventLoop<T> {
                     ref event,
                     window_id,
                 } if window_id == ctx.window().id() => {
-                    let is_egui_event = egui.process_input(ctx.window, event);
+                    let is_egui_event = egui.process_input(ctx.window(), event);
                     if is_egui_event {
                         return;
                     }
-                    let is_volym_event =
-                        state.process_input(&ctx, &render_input_texture.texture, event);
+                    let is_volym_event = state.process_input(
+                        &ctx,
+                        render_pipeline,
+                        &render_input_texture.texture,
+                        event,
+                    );
                     if is_volym_event {
                         return;
                     }
@@ -89,19 +96,67 @@ impl<T: std::fmt::Debug> EventLoopEx for EventLoop<T> {
                                 ..
                             } => control_flow.exit(),
                             WindowEvent::Resized(physical_size) => {
+                                // Zero sizes, when minimized, leave the surface as it was.
+                                // The output follows on the next frame.
                                 ctx.resize(*physical_size);
+                                state.camera.aspect =
+                                    ctx.size.width as f32 / ctx.size.height as f32;
                             }
                             WindowEvent::RedrawRequested => {
                                 // queue another redraw
                                 ctx.window().request_redraw();
 
+                                // Pick up edited shaders, a broken one keeps the last good pipeline
+                                let reloads = demo
+                                    .reload_shaders(&ctx)
+                                    .into_iter()
+                                    .chain(render_pipeline.reload_if_changed(&ctx));
+                                for reload in reloads {
+                                    match reload.result {
+                                        Ok(()) => {
+                                            info!("Reloaded {:?}", reload.path);
+                                            state.shader_errors.remove(&reload.path);
+                                        }
+                                        Err(e) => {
+                                            error!("Failed to reload {:?}: {}", reload.path, e);
+                                            state.shader_errors.insert(reload.path, e.to_string());
+                                        }
+                                    }
+                                }
+
                                 // update the state
                                 {
                                     state.update();
-                                    demo.update_gpu_state(&ctx, state).unwrap();
+                                    render_pipeline.update_gpu_state(&ctx, state);
+                                }
+
+                                // After a resize, or a change of render scale
+                                let output_size = state.output_size(ctx.size.into());
+                                let current_size = render_input_texture.texture.size();
+                                if output_size != (current_size.width, current_size.height) {
+                                    match resize_output(&ctx, demo, render_pipeline, output_size) {
+                                        Ok(texture) => render_input_texture = texture,
+                                        Err(e) => error!("Failed to resize the output: {}", e),
+                                    }
                                 }
 
-                                //  compute and render
+                                // A demo that fails to update or compute skips the frame,
+                                // the last output stays on screen under the error
+                                let computed = demo
+                                    .update_gpu_state(&ctx, state)
+                                    .and_then(|()| demo.compute_pass(&ctx));
+                                match computed {
+                                    Ok(()) => state.frame_error = None,
+                                    Err(e) => {
+                                        let e = e.to_string();
+                                        if state.frame_error.as_ref() != Some(&e) {
+                                            error!("Skipping frames: {}", e);
+                                        }
+                                        state.frame_error = Some(e);
+                                    }
+                                }
+
+                                // render
                                 let render_result = {
                                     let screen_descriptor = ScreenDescriptor {
                                         size_in_pixels: [
@@ -111,12 +166,11 @@ impl<T: std::fmt::Debug> EventLoopEx for EventLoop<T> {
                                         pixels_per_point: ctx.window().scale_factor() as f32,
                                     };
 
-                                    let output = ctx.surface.get_current_texture().unwrap();
+                                    let output = ctx.surface().get_current_texture().unwrap();
                                     let view = output
                                         .texture
                                         .create_view(&wgpu::TextureViewDescriptor::default());
 
-                                    demo.compute_pass(&ctx).unwrap();
                                     let r = render_pipeline.render_pass(&ctx, &view);
                                     egui.draw(&ctx, state, &view, screen_descriptor);
 
@@ -124,17 +178,22 @@ impl<T: std::fmt::Debug> EventLoopEx for EventLoop<T> {
                                     // syncs us to the monitor refresh rate.
                                     // https://docs.rs/winit/latest/winit/window/struct.Window.html#platform-specific-2
                                     if settings.refresh_rate_sync {
-                                        ctx.window.pre_present_notify();
+                                        ctx.window().pre_present_notify();
                                     }
 
                                     output.present();
                                     r
                                 };
 
+                                // The window is opened again with the demo picked in the GUI
+                                if state.requested_demo.is_some() {
+                                    control_flow.exit();
+                                }
+
                                 match render_result {
                                     Ok(_) => {
                                         frame_count += 1;
-                                        total_frames += 1;
+                                        *frames += 1;
                                         let now = Instant::now();
                                         if now.duration_since(last_update) >= Duration::from_secs(1)
                                         {
@@ -167,3 +226,18 @@ impl<T: std::fmt::Debug> EventLoopEx for EventLoop<T> {
         return Ok((total_frames, Instant::now().duration_since(first_update)));
     }
 }
+
+/// A new output texture of `size`, the demo renders to it and the render pipeline draws it
+/// from now on
+fn resize_output(
+    ctx: &GpuContext,
+    demo: &dyn ComputeDemo,
+    render_pipeline: &RenderPipeline,
+    size: (u32, u32),
+) -> Result<GpuReadTexture2D> {
+    let output_texture = GpuWriteTexture2D::with_size(ctx, size);
+    demo.resize(ctx, &output_texture)?;
+    let render_input_texture = output_texture.into_read_texture_2d(ctx);
+    render_pipeline.set_input_texture(ctx, &render_input_texture);
+    Ok(render_input_texture)
+}
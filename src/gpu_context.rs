@@ -1,18 +1,23 @@
 // lib.rs
+use color_eyre::eyre::eyre;
 use egui_wgpu::wgpu::{self, TextureFormat};
 use egui_winit::winit;
 use egui_winit::winit::window::Window;
+use tracing::{info, warn};
 
 use crate::Result;
 
 #[derive(Debug)]
 pub struct GpuContext<'a> {
-    pub surface: wgpu::Surface<'a>,
+    /// None when rendering offscreen
+    pub surface: Option<wgpu::Surface<'a>>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
+    /// Describes the render target, also when there is no surface to configure
     pub surface_config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
-    pub window: &'a Window,
+    /// None when rendering offscreen
+    pub window: Option<&'a Window>,
 }
 
 impl<'a> GpuContext<'a> {
@@ -29,10 +34,7 @@ impl<'a> GpuContext<'a> {
             .await
             .unwrap();
 
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor::default(), None)
-            .await
-            .unwrap();
+        let (device, queue) = request_device(&adapter).await?;
 
         let surface_caps = surface.get_capabilities(&adapter);
         let surface_format = TextureFormat::Rgba8Unorm;
@@ -52,8 +54,54 @@ impl<'a> GpuContext<'a> {
         //let pipelines = Pipelines::new_from_demo(&simple::Simple, &device, &config)?;
 
         Ok(Self {
-            window,
-            surface,
+            window: Some(window),
+            surface: Some(surface),
+            device,
+            queue,
+            surface_config,
+            size,
+        })
+    }
+
+    /// Context without a window, for offscreen rendering. Falls back to a software adapter
+    /// when there is no GPU, i.e. on CI machines.
+    pub async fn new_headless(size: winit::dpi::PhysicalSize<u32>) -> Result<GpuContext<'a>> {
+        let instance = wgpu::Instance::default();
+        let mut adapter = None;
+        for force_fallback_adapter in [false, true] {
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    compatible_surface: None,
+                    force_fallback_adapter,
+                })
+                .await;
+            if adapter.is_some() {
+                break;
+            }
+            if !force_fallback_adapter {
+                warn!("No hardware adapter found, trying the fallback adapter");
+            }
+        }
+        let adapter = adapter.ok_or(eyre!("No adapter found for offscreen rendering"))?;
+        info!("Rendering offscreen with {:?}", adapter.get_info());
+
+        let (device, queue) = request_device(&adapter).await?;
+
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: TextureFormat::Rgba8Unorm,
+            width: size.width,
+            height: size.height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+
+        Ok(Self {
+            window: None,
+            surface: None,
             device,
             queue,
             surface_config,
@@ -61,8 +109,14 @@ impl<'a> GpuContext<'a> {
         })
     }
 
+    /// Panics when rendering offscreen
     pub fn window(&self) -> &Window {
-        self.window
+        self.window.expect("GpuContext has no window")
+    }
+
+    /// Panics when rendering offscreen
+    pub fn surface(&self) -> &wgpu::Surface<'a> {
+        self.surface.as_ref().expect("GpuContext has no surface")
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -70,7 +124,25 @@ impl<'a> GpuContext<'a> {
             self.size = new_size;
             self.surface_config.width = new_size.width;
             self.surface_config.height = new_size.height;
-            self.surface.configure(&self.device, &self.surface_config);
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.surface_config);
+            }
         }
     }
 }
+
+async fn request_device(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue)> {
+    // Higher precision volume formats, used when the adapter supports them
+    let optional_features =
+        wgpu::Features::TEXTURE_FORMAT_16BIT_NORM | wgpu::Features::FLOAT32_FILTERABLE;
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                required_features: adapter.features() & optional_features,
+                ..Default::default()
+            },
+            None,
+        )
+        .await?;
+    Ok((device, queue))
+}
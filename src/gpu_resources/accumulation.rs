@@ -0,0 +1,119 @@
+use std::cell::Cell;
+
+use bytemuck::{Pod, Zeroable};
+use egui_wgpu::wgpu::{self, util::DeviceExt};
+
+use crate::gpu_context::GpuContext;
+
+use super::{BindGroupLayoutEntryUnbound, ToGpuResources};
+
+/// Past this many frames the average turns into a moving average, new frames keep a weight
+const MAX_ACCUMULATED_FRAMES: u32 = 1024;
+
+/// Running average of the frames rendered since the view last changed. With jittered rays the
+/// average converges to a render with a much smaller step size.
+#[derive(Debug)]
+pub struct GpuAccumulation {
+    /// A vec4<f32> per output pixel
+    buffer: wgpu::Buffer,
+    uniforms: wgpu::Buffer,
+    frame_index: Cell<u32>,
+    /// Frames in the average, 0 after a reset
+    accumulated_frames: Cell<u32>,
+}
+
+impl GpuAccumulation {
+    pub const BIND_GROUP_LAYOUT_ENTRIES: &[BindGroupLayoutEntryUnbound] = &[
+        BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+            uniform_layout: None,
+        },
+        BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+            uniform_layout: Some(AccumulationUniforms::LAYOUT),
+        },
+    ];
+
+    /// An empty average for an output of `size`
+    pub fn new(ctx: &GpuContext, size: wgpu::Extent3d) -> Self {
+        let buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Accumulation Buffer"),
+            size: (size.width * size.height) as u64 * 16,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let uniforms = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Accumulation Uniforms Buffer"),
+                contents: bytemuck::cast_slice(&[AccumulationUniforms::zeroed()]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        Self {
+            buffer,
+            uniforms,
+            frame_index: Cell::new(0),
+            accumulated_frames: Cell::new(0),
+        }
+    }
+
+    /// Starts a new average with the next frame
+    pub fn reset(&self) {
+        self.accumulated_frames.set(0);
+    }
+
+    /// Uploads the counters for the next frame. Without `accumulate`, the frame replaces the
+    /// average.
+    pub fn update(&self, ctx: &GpuContext, accumulate: bool) {
+        if !accumulate {
+            self.reset();
+        }
+        let uniforms = AccumulationUniforms {
+            frame_index: self.frame_index.get(),
+            accumulated_frames: self.accumulated_frames.get(),
+        };
+        ctx.queue
+            .write_buffer(&self.uniforms, 0, bytemuck::cast_slice(&[uniforms]));
+
+        self.frame_index.set(self.frame_index.get().wrapping_add(1));
+        self.accumulated_frames
+            .set((uniforms.accumulated_frames + 1).min(MAX_ACCUMULATED_FRAMES));
+    }
+}
+
+impl ToGpuResources for GpuAccumulation {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
+        vec![
+            self.buffer.as_entire_binding(),
+            self.uniforms.as_entire_binding(),
+        ]
+    }
+}
+
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct AccumulationUniforms {
+    /// Varies the jitter from frame to frame
+    frame_index: u32,
+    accumulated_frames: u32,
+}
+
+impl AccumulationUniforms {
+    uniform_layout!(AccumulationUniforms {
+        frame_index,
+        accumulated_frames
+    });
+}
@@ -0,0 +1,68 @@
+use egui_wgpu::wgpu::{self, util::DeviceExt};
+
+use crate::{gpu_context::GpuContext, Result};
+
+use super::{BindGroupLayoutEntryUnbound, ToGpuResources};
+
+/// A 64x64 tile of blue noise, made with Ulichney's void-and-cluster method (Gaussian of
+/// sigma 1.5, wrapping around the edges) so that the tile repeats without seams. Every value
+/// from 0 to 255 appears 16 times.
+const BLUE_NOISE_PNG: &[u8] = include_bytes!("../../assets/blue_noise_64.png");
+
+/// Per pixel offsets of the rays, tiled over the output. Neighbouring pixels get offsets that
+/// are far apart, so the error of a jittered frame is high frequency noise.
+#[derive(Debug)]
+pub struct GpuBlueNoise {
+    _texture: wgpu::Texture,
+    texture_view: wgpu::TextureView,
+}
+
+impl GpuBlueNoise {
+    pub const BIND_GROUP_LAYOUT_ENTRIES: &[BindGroupLayoutEntryUnbound] =
+        &[BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+            uniform_layout: None,
+        }];
+
+    pub fn new(ctx: &GpuContext) -> Result<Self> {
+        let noise = image::load_from_memory_with_format(BLUE_NOISE_PNG, image::ImageFormat::Png)?
+            .into_luma8();
+        let texture = ctx.device.create_texture_with_data(
+            &ctx.queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Blue Noise Texture"),
+                size: wgpu::Extent3d {
+                    width: noise.width(),
+                    height: noise.height(),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            noise.as_raw(),
+        );
+        let texture_view = texture.create_view(&Default::default());
+
+        Ok(Self {
+            _texture: texture,
+            texture_view,
+        })
+    }
+}
+
+impl ToGpuResources for GpuBlueNoise {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
+        vec![wgpu::BindingResource::TextureView(&self.texture_view)]
+    }
+}
@@ -1,3 +1,5 @@
+use std::cell::Cell;
+
 use bytemuck::{Pod, Zeroable};
 use cgmath::{Matrix4, SquareMatrix};
 use egui_wgpu::wgpu;
@@ -13,6 +15,7 @@ use super::{BindGroupLayoutEntryUnbound, ToGpuResources};
 #[derive(Debug)]
 pub struct GpuCamera {
     camera_buffer: wgpu::Buffer,
+    uploaded: Cell<CameraUniforms>,
 }
 
 impl GpuCamera {
@@ -25,6 +28,7 @@ impl GpuCamera {
                 min_binding_size: None,
             },
             count: None,
+            uniform_layout: Some(CameraUniforms::LAYOUT),
         }];
     pub fn new(ctx: &GpuContext, state: &State) -> Self {
         let uniforms: CameraUniforms = CameraUniforms::try_from(&state.camera).unwrap();
@@ -36,19 +40,27 @@ impl GpuCamera {
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             });
 
-        Self { camera_buffer }
+        Self {
+            camera_buffer,
+            uploaded: Cell::new(uniforms),
+        }
     }
-    pub fn update(&self, ctx: &GpuContext, state: &State) -> Result<()> {
+    /// Returns whether the camera changed since the last upload
+    pub fn update(&self, ctx: &GpuContext, state: &State) -> Result<bool> {
         let uniforms = CameraUniforms::try_from(&state.camera)?;
+        if bytemuck::bytes_of(&uniforms) == bytemuck::bytes_of(&self.uploaded.get()) {
+            return Ok(false);
+        }
         ctx.queue
             .write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        self.uploaded.set(uniforms);
 
-        Ok(())
+        Ok(true)
     }
 }
 
 impl ToGpuResources for GpuCamera {
-    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource> {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
         vec![self.camera_buffer.as_entire_binding()]
     }
 }
@@ -63,6 +75,15 @@ pub struct CameraUniforms {
     _padding: f32,
 }
 
+impl CameraUniforms {
+    uniform_layout!(CameraUniforms {
+        view_matrix,
+        projection_matrix,
+        inverse_view_proj,
+        camera_position
+    });
+}
+
 impl TryFrom<&Camera> for CameraUniforms {
     type Error = crate::Error;
     fn try_from(camera: &Camera) -> std::result::Result<Self, Self::Error> {
@@ -0,0 +1,119 @@
+use std::cell::Cell;
+
+use bytemuck::{Pod, Zeroable};
+use egui_wgpu::wgpu::{self, util::DeviceExt};
+
+use crate::{
+    clipping::{ClipPlane, MAX_CLIP_PLANES, SLAB_PLANES},
+    gpu_context::GpuContext,
+    state::State,
+    Result,
+};
+
+use super::{BindGroupLayoutEntryUnbound, ToGpuResources};
+
+/// Clip box, clipping planes and the slab's planes, the shader restricts every ray to what
+/// they keep
+#[derive(Debug)]
+pub struct GpuClipping {
+    buffer: wgpu::Buffer,
+    uploaded: Cell<ClippingUniforms>,
+}
+
+impl GpuClipping {
+    pub const BIND_GROUP_LAYOUT_ENTRIES: &[BindGroupLayoutEntryUnbound] =
+        &[BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+            uniform_layout: Some(ClippingUniforms::LAYOUT),
+        }];
+
+    pub fn new(ctx: &GpuContext, state: &State) -> Self {
+        let uniforms = ClippingUniforms::from(state);
+        let buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Clipping Buffer"),
+                contents: bytemuck::cast_slice(&[uniforms]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        Self {
+            buffer,
+            uploaded: Cell::new(uniforms),
+        }
+    }
+
+    /// Returns whether the clipping changed since the last upload
+    pub fn update(&self, ctx: &GpuContext, state: &State) -> Result<bool> {
+        let uniforms = ClippingUniforms::from(state);
+        if bytemuck::bytes_of(&uniforms) == bytemuck::bytes_of(&self.uploaded.get()) {
+            return Ok(false);
+        }
+        ctx.queue
+            .write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        self.uploaded.set(uniforms);
+
+        Ok(true)
+    }
+}
+
+impl ToGpuResources for GpuClipping {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
+        vec![self.buffer.as_entire_binding()]
+    }
+}
+
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+#[repr(C, align(16))]
+struct ClippingUniforms {
+    box_min: [f32; 3],
+    plane_count: u32,
+    box_max: [f32; 3],
+    _padding: u32,
+    planes: [[f32; 4]; MAX_CLIP_PLANES + SLAB_PLANES],
+}
+
+impl ClippingUniforms {
+    uniform_layout!(ClippingUniforms {
+        box_min,
+        plane_count,
+        box_max,
+        planes
+    });
+}
+
+impl From<&State> for ClippingUniforms {
+    fn from(s: &State) -> Self {
+        let clip_box = s.clipping.active_box();
+        let mut planes = [ClipPlane {
+            enabled: false,
+            ..Default::default()
+        }
+        .to_vec4(); MAX_CLIP_PLANES + SLAB_PLANES];
+        let slab_planes = s.clipping.slab.planes(&s.camera, s.volume_extent);
+        let clip_planes = s.clipping.planes.iter().take(MAX_CLIP_PLANES);
+        for (plane, clip_plane) in planes.iter_mut().zip(clip_planes.chain(&slab_planes)) {
+            *plane = clip_plane.to_vec4();
+        }
+        let plane_count = s.clipping.planes.len().min(MAX_CLIP_PLANES)
+            + if s.clipping.slab.enabled {
+                SLAB_PLANES
+            } else {
+                0
+            };
+
+        Self {
+            box_min: clip_box.min,
+            plane_count: plane_count as u32,
+            box_max: clip_box.max,
+            _padding: 0,
+            planes,
+        }
+    }
+}
@@ -1,5 +1,4 @@
 use crate::gpu_context::GpuContext;
-use crate::state::State;
 use egui_wgpu::wgpu;
 
 use super::{BindGroupLayoutEntryUnbound, ToGpuResources};
@@ -16,23 +15,21 @@ impl GpuDebugMatrix {
             visibility: wgpu::ShaderStages::COMPUTE,
             ty: wgpu::BindingType::StorageTexture {
                 access: wgpu::StorageTextureAccess::WriteOnly,
-                format: wgpu::TextureFormat::Rgba8Unorm,
+                format: wgpu::TextureFormat::Rgba16Float,
                 view_dimension: wgpu::TextureViewDimension::D2,
             },
             count: None,
+            uniform_layout: None,
         }];
-    pub fn new(ctx: &GpuContext, state: &State) -> Self {
+    /// `size` is the output texture's, the debug matrix has a texel for every output pixel
+    pub fn new(ctx: &GpuContext, size: wgpu::Extent3d) -> Self {
         let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Debug Matrix Texture"),
-            size: wgpu::Extent3d {
-                width: ctx.surface_config.width,
-                height: ctx.surface_config.height,
-                depth_or_array_layers: 1,
-            },
+            size,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
+            format: wgpu::TextureFormat::Rgba16Float,
             usage: wgpu::TextureUsages::TEXTURE_BINDING
                 | wgpu::TextureUsages::STORAGE_BINDING
                 | wgpu::TextureUsages::COPY_DST,
@@ -45,7 +42,7 @@ impl GpuDebugMatrix {
 }
 
 impl ToGpuResources for GpuDebugMatrix {
-    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource> {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
         vec![wgpu::BindingResource::TextureView(&self.texture_view)]
     }
 }
@@ -0,0 +1,284 @@
+use bytemuck::{Pod, Zeroable};
+use color_eyre::eyre::eyre;
+use egui_wgpu::wgpu::{self, util::DeviceExt};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    demos::pipeline::{bindgroup_from_resources, layout_from_unbound_entries},
+    gpu_context::GpuContext,
+    shader::WatchedShader,
+    Result,
+};
+
+use super::{volume::GpuVolume, BindGroupLayoutEntryUnbound, ToGpuResources};
+
+const WORKGROUP_SIZE: u32 = 4;
+/// Rgba16Float
+const BYTES_PER_TEXEL: u32 = 8;
+
+/// Kernel used to precompute the gradients
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum GradientFilter {
+    /// 6 samples, sharp but noisy
+    CentralDifference,
+    /// 27 samples, smooths the noise of the normals
+    #[default]
+    Sobel,
+}
+
+/// Density gradient of every voxel, computed once at load time so that shading and
+/// the 2D transfer function don't need six extra volume samples per ray step.
+/// `xyz` is the gradient in density change per voxel, `w` its magnitude.
+///
+/// The pass writes to a buffer that is then copied to the texture, a slab of slices at a time:
+/// the GL backend can only bind a single slice of a 3D storage texture.
+#[derive(Debug)]
+pub struct GpuGradients {
+    texture_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+}
+
+impl GpuGradients {
+    pub const BIND_GROUP_LAYOUT_ENTRIES: &[BindGroupLayoutEntryUnbound] = &[
+        BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D3,
+                multisampled: false,
+            },
+            count: None,
+            uniform_layout: None,
+        },
+        BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+            uniform_layout: None,
+        },
+    ];
+
+    const OUTPUT_LAYOUT_ENTRIES: &[BindGroupLayoutEntryUnbound] = &[
+        BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+            uniform_layout: None,
+        },
+        BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+            uniform_layout: Some(SlabUniforms::LAYOUT),
+        },
+    ];
+
+    /// The compute shader filling the gradients, checked against the pipeline's bind groups
+    pub fn shader() -> WatchedShader {
+        WatchedShader::new(
+            format!("{}/shaders/gradients.wgsl", env!("CARGO_MANIFEST_DIR")),
+            &[],
+        )
+        .with_bind_groups(&[
+            &[GpuVolume::BIND_GROUP_LAYOUT_ENTRIES],
+            &[Self::OUTPUT_LAYOUT_ENTRIES],
+        ])
+    }
+
+    pub fn init(volume: &GpuVolume, filter: GradientFilter, ctx: &GpuContext) -> Result<Self> {
+        info!("Computing volume gradients with {:?}", filter);
+        let size = volume.size;
+
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Gradients Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&Default::default());
+
+        let shader = Self::shader().load(ctx)?;
+
+        let input_layout = layout_from_unbound_entries(
+            ctx,
+            "Gradients Input Layout",
+            &[GpuVolume::BIND_GROUP_LAYOUT_ENTRIES],
+        );
+        let output_layout = layout_from_unbound_entries(
+            ctx,
+            "Gradients Output Layout",
+            &[Self::OUTPUT_LAYOUT_ENTRIES],
+        );
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Gradients Pipeline Layout"),
+                bind_group_layouts: &[&input_layout, &output_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = ctx
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Gradients Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "main",
+                compilation_options: Default::default(),
+            });
+
+        let input_group = bindgroup_from_resources(
+            ctx,
+            "Gradients Input Bind Group",
+            &input_layout,
+            &[volume.to_gpu_resources()],
+        );
+        // As many slices per slab as the limits allow
+        let row_stride = (size.width * BYTES_PER_TEXEL)
+            .next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            / BYTES_PER_TEXEL;
+        let slice_bytes = (row_stride * BYTES_PER_TEXEL) as u64 * size.height as u64;
+        let limits = ctx.device.limits();
+        let max_bytes = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+        let slab_depth = (max_bytes / slice_bytes).min(size.depth_or_array_layers as u64) as u32;
+        if slab_depth == 0 {
+            return Err(eyre!(
+                "A {}x{} slice of gradients doesn't fit in a storage buffer",
+                size.width,
+                size.height
+            ));
+        }
+
+        let gradients_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Gradients Buffer"),
+            size: slice_bytes * slab_depth as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let slab_buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Gradients Slab Buffer"),
+                contents: bytemuck::cast_slice(&[SlabUniforms::zeroed()]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let output_group = bindgroup_from_resources(
+            ctx,
+            "Gradients Output Bind Group",
+            &output_layout,
+            &[vec![
+                gradients_buffer.as_entire_binding(),
+                slab_buffer.as_entire_binding(),
+            ]],
+        );
+
+        for first_slice in (0..size.depth_or_array_layers).step_by(slab_depth as usize) {
+            let depth = slab_depth.min(size.depth_or_array_layers - first_slice);
+            let slab = SlabUniforms {
+                first_slice,
+                depth,
+                row_stride,
+                use_sobel: (filter == GradientFilter::Sobel) as u32,
+            };
+            ctx.queue
+                .write_buffer(&slab_buffer, 0, bytemuck::cast_slice(&[slab]));
+
+            let mut encoder = ctx
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Gradients Encoder"),
+                });
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Gradients Pass"),
+                    timestamp_writes: None,
+                });
+                compute_pass.set_pipeline(&pipeline);
+                compute_pass.set_bind_group(0, &input_group, &[]);
+                compute_pass.set_bind_group(1, &output_group, &[]);
+                compute_pass.dispatch_workgroups(
+                    size.width.div_ceil(WORKGROUP_SIZE),
+                    size.height.div_ceil(WORKGROUP_SIZE),
+                    depth.div_ceil(WORKGROUP_SIZE),
+                );
+            }
+            encoder.copy_buffer_to_texture(
+                wgpu::ImageCopyBuffer {
+                    buffer: &gradients_buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(row_stride * BYTES_PER_TEXEL),
+                        rows_per_image: Some(size.height),
+                    },
+                },
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: first_slice,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    depth_or_array_layers: depth,
+                    ..size
+                },
+            );
+            ctx.queue.submit(Some(encoder.finish()));
+        }
+
+        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Gradients Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture_view,
+            sampler,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct SlabUniforms {
+    first_slice: u32,
+    depth: u32,
+    row_stride: u32,
+    use_sobel: u32,
+}
+
+impl SlabUniforms {
+    uniform_layout!(SlabUniforms {
+        first_slice,
+        depth,
+        row_stride,
+        use_sobel
+    });
+}
+
+impl ToGpuResources for GpuGradients {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
+        vec![
+            wgpu::BindingResource::TextureView(&self.texture_view),
+            wgpu::BindingResource::Sampler(&self.sampler),
+        ]
+    }
+}
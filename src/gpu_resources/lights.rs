@@ -0,0 +1,187 @@
+use std::cell::Cell;
+
+use bytemuck::{Pod, Zeroable};
+use egui_wgpu::wgpu::{self, util::DeviceExt};
+
+use crate::{
+    gpu_context::GpuContext,
+    lighting::{LightKind, Lighting, MAX_LIGHTS},
+    state::State,
+    Result,
+};
+
+use super::{BindGroupLayoutEntryUnbound, ToGpuResources};
+
+/// Enabled lights, the headlight and the material the shader shades the samples with
+#[derive(Debug)]
+pub struct GpuLights {
+    buffer: wgpu::Buffer,
+    uploaded: Cell<LightingUniforms>,
+}
+
+impl GpuLights {
+    pub const BIND_GROUP_LAYOUT_ENTRIES: &[BindGroupLayoutEntryUnbound] =
+        &[BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+            uniform_layout: Some(LightingUniforms::LAYOUT),
+        }];
+
+    pub fn new(ctx: &GpuContext, state: &State) -> Self {
+        let uniforms = LightingUniforms::from(&state.lighting);
+        let buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Lights Buffer"),
+                contents: bytemuck::cast_slice(&[uniforms]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        Self {
+            buffer,
+            uploaded: Cell::new(uniforms),
+        }
+    }
+
+    /// Returns whether the lighting changed since the last upload
+    pub fn update(&self, ctx: &GpuContext, state: &State) -> Result<bool> {
+        let uniforms = LightingUniforms::from(&state.lighting);
+        if bytemuck::bytes_of(&uniforms) == bytemuck::bytes_of(&self.uploaded.get()) {
+            return Ok(false);
+        }
+        ctx.queue
+            .write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        self.uploaded.set(uniforms);
+
+        Ok(true)
+    }
+}
+
+impl ToGpuResources for GpuLights {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
+        vec![self.buffer.as_entire_binding()]
+    }
+}
+
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+#[repr(C, align(16))]
+struct LightingUniforms {
+    /// `xyz` is the direction the light comes from or its position, `w` is 0 for directional
+    /// lights and 1 for point lights
+    light_vectors: [[f32; 4]; MAX_LIGHTS],
+    /// Color times intensity, `w` is unused
+    light_colors: [[f32; 4]; MAX_LIGHTS],
+    light_count: u32,
+    headlight: u32,
+    ambient: f32,
+    diffuse: f32,
+    specular: f32,
+    shininess: f32,
+    _padding: [u32; 2],
+}
+
+impl LightingUniforms {
+    uniform_layout!(LightingUniforms {
+        light_vectors,
+        light_colors,
+        light_count,
+        headlight,
+        ambient,
+        diffuse,
+        specular,
+        shininess
+    });
+}
+
+/// Packs the enabled lights, past [`MAX_LIGHTS`] of them the others are left out
+impl From<&Lighting> for LightingUniforms {
+    fn from(lighting: &Lighting) -> Self {
+        let mut light_vectors = [[0.0; 4]; MAX_LIGHTS];
+        let mut light_colors = [[0.0; 4]; MAX_LIGHTS];
+        let lights = lighting
+            .lights
+            .iter()
+            .filter(|light| light.enabled)
+            .take(MAX_LIGHTS);
+        let mut light_count = 0;
+        for (i, light) in lights.enumerate() {
+            let [x, y, z] = light.vector;
+            let w = match light.kind {
+                LightKind::Directional => 0.0,
+                LightKind::Point => 1.0,
+            };
+            light_vectors[i] = [x, y, z, w];
+            let [r, g, b] = light.color.map(|c| c * light.intensity);
+            light_colors[i] = [r, g, b, 0.0];
+            light_count += 1;
+        }
+
+        Self {
+            light_vectors,
+            light_colors,
+            light_count,
+            headlight: if lighting.headlight { 1 } else { 0 },
+            ambient: lighting.material.ambient,
+            diffuse: lighting.material.diffuse,
+            specular: lighting.material.specular,
+            shininess: lighting.material.shininess,
+            _padding: [0; 2],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lighting::Light;
+
+    fn light(kind: LightKind, x: f32, enabled: bool) -> Light {
+        Light {
+            kind,
+            vector: [x, 0.0, 1.0],
+            color: [1.0, 0.5, 0.0],
+            intensity: 2.0,
+            enabled,
+        }
+    }
+
+    #[test]
+    fn packs_enabled_lights_up_to_max_lights() {
+        let lighting = Lighting {
+            lights: (0..MAX_LIGHTS + 2)
+                .map(|i| light(LightKind::Point, i as f32, i != 1))
+                .collect(),
+            headlight: true,
+            ..Default::default()
+        };
+        let uniforms = LightingUniforms::from(&lighting);
+
+        assert_eq!(uniforms.light_count, MAX_LIGHTS as u32);
+        // The disabled light is skipped, the next ones move up
+        let xs: Vec<f32> = uniforms.light_vectors.iter().map(|v| v[0]).collect();
+        assert_eq!(xs, [0.0, 2.0, 3.0, 4.0]);
+        assert!(uniforms.light_vectors.iter().all(|v| v[3] == 1.0));
+        assert_eq!(uniforms.light_colors[0], [2.0, 1.0, 0.0, 0.0]);
+        assert_eq!(uniforms.headlight, 1);
+    }
+
+    #[test]
+    fn leaves_unused_lights_empty() {
+        let lighting = Lighting {
+            lights: vec![light(LightKind::Directional, 0.5, true)],
+            ..Default::default()
+        };
+        let uniforms = LightingUniforms::from(&lighting);
+
+        assert_eq!(uniforms.light_count, 1);
+        assert_eq!(uniforms.light_vectors[0], [0.5, 0.0, 1.0, 0.0]);
+        assert!(uniforms.light_vectors[1..].iter().all(|v| *v == [0.0; 4]));
+        assert_eq!(uniforms.headlight, 0);
+        assert_eq!(uniforms.shininess, lighting.material.shininess);
+    }
+}
@@ -3,10 +3,33 @@ use std::num::NonZeroU32;
 use egui_wgpu::wgpu;
 use egui_wgpu::wgpu::{BindingType, ShaderStages};
 
+/// Defines `LAYOUT`, the [`UniformLayout`] of a `#[repr(C)]` struct mirrored in WGSL.
+/// Padding fields are left out, the WGSL struct doesn't declare them.
+macro_rules! uniform_layout {
+    ($struct:ident { $($field:ident),* $(,)? }) => {
+        const LAYOUT: $crate::gpu_resources::UniformLayout = $crate::gpu_resources::UniformLayout {
+            name: stringify!($struct),
+            size: std::mem::size_of::<$struct>(),
+            fields: &[$($crate::gpu_resources::UniformFieldLayout::of(
+                stringify!($field),
+                std::mem::offset_of!($struct, $field),
+                |uniforms: &$struct| &uniforms.$field,
+            )),*],
+        };
+    };
+}
+
+pub mod accumulation;
+pub mod blue_noise;
 pub mod camera;
+pub mod clipping;
 pub mod debug_matrix;
+pub mod gradients;
+pub mod lights;
+pub mod occupancy;
 pub mod parameters;
 pub mod texture;
+pub mod tone_mapping;
 pub mod transfer_function;
 pub mod volume;
 
@@ -26,19 +49,76 @@ pub struct BindGroupLayoutEntryUnbound {
     ///
     /// If this value is Some and `ty` is any other variant, bind group creation will fail.
     pub count: Option<NonZeroU32>,
+    /// Layout of the Rust struct filling a uniform buffer, checked against the shader's struct
+    /// when the shader is loaded, see [`crate::shader::reflection`]
+    pub uniform_layout: Option<UniformLayout>,
+}
+
+/// Size and fields, in bytes, of a struct uploaded to a uniform buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UniformLayout {
+    pub name: &'static str,
+    pub size: usize,
+    pub fields: &'static [UniformFieldLayout],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UniformFieldLayout {
+    pub name: &'static str,
+    pub offset: usize,
+    pub size: usize,
+    /// Kind of the scalars the field is made of, i.e. `Float` for `[[f32; 4]; 4]`
+    pub kind: naga::ScalarKind,
+}
+
+impl UniformFieldLayout {
+    /// The layout of the field `field` returns, which is only used for its type
+    pub const fn of<S, T: UniformScalars>(
+        name: &'static str,
+        offset: usize,
+        _field: fn(&S) -> &T,
+    ) -> Self {
+        Self {
+            name,
+            offset,
+            size: std::mem::size_of::<T>(),
+            kind: T::KIND,
+        }
+    }
+}
+
+/// Types a uniform struct field can have, scalars and arrays of them
+pub trait UniformScalars {
+    const KIND: naga::ScalarKind;
+}
+
+impl UniformScalars for f32 {
+    const KIND: naga::ScalarKind = naga::ScalarKind::Float;
+}
+
+impl UniformScalars for u32 {
+    const KIND: naga::ScalarKind = naga::ScalarKind::Uint;
+}
+
+impl UniformScalars for i32 {
+    const KIND: naga::ScalarKind = naga::ScalarKind::Sint;
+}
+
+impl<T: UniformScalars, const N: usize> UniformScalars for [T; N] {
+    const KIND: naga::ScalarKind = T::KIND;
 }
 
 pub trait ToGpuResources {
-    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource>;
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>>;
 }
 
 // TODO: there are definitely better names and better ways to do this.
 pub trait ToBindGroupEntries: Sized {
-    fn to_bind_group_entries(&self) -> Vec<wgpu::BindGroupEntry>;
+    fn to_bind_group_entries(&self) -> Vec<wgpu::BindGroupEntry<'_>>;
 }
 
 impl ToBindGroupEntries for Vec<wgpu::BindingResource<'_>> {
-    fn to_bind_group_entries(&self) -> Vec<wgpu::BindGroupEntry> {
+    fn to_bind_group_entries(&self) -> Vec<wgpu::BindGroupEntry<'_>> {
         self.iter()
             .enumerate()
             .map(|(i, r)| wgpu::BindGroupEntry {
@@ -67,22 +147,67 @@ impl ToBindGroupLayoutEntries for Vec<&BindGroupLayoutEntryUnbound> {
     }
 }
 
-pub fn flip_3d_texture_y(data: &mut [u8], (x, y, z): (usize, usize, usize)) {
+/// Flips the rows of every slice of a volume. `bytes_per_voxel` keeps multi-byte voxels intact.
+pub fn flip_3d_texture_y(
+    data: &mut [u8],
+    (x, y, z): (usize, usize, usize),
+    bytes_per_voxel: usize,
+) {
+    let row_len = x * bytes_per_voxel;
     for k in 0..z {
+        let slice = &mut data[k * row_len * y..(k + 1) * row_len * y];
         for j in 0..(y / 2) {
-            let top_row = j * x;
-            let bottom_row = (y - j - 1) * x;
-            for i in 0..x {
-                let top_index = k * x * y + top_row + i;
-                let bottom_index = k * x * y + bottom_row + i;
-                data.swap(top_index, bottom_index);
-            }
+            let (top, bottom) = slice.split_at_mut((y - j - 1) * row_len);
+            top[j * row_len..(j + 1) * row_len].swap_with_slice(&mut bottom[..row_len]);
         }
     }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum FlipMode {
+    #[allow(dead_code)]
     None,
     Y,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Voxels of `bytes_per_voxel` bytes numbered `z * 100 + y * 10 + x`, each byte offset
+    /// by 50 times its index within the voxel
+    fn numbered((x, y, z): (usize, usize, usize), bytes_per_voxel: usize, flip: bool) -> Vec<u8> {
+        let mut data = Vec::new();
+        for k in 0..z {
+            for j in 0..y {
+                let j = if flip { y - 1 - j } else { j };
+                for i in 0..x {
+                    let number = k * 100 + j * 10 + i;
+                    data.extend((0..bytes_per_voxel).map(|b| (number + b * 50) as u8));
+                }
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn flips_multi_byte_rows_of_every_slice() {
+        for dims in [(3, 4, 2), (2, 3, 2), (1, 1, 1)] {
+            for bytes_per_voxel in [1, 2, 4] {
+                let mut data = numbered(dims, bytes_per_voxel, false);
+                flip_3d_texture_y(&mut data, dims, bytes_per_voxel);
+                assert_eq!(
+                    data,
+                    numbered(dims, bytes_per_voxel, true),
+                    "{:?} with {} bytes per voxel",
+                    dims,
+                    bytes_per_voxel
+                );
+
+                // Flipping twice gives the volume back
+                flip_3d_texture_y(&mut data, dims, bytes_per_voxel);
+                assert_eq!(data, numbered(dims, bytes_per_voxel, false));
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use bytemuck::{Pod, Zeroable};
+use egui_wgpu::wgpu::{self, util::DeviceExt};
+use tracing::info;
+
+use crate::{
+    gpu_context::GpuContext,
+    volume::{
+        min_max_grid::{MinMaxGrid, CELL_SIZE},
+        ValueWindow, Volume,
+    },
+};
+
+use super::{BindGroupLayoutEntryUnbound, FlipMode, ToGpuResources};
+
+/// Which macro cells of the volume can contribute to the image, for rays to jump over the
+/// others. The density range of every cell is computed once, cells are classified again
+/// whenever the threshold or the transfer function changes.
+#[derive(Debug)]
+pub struct GpuOccupancyGrid {
+    grid: MinMaxGrid,
+    texture: wgpu::Texture,
+    texture_view: wgpu::TextureView,
+    uniforms_buffer: wgpu::Buffer,
+}
+
+impl GpuOccupancyGrid {
+    pub const BIND_GROUP_LAYOUT_ENTRIES: &[BindGroupLayoutEntryUnbound] = &[
+        BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D3,
+                multisampled: false,
+            },
+            count: None,
+            uniform_layout: None,
+        },
+        BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+            uniform_layout: Some(OccupancyUniforms::LAYOUT),
+        },
+    ];
+
+    /// Every cell starts as occupied, until the first [`Self::update`]
+    pub fn init(
+        volume: &Volume,
+        window: ValueWindow,
+        flip_mode: FlipMode,
+        ctx: &GpuContext,
+    ) -> Self {
+        let grid = MinMaxGrid::new(volume, window, flip_mode == FlipMode::Y);
+        let (width, height, depth) = grid.dims;
+        info!("Occupancy grid of {}x{}x{} cells", width, height, depth);
+
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Occupancy Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: depth,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&Default::default());
+
+        let (volume_width, volume_height, volume_depth) = volume.descriptor.dims;
+        let uniforms = OccupancyUniforms {
+            cell_size: [
+                CELL_SIZE as f32 / volume_width as f32,
+                CELL_SIZE as f32 / volume_height as f32,
+                CELL_SIZE as f32 / volume_depth as f32,
+            ],
+            _padding: 0.0,
+        };
+        let uniforms_buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Occupancy Uniforms Buffer"),
+                contents: bytemuck::cast_slice(&[uniforms]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+        let occupancy_grid = Self {
+            grid,
+            texture,
+            texture_view,
+            uniforms_buffer,
+        };
+        occupancy_grid.upload(&occupancy_grid.grid.occupancy(0.0, None), &ctx.queue);
+        occupancy_grid
+    }
+
+    /// Classifies the cells again, see [`MinMaxGrid::occupancy`]
+    pub fn update(&self, threshold: f32, opacities: Option<&[f32]>, queue: &wgpu::Queue) {
+        self.upload(&self.grid.occupancy(threshold, opacities), queue);
+    }
+
+    fn upload(&self, occupancy: &[u8], queue: &wgpu::Queue) {
+        let (width, height, _) = self.grid.dims;
+        queue.write_texture(
+            self.texture.as_image_copy(),
+            occupancy,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: Some(height),
+            },
+            self.texture.size(),
+        );
+    }
+}
+
+impl ToGpuResources for GpuOccupancyGrid {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
+        vec![
+            wgpu::BindingResource::TextureView(&self.texture_view),
+            self.uniforms_buffer.as_entire_binding(),
+        ]
+    }
+}
+
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+#[repr(C, align(16))]
+struct OccupancyUniforms {
+    // Size of a cell in texture coordinates
+    cell_size: [f32; 3],
+    _padding: f32,
+}
+
+impl OccupancyUniforms {
+    uniform_layout!(OccupancyUniforms { cell_size });
+}
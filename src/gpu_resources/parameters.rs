@@ -1,7 +1,12 @@
+use std::cell::Cell;
+
 use bytemuck::{Pod, Zeroable};
 use egui_wgpu::wgpu::{self, util::DeviceExt};
 
-use crate::{gpu_context::GpuContext, state::State};
+use crate::{
+    gpu_context::GpuContext,
+    state::{State, MAX_ISOSURFACES},
+};
 
 use super::{BindGroupLayoutEntryUnbound, ToGpuResources};
 use crate::Result;
@@ -10,6 +15,7 @@ use crate::Result;
 #[repr(C)]
 pub struct GpuParameters {
     buffer: wgpu::Buffer,
+    uploaded: Cell<ParameterUniforms>,
 }
 
 impl GpuParameters {
@@ -22,6 +28,7 @@ impl GpuParameters {
                 min_binding_size: None,
             },
             count: None,
+            uniform_layout: Some(ParameterUniforms::LAYOUT),
         }];
 
     pub fn new(ctx: &GpuContext, state: &State) -> Self {
@@ -34,20 +41,28 @@ impl GpuParameters {
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             });
 
-        Self { buffer }
+        Self {
+            buffer,
+            uploaded: Cell::new(uniforms),
+        }
     }
 
-    pub fn update(&self, ctx: &GpuContext, state: &State) -> Result<()> {
+    /// Returns whether the parameters changed since the last upload
+    pub fn update(&self, ctx: &GpuContext, state: &State) -> Result<bool> {
         let uniforms = ParameterUniforms::try_from(state)?;
+        if bytemuck::bytes_of(&uniforms) == bytemuck::bytes_of(&self.uploaded.get()) {
+            return Ok(false);
+        }
         ctx.queue
             .write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        self.uploaded.set(uniforms);
 
-        Ok(())
+        Ok(true)
     }
 }
 
 impl ToGpuResources for GpuParameters {
-    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource> {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
         vec![self.buffer.as_entire_binding()]
     }
 }
@@ -63,12 +78,63 @@ pub struct ParameterUniforms {
     use_gaussian_smoothing: u32,
     importance_check_ahead_steps: u32,
     raymarching_step_size: f32,
+    use_transfer_function_2d: u32,
+    use_precomputed_gradients: u32,
+    max_gradient_magnitude: f32,
+    use_empty_space_skipping: u32,
+    render_mode: u32,
+    isosurface_count: u32,
+    show_slices: u32,
+    use_slice_transfer_function: u32,
+    isovalues: [f32; MAX_ISOSURFACES],
+    isosurface_colors: [[f32; 4]; MAX_ISOSURFACES],
+    probe_position: [f32; 3],
+    show_slice_segments: u32,
+    use_jittering: u32,
+    _padding: [u32; 3],
+}
+
+impl ParameterUniforms {
+    uniform_layout!(ParameterUniforms {
+        density_threshold,
+        use_cone_importance_check,
+        use_importance_coloring,
+        use_opacity,
+        use_importance_rendering,
+        use_gaussian_smoothing,
+        importance_check_ahead_steps,
+        raymarching_step_size,
+        use_transfer_function_2d,
+        use_precomputed_gradients,
+        max_gradient_magnitude,
+        use_empty_space_skipping,
+        render_mode,
+        isosurface_count,
+        show_slices,
+        use_slice_transfer_function,
+        isovalues,
+        isosurface_colors,
+        probe_position,
+        show_slice_segments,
+        use_jittering
+    });
 }
 
 impl TryFrom<&State> for ParameterUniforms {
     type Error = crate::Error;
 
     fn try_from(s: &State) -> std::result::Result<Self, Self::Error> {
+        // Sorted, so that the shader can find the surfaces in the order the ray crosses them
+        let mut isosurfaces = s.isosurfaces.clone();
+        isosurfaces.truncate(MAX_ISOSURFACES);
+        isosurfaces.sort_by(|a, b| a.value.total_cmp(&b.value));
+        let mut isovalues = [0.0; MAX_ISOSURFACES];
+        let mut isosurface_colors = [[0.0; 4]; MAX_ISOSURFACES];
+        for (i, isosurface) in isosurfaces.iter().enumerate() {
+            isovalues[i] = isosurface.value;
+            isosurface_colors[i] = isosurface.color;
+        }
+
         Ok(Self {
             use_cone_importance_check: if s.use_cone_importance_check { 1 } else { 0 },
             use_importance_coloring: if s.use_importance_coloring { 1 } else { 0 },
@@ -78,6 +144,20 @@ impl TryFrom<&State> for ParameterUniforms {
             use_gaussian_smoothing: if s.use_gaussian_smoothing { 1 } else { 0 },
             importance_check_ahead_steps: s.importance_check_ahead_steps,
             raymarching_step_size: s.raymarching_step_size,
+            use_transfer_function_2d: if s.use_transfer_function_2d { 1 } else { 0 },
+            use_precomputed_gradients: if s.use_precomputed_gradients { 1 } else { 0 },
+            max_gradient_magnitude: s.transfer_function_2d.max_gradient_magnitude,
+            use_empty_space_skipping: if s.use_empty_space_skipping { 1 } else { 0 },
+            render_mode: s.render_mode as u32,
+            isosurface_count: isosurfaces.len() as u32,
+            show_slices: if s.slices.enabled { 1 } else { 0 },
+            use_slice_transfer_function: if s.slices.use_transfer_function { 1 } else { 0 },
+            isovalues,
+            isosurface_colors,
+            probe_position: s.slices.probe,
+            show_slice_segments: if s.slices.show_segments { 1 } else { 0 },
+            use_jittering: if s.use_jittering { 1 } else { 0 },
+            _padding: [0; 3],
         })
     }
 }
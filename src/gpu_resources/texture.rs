@@ -15,16 +15,13 @@ impl GpuWriteTexture2D {
             visibility: wgpu::ShaderStages::COMPUTE,
             ty: wgpu::BindingType::StorageTexture {
                 access: wgpu::StorageTextureAccess::WriteOnly,
-                format: wgpu::TextureFormat::Rgba8Unorm,
+                format: wgpu::TextureFormat::Rgba16Float,
                 view_dimension: wgpu::TextureViewDimension::D2,
             },
             count: None,
+            uniform_layout: None,
         }];
 
-    pub fn from_write_texture_2d(read_texture: GpuReadTexture2D) -> Self {
-        Self::from_wgpu_texture(read_texture.texture)
-    }
-
     pub fn into_read_texture_2d(self, ctx: &GpuContext) -> GpuReadTexture2D {
         GpuReadTexture2D::from_wgpu_texture(ctx, self.texture)
     }
@@ -38,17 +35,22 @@ impl GpuWriteTexture2D {
     }
 
     pub fn new(ctx: &GpuContext) -> Self {
+        Self::with_size(ctx, (ctx.surface_config.width, ctx.surface_config.height))
+    }
+
+    /// An output of `width` x `height`, which the render pipeline scales to the window
+    pub fn with_size(ctx: &GpuContext, (width, height): (u32, u32)) -> Self {
         let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Render Input Texture"),
             size: wgpu::Extent3d {
-                width: ctx.surface_config.width,
-                height: ctx.surface_config.height,
+                width,
+                height,
                 depth_or_array_layers: 1,
             },
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
+            format: wgpu::TextureFormat::Rgba16Float,
             usage: wgpu::TextureUsages::STORAGE_BINDING
                 | wgpu::TextureUsages::TEXTURE_BINDING
                 | wgpu::TextureUsages::COPY_SRC
@@ -60,7 +62,7 @@ impl GpuWriteTexture2D {
 }
 
 impl ToGpuResources for GpuWriteTexture2D {
-    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource> {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
         vec![wgpu::BindingResource::TextureView(&self.texture_view)]
     }
 }
@@ -73,14 +75,6 @@ pub struct GpuReadTexture2D {
 }
 
 impl GpuReadTexture2D {
-    pub fn from_write_texture_2d(ctx: &GpuContext, write_texture: GpuWriteTexture2D) -> Self {
-        Self::from_wgpu_texture(ctx, write_texture.texture)
-    }
-
-    pub fn into_write_texture_2d(self) -> GpuWriteTexture2D {
-        GpuWriteTexture2D::from_wgpu_texture(self.texture)
-    }
-
     fn from_wgpu_texture(ctx: &GpuContext, texture: wgpu::Texture) -> Self {
         let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("Sampler"),
@@ -99,28 +93,6 @@ impl GpuReadTexture2D {
             sampler,
         }
     }
-    pub fn new(ctx: &GpuContext) -> Self {
-        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Render Input Texture"),
-            size: wgpu::Extent3d {
-                width: ctx.surface_config.width,
-                height: ctx.surface_config.height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::STORAGE_BINDING
-                | wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::RENDER_ATTACHMENT
-                | wgpu::TextureUsages::COPY_SRC,
-            view_formats: &[],
-        });
-
-        Self::from_wgpu_texture(ctx, texture)
-    }
-
     pub fn bind_group_layout_entries() -> Vec<BindGroupLayoutEntryUnbound> {
         Vec::from(&[
             BindGroupLayoutEntryUnbound {
@@ -131,18 +103,20 @@ impl GpuReadTexture2D {
                     sample_type: wgpu::TextureSampleType::Float { filterable: true },
                 },
                 count: None,
+                uniform_layout: None,
             },
             BindGroupLayoutEntryUnbound {
                 visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                 ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                 count: None,
+                uniform_layout: None,
             },
         ])
     }
 }
 
 impl ToGpuResources for GpuReadTexture2D {
-    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource> {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
         vec![
             wgpu::BindingResource::TextureView(&self.texture_view),
             wgpu::BindingResource::Sampler(&self.sampler),
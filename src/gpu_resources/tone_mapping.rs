@@ -0,0 +1,97 @@
+use std::cell::Cell;
+
+use bytemuck::{Pod, Zeroable};
+use egui_wgpu::wgpu::{self, util::DeviceExt};
+
+use crate::{
+    gpu_context::GpuContext,
+    state::{State, ToneMapping},
+};
+
+use super::{BindGroupLayoutEntryUnbound, ToGpuResources};
+
+/// Exposure, tone mapping curve and gamma turning the HDR output into display colors, read
+/// by the render pipeline's fragment shader
+#[derive(Debug)]
+pub struct GpuToneMapping {
+    buffer: wgpu::Buffer,
+    uploaded: Cell<ToneMappingUniforms>,
+}
+
+impl GpuToneMapping {
+    pub const BIND_GROUP_LAYOUT_ENTRIES: &[BindGroupLayoutEntryUnbound] =
+        &[BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+            uniform_layout: Some(ToneMappingUniforms::LAYOUT),
+        }];
+
+    pub fn new(ctx: &GpuContext, state: &State) -> Self {
+        let uniforms = ToneMappingUniforms::from(state);
+        let buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Tone Mapping Buffer"),
+                contents: bytemuck::cast_slice(&[uniforms]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        Self {
+            buffer,
+            uploaded: Cell::new(uniforms),
+        }
+    }
+
+    pub fn update(&self, ctx: &GpuContext, state: &State) {
+        let uniforms = ToneMappingUniforms::from(state);
+        if bytemuck::bytes_of(&uniforms) == bytemuck::bytes_of(&self.uploaded.get()) {
+            return;
+        }
+        ctx.queue
+            .write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        self.uploaded.set(uniforms);
+    }
+}
+
+impl ToGpuResources for GpuToneMapping {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
+        vec![self.buffer.as_entire_binding()]
+    }
+}
+
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct ToneMappingUniforms {
+    /// `2^exposure`
+    scale: f32,
+    inverse_gamma: f32,
+    /// 0 for none, 1 for Reinhard, 2 for ACES
+    curve: u32,
+}
+
+impl ToneMappingUniforms {
+    uniform_layout!(ToneMappingUniforms {
+        scale,
+        inverse_gamma,
+        curve
+    });
+}
+
+impl From<&State> for ToneMappingUniforms {
+    fn from(s: &State) -> Self {
+        Self {
+            scale: s.exposure.exp2(),
+            inverse_gamma: 1.0 / s.gamma,
+            curve: match s.tone_mapping {
+                ToneMapping::None => 0,
+                ToneMapping::Reinhard => 1,
+                ToneMapping::Aces => 2,
+            },
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use crate::transfer_function::TransferFunction;
+use crate::transfer_function::{
+    two_dimensional::{self, TransferFunction2D},
+    TransferFunction,
+};
 
 use egui_wgpu::wgpu;
 
@@ -6,6 +9,7 @@ use super::{BindGroupLayoutEntryUnbound, ToGpuResources};
 
 #[derive(Debug)]
 pub struct GPUTransferFunction {
+    texture: wgpu::Texture,
     texture_view: wgpu::TextureView,
     sampler: wgpu::Sampler,
 }
@@ -20,11 +24,13 @@ impl GPUTransferFunction {
                 multisampled: false,
             },
             count: None,
+            uniform_layout: None,
         },
         BindGroupLayoutEntryUnbound {
             visibility: wgpu::ShaderStages::COMPUTE,
             ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
             count: None,
+            uniform_layout: None,
         },
     ];
 
@@ -34,7 +40,6 @@ impl GPUTransferFunction {
         queue: &wgpu::Queue,
     ) -> Self {
         let tf_size = tf.max_density + 1;
-        let bytes_per_color = 4;
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Transfer Function 1D Texture"),
@@ -51,6 +56,38 @@ impl GPUTransferFunction {
             view_formats: &[],
         });
 
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Transfer Function 1D View"),
+            dimension: Some(wgpu::TextureViewDimension::D1),
+            ..Default::default()
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Transfer Function Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let gpu_transfer_function = Self {
+            texture,
+            texture_view,
+            sampler,
+        };
+        gpu_transfer_function.update(tf, queue);
+        gpu_transfer_function
+    }
+
+    /// Uploads the function again, `tf` must have the `max_density` the texture was created with
+    pub fn update(&self, tf: &TransferFunction, queue: &wgpu::Queue) {
+        let tf_size = tf.max_density + 1;
+        let bytes_per_color = 4;
+        debug_assert_eq!(tf_size, self.texture.width());
+
         // flatten the transfer function data
         let mut texture_data: Vec<u8> = Vec::with_capacity((tf_size * bytes_per_color) as usize);
 
@@ -67,15 +104,10 @@ impl GPUTransferFunction {
             //}
             texture_data.push((alpha * 255.0) as u8);
         }
-        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor {
-            label: Some("Transfer Function 1D View"),
-            dimension: Some(wgpu::TextureViewDimension::D1),
-            ..Default::default()
-        });
 
         // Calculate proper dimensions based on the actual texture data
         queue.write_texture(
-            texture.as_image_copy(),
+            self.texture.as_image_copy(),
             &texture_data,
             wgpu::ImageDataLayout {
                 offset: 0,
@@ -88,9 +120,65 @@ impl GPUTransferFunction {
                 depth_or_array_layers: 1,
             },
         );
+    }
+}
+
+impl ToGpuResources for GPUTransferFunction {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
+        vec![
+            wgpu::BindingResource::TextureView(&self.texture_view),
+            wgpu::BindingResource::Sampler(&self.sampler),
+        ]
+    }
+}
+
+/// Density x gradient magnitude transfer function, see [`TransferFunction2D`]
+#[derive(Debug)]
+pub struct GPUTransferFunction2D {
+    texture: wgpu::Texture,
+    texture_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+}
+
+impl GPUTransferFunction2D {
+    pub const BIND_GROUP_LAYOUT_ENTRIES: &[BindGroupLayoutEntryUnbound] = &[
+        BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+            uniform_layout: None,
+        },
+        BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+            uniform_layout: None,
+        },
+    ];
+
+    pub fn new(tf: &TransferFunction2D, device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Transfer Function 2D Texture"),
+            size: wgpu::Extent3d {
+                width: two_dimensional::WIDTH,
+                height: two_dimensional::HEIGHT,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&Default::default());
 
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("Transfer Function Sampler"),
+            label: Some("Transfer Function 2D Sampler"),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
@@ -100,15 +188,32 @@ impl GPUTransferFunction {
             ..Default::default()
         });
 
-        Self {
+        let gpu_transfer_function = Self {
+            texture,
             texture_view,
             sampler,
-        }
+        };
+        gpu_transfer_function.update(tf, queue);
+        gpu_transfer_function
+    }
+
+    pub fn update(&self, tf: &TransferFunction2D, queue: &wgpu::Queue) {
+        let bytes_per_color = 4;
+        queue.write_texture(
+            self.texture.as_image_copy(),
+            &tf.to_rgba8(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(two_dimensional::WIDTH * bytes_per_color),
+                rows_per_image: Some(two_dimensional::HEIGHT),
+            },
+            self.texture.size(),
+        );
     }
 }
 
-impl ToGpuResources for GPUTransferFunction {
-    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource> {
+impl ToGpuResources for GPUTransferFunction2D {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
         vec![
             wgpu::BindingResource::TextureView(&self.texture_view),
             wgpu::BindingResource::Sampler(&self.sampler),
@@ -1,8 +1,13 @@
-use egui_wgpu::wgpu;
+use bytemuck::{Pod, Zeroable};
+use egui_wgpu::wgpu::{self, util::DeviceExt};
+use half::f16;
 use tracing::info;
 
-use crate::{gpu_resources::flip_3d_texture_y, Result};
-use std::path::Path;
+use crate::{
+    gpu_resources::flip_3d_texture_y,
+    volume::{Rescale, ValueWindow, Volume, VolumeDescriptor, VoxelType},
+    Result,
+};
 
 use crate::gpu_context::GpuContext;
 
@@ -10,8 +15,11 @@ use super::{BindGroupLayoutEntryUnbound, FlipMode, ToGpuResources};
 
 #[derive(Debug)]
 pub struct GpuVolume {
+    /// Dimensions of the volume texture
+    pub size: wgpu::Extent3d,
     texture_view: wgpu::TextureView,
     sampler: wgpu::Sampler,
+    uniforms_buffer: wgpu::Buffer,
 }
 
 impl GpuVolume {
@@ -24,46 +32,60 @@ impl GpuVolume {
                 multisampled: false,
             },
             count: None,
+            uniform_layout: None,
         },
         BindGroupLayoutEntryUnbound {
             visibility: wgpu::ShaderStages::COMPUTE,
             ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
             count: None,
+            uniform_layout: None,
+        },
+        BindGroupLayoutEntryUnbound {
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+            uniform_layout: Some(VolumeUniforms::LAYOUT),
         },
     ];
 
-    pub fn init(path: &Path, flip_mode: FlipMode, ctx: &GpuContext) -> Result<Self> {
+    pub fn init(
+        volume: &Volume,
+        window: ValueWindow,
+        flip_mode: FlipMode,
+        ctx: &GpuContext,
+    ) -> Result<Self> {
         info!("Loading volume");
 
-        let data = {
-            let mut data = std::fs::read(path)?;
-            // center the volume to be 256x256x256
-            let desired_len = 256 * 256 * 256;
-
-            if data.len() < desired_len {
-                info!(
-                    "Volume's size is {}, which is less than 256x256x256, padding with zeros",
-                    data.len()
-                );
-                data.resize(desired_len, 0);
-            } else {
-                info!(
-                    "Volume's size is {}, which is greater than 256x256x256, truncating",
-                    data.len()
-                );
-                data.truncate(desired_len);
-            }
+        let descriptor = volume.descriptor;
+        let (width, height, depth) = descriptor.dims;
 
+        let texture_data = VolumeTextureData::new(volume, ctx.device.features());
+        info!(
+            "Uploading {:?} volume as {:?}, value window [{}, {}]",
+            descriptor.voxel_type, texture_data.format, window.min, window.max
+        );
+        let bytes_per_voxel = (texture_data.data.len() / descriptor.voxel_count()) as u32;
+
+        let data = {
+            let mut data = texture_data.data;
             if flip_mode == FlipMode::Y {
-                flip_3d_texture_y(&mut data, (256, 256, 256));
+                flip_3d_texture_y(
+                    &mut data,
+                    (width as usize, height as usize, depth as usize),
+                    bytes_per_voxel as usize,
+                );
             }
             data
         };
 
         let size = wgpu::Extent3d {
-            width: 256,
-            height: 256,
-            depth_or_array_layers: 256,
+            width,
+            height,
+            depth_or_array_layers: depth,
         };
 
         let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
@@ -72,7 +94,7 @@ impl GpuVolume {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D3,
-            format: wgpu::TextureFormat::R8Unorm,
+            format: texture_data.format,
             usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[], // TODO
         });
@@ -83,8 +105,8 @@ impl GpuVolume {
             &data,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(256),
-                rows_per_image: Some(256),
+                bytes_per_row: Some(width * bytes_per_voxel),
+                rows_per_image: Some(height),
             },
             size,
         );
@@ -94,18 +116,126 @@ impl GpuVolume {
             ..Default::default()
         });
 
+        let uniforms = VolumeUniforms::new(&descriptor, texture_data.sample_to_stored, window);
+        let uniforms_buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Volume Uniforms Buffer"),
+                contents: bytemuck::cast_slice(&[uniforms]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
         Ok(Self {
+            size,
             texture_view,
             sampler,
+            uniforms_buffer,
         })
     }
 }
 
 impl ToGpuResources for GpuVolume {
-    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource> {
+    fn to_gpu_resources(&self) -> Vec<wgpu::BindingResource<'_>> {
         vec![
             wgpu::BindingResource::TextureView(&self.texture_view),
             wgpu::BindingResource::Sampler(&self.sampler),
+            self.uniforms_buffer.as_entire_binding(),
         ]
     }
 }
+
+/// Texture format a volume is uploaded as, and how its samples map back to stored voxel values
+struct VolumeTextureData {
+    format: wgpu::TextureFormat,
+    data: Vec<u8>,
+    sample_to_stored: Rescale,
+}
+
+impl VolumeTextureData {
+    fn new(volume: &Volume, features: wgpu::Features) -> Self {
+        let norm16 = features.contains(wgpu::Features::TEXTURE_FORMAT_16BIT_NORM);
+        let float32 = features.contains(wgpu::Features::FLOAT32_FILTERABLE);
+        let scaled_by = |slope, intercept| Rescale { slope, intercept };
+
+        match volume.descriptor.voxel_type {
+            VoxelType::Uint8 => Self {
+                format: wgpu::TextureFormat::R8Unorm,
+                data: volume.data.clone(),
+                sample_to_stored: scaled_by(u8::MAX as f32, 0.0),
+            },
+            VoxelType::Uint16 if norm16 => Self {
+                format: wgpu::TextureFormat::R16Unorm,
+                data: volume.data.clone(),
+                sample_to_stored: scaled_by(u16::MAX as f32, 0.0),
+            },
+            // Biased to be unsigned, R16Snorm can't tell -32768 and -32767 apart
+            VoxelType::Int16 if norm16 => Self {
+                format: wgpu::TextureFormat::R16Unorm,
+                data: volume
+                    .values()
+                    .flat_map(|v| ((v as i32 - i16::MIN as i32) as u16).to_le_bytes())
+                    .collect(),
+                sample_to_stored: scaled_by(u16::MAX as f32, i16::MIN as f32),
+            },
+            VoxelType::Float32 if float32 => Self {
+                format: wgpu::TextureFormat::R32Float,
+                data: volume.data.clone(),
+                sample_to_stored: Rescale::default(),
+            },
+            // Half floats are always filterable. Values are normalized first, so that the
+            // precision is spent on the volume's range instead of on huge or tiny numbers.
+            _ => {
+                let (min, max) = volume.value_range();
+                let range = if max > min { max - min } else { 1.0 };
+                Self {
+                    format: wgpu::TextureFormat::R16Float,
+                    data: volume
+                        .values()
+                        .flat_map(|v| f16::from_f32((v - min) / range).to_le_bytes())
+                        .collect(),
+                    sample_to_stored: scaled_by(range, min),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+#[repr(C, align(16))]
+pub struct VolumeUniforms {
+    extent: [f32; 3],
+    // `density = sample * value_scale + value_offset` maps samples to the 0..=1 window
+    value_scale: f32,
+    value_offset: f32,
+    _padding: [f32; 3],
+}
+
+impl VolumeUniforms {
+    uniform_layout!(VolumeUniforms {
+        extent,
+        value_scale,
+        value_offset
+    });
+}
+
+impl VolumeUniforms {
+    fn new(descriptor: &VolumeDescriptor, sample_to_stored: Rescale, window: ValueWindow) -> Self {
+        let rescale = descriptor.rescale;
+        let window_width = if window.width() != 0.0 {
+            window.width()
+        } else {
+            1.0
+        };
+        let sample_to_real = Rescale {
+            slope: sample_to_stored.slope * rescale.slope,
+            intercept: rescale.apply(sample_to_stored.intercept),
+        };
+
+        Self {
+            extent: descriptor.extent(),
+            value_scale: sample_to_real.slope / window_width,
+            value_offset: (sample_to_real.intercept - window.min) / window_width,
+            _padding: [0.0; 3],
+        }
+    }
+}
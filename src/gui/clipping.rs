@@ -0,0 +1,230 @@
+/// Clip box and clipping plane controls, and handles to drag the faces of the clip box
+/// over the rendering
+use cgmath::{Point3, Vector3};
+use egui::{Color32, Context, Id, LayerId, Order, Pos2, RichText, Sense, Stroke, Ui, Vec2};
+
+use crate::{
+    clipping::{ClipPlane, Clipping, Slab, MAX_CLIP_PLANES},
+    state::State,
+};
+
+const HANDLE_SIZE: f32 = 12.0;
+/// Smallest side of the clip box, in volume coordinates
+const MIN_BOX_SIZE: f32 = 0.01;
+const AXES: [&str; 3] = ["X", "Y", "Z"];
+
+pub fn show(ui: &mut Ui, clipping: &mut Clipping) {
+    ui.checkbox(
+        &mut clipping.use_clip_box,
+        RichText::new("Clip Box").strong(),
+    )
+    .on_hover_text("Only keep the inside of the box, in volume coordinates");
+    if clipping.use_clip_box {
+        egui::Grid::new("clip_box_grid")
+            .num_columns(3)
+            .spacing([8.0, 4.0])
+            .show(ui, |ui| {
+                let clip_box = &mut clipping.clip_box;
+                for (axis, name) in AXES.iter().enumerate() {
+                    ui.label(RichText::new(*name).strong());
+                    let max = clip_box.max[axis];
+                    ui.add(
+                        egui::DragValue::new(&mut clip_box.min[axis])
+                            .speed(0.005)
+                            .range(0.0..=max - MIN_BOX_SIZE),
+                    );
+                    let min = clip_box.min[axis];
+                    ui.add(
+                        egui::DragValue::new(&mut clip_box.max[axis])
+                            .speed(0.005)
+                            .range(min + MIN_BOX_SIZE..=1.0),
+                    );
+                    ui.end_row();
+                }
+            });
+    }
+    ui.add_enabled(
+        clipping.use_clip_box,
+        egui::Checkbox::new(&mut clipping.show_handles, "Show Handles"),
+    )
+    .on_hover_text("Draw the clip box over the volume, drag its handles to move the faces");
+
+    ui.add_space(4.0);
+    let mut removed = None;
+    for (i, plane) in clipping.planes.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut plane.enabled, "");
+            for (value, name) in plane.normal.iter_mut().zip(AXES) {
+                ui.add(
+                    egui::DragValue::new(value)
+                        .speed(0.01)
+                        .range(-1.0..=1.0)
+                        .prefix(format!("{}: ", name)),
+                );
+            }
+            if ui.small_button("⇄").on_hover_text("Flip").clicked() {
+                plane.normal = plane.normal.map(|n| -n);
+                plane.offset = -plane.offset;
+            }
+            if ui.small_button("🗑").on_hover_text("Remove").clicked() {
+                removed = Some(i);
+            }
+        });
+        ui.add(egui::Slider::new(&mut plane.offset, -0.87..=0.87).text("Offset"))
+            .on_hover_text("Distance of the plane from the center of the volume");
+    }
+    if let Some(i) = removed {
+        clipping.planes.remove(i);
+    }
+
+    if ui
+        .add_enabled(
+            clipping.planes.len() < MAX_CLIP_PLANES,
+            egui::Button::new("Add Clipping Plane"),
+        )
+        .on_hover_text("Planes remove the side their normal points to")
+        .clicked()
+    {
+        clipping.planes.push(ClipPlane::default());
+    }
+
+    ui.add_space(4.0);
+    show_slab(ui, &mut clipping.slab);
+}
+
+fn show_slab(ui: &mut Ui, slab: &mut Slab) {
+    ui.checkbox(&mut slab.enabled, RichText::new("Slab").strong())
+        .on_hover_text(
+            "Only keep a slab between two parallel planes, composited or projected \
+            depending on the render mode",
+        );
+    ui.add_enabled_ui(slab.enabled, |ui| {
+        ui.checkbox(&mut slab.follow_camera, "Face Camera")
+            .on_hover_text("Use the viewing direction as the slab's normal");
+        ui.add_enabled_ui(!slab.follow_camera, |ui| {
+            ui.horizontal(|ui| {
+                for (value, name) in slab.normal.iter_mut().zip(AXES) {
+                    ui.add(
+                        egui::DragValue::new(value)
+                            .speed(0.01)
+                            .range(-1.0..=1.0)
+                            .prefix(format!("{}: ", name)),
+                    );
+                }
+            });
+        });
+        ui.add(egui::Slider::new(&mut slab.offset, -0.87..=0.87).text("Offset"))
+            .on_hover_text("Distance of the slab's middle from the center of the volume");
+        ui.add(egui::Slider::new(&mut slab.thickness, 0.005..=1.0).text("Thickness"));
+    });
+}
+
+/// Outline of the clip box, with a handle at the center of every face that moves it
+/// along its axis
+pub fn show_handles(ctx: &Context, state: &mut State) {
+    if !state.clipping.use_clip_box {
+        return;
+    }
+
+    let screen = ctx.screen_rect();
+    // The 3D view leaves room for the slice panes and is cropped, not squeezed, to fit
+    let pixels_per_point = ctx.pixels_per_point();
+    let (width, height) = (
+        (screen.width() * pixels_per_point) as u32,
+        (screen.height() * pixels_per_point) as u32,
+    );
+    let view_width = state.slices.view_width(width, height) as f32 / pixels_per_point;
+    let crop = view_width / screen.width();
+    let view_proj = state.camera.projection_matrix() * state.camera.view_matrix();
+    let extent = Vector3::from(state.volume_extent);
+    let box_origin = Point3::new(0.5, 0.5, 0.5) - extent * 0.5;
+    // Volume coordinates to screen points, `None` behind the camera
+    let to_screen = |p: [f32; 3]| {
+        let world = box_origin + Vector3::new(p[0] * extent.x, p[1] * extent.y, p[2] * extent.z);
+        let clip = view_proj * world.to_homogeneous();
+        if clip.w <= 0.0 {
+            return None;
+        }
+        Some(Pos2::new(
+            screen.left() + (clip.x / clip.w / crop + 1.0) * 0.5 * view_width,
+            screen.top() + (1.0 - clip.y / clip.w) * 0.5 * screen.height(),
+        ))
+    };
+
+    let clip_box = state.clipping.clip_box;
+    let corner = |i: usize| {
+        [0, 1, 2].map(|axis| {
+            if i & (1 << axis) == 0 {
+                clip_box.min[axis]
+            } else {
+                clip_box.max[axis]
+            }
+        })
+    };
+    let painter = ctx.layer_painter(LayerId::new(Order::Background, Id::new("clip_box")));
+    let stroke = Stroke::new(1.5_f32, Color32::LIGHT_BLUE);
+    for i in 0..8 {
+        for axis in 0..3 {
+            // Every edge once, from the corner on its min side
+            if i & (1 << axis) != 0 {
+                continue;
+            }
+            if let (Some(a), Some(b)) = (to_screen(corner(i)), to_screen(corner(i | (1 << axis)))) {
+                painter.line_segment([a, b], stroke);
+            }
+        }
+    }
+
+    for axis in 0..3 {
+        for is_max in [false, true] {
+            let mut face_center = [0, 1, 2].map(|a| (clip_box.min[a] + clip_box.max[a]) * 0.5);
+            face_center[axis] = if is_max {
+                clip_box.max[axis]
+            } else {
+                clip_box.min[axis]
+            };
+            let mut moved_center = face_center;
+            moved_center[axis] += 0.01;
+            let (Some(center), Some(moved)) = (to_screen(face_center), to_screen(moved_center))
+            else {
+                continue;
+            };
+
+            let response = egui::Area::new(Id::new(("clip_box_handle", axis, is_max)))
+                .fixed_pos(center - Vec2::splat(HANDLE_SIZE * 0.5))
+                .order(Order::Middle)
+                .show(ctx, |ui| {
+                    let (rect, response) =
+                        ui.allocate_exact_size(Vec2::splat(HANDLE_SIZE), Sense::drag());
+                    let color = if response.hovered() || response.dragged() {
+                        Color32::WHITE
+                    } else {
+                        Color32::LIGHT_BLUE
+                    };
+                    ui.painter()
+                        .circle_filled(rect.center(), HANDLE_SIZE * 0.5, color);
+                    response
+                })
+                .inner;
+            if !response.dragged() {
+                continue;
+            }
+
+            // Drag along the on screen direction of the axis
+            let axis_on_screen = moved - center;
+            if axis_on_screen.length_sq() < 1e-3 {
+                continue;
+            }
+            let delta =
+                response.drag_delta().dot(axis_on_screen) / axis_on_screen.length_sq() * 0.01;
+            let clip_box = &mut state.clipping.clip_box;
+            if is_max {
+                clip_box.max[axis] =
+                    (clip_box.max[axis] + delta).clamp(clip_box.min[axis] + MIN_BOX_SIZE, 1.0);
+            } else {
+                clip_box.min[axis] =
+                    (clip_box.min[axis] + delta).clamp(0.0, clip_box.max[axis] - MIN_BOX_SIZE);
+            }
+        }
+    }
+}
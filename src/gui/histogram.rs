@@ -0,0 +1,71 @@
+/// Histogram drawing, behind the transfer function editor and the density threshold slider
+use egui::{Color32, Rect, Sense, Stroke, Ui, Vec2};
+
+use crate::state::State;
+
+const STRIP_HEIGHT: f32 = 32.0;
+pub const BAR_COLOR: Color32 = Color32::from_rgba_premultiplied(90, 90, 90, 90);
+
+/// Bars of `heights` (normalized to `0..=1`) over the whole width of `rect`
+pub fn paint(painter: &egui::Painter, rect: Rect, heights: &[f32], color: Color32) {
+    let bar_width = rect.width() / heights.len() as f32;
+    for (i, height) in heights.iter().enumerate() {
+        if *height <= 0.0 {
+            continue;
+        }
+        let left = rect.left() + i as f32 * bar_width;
+        painter.rect_filled(
+            Rect::from_x_y_ranges(
+                left..=left + bar_width,
+                rect.bottom() - height * rect.height()..=rect.bottom(),
+            ),
+            0.0,
+            color,
+        );
+    }
+}
+
+/// Histogram with the densities below `threshold` dimmed, as they are skipped when rendering
+pub fn show_with_threshold(ui: &mut Ui, heights: &[f32], threshold: f32) {
+    let width = ui.available_width().max(200.0);
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(width, STRIP_HEIGHT), Sense::hover());
+    let painter = ui.painter();
+    painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+    paint(painter, rect, heights, Color32::from_gray(160));
+
+    let x = rect.left() + threshold.clamp(0.0, 1.0) * width;
+    painter.rect_filled(
+        Rect::from_x_y_ranges(rect.left()..=x, rect.y_range()),
+        0.0,
+        Color32::from_black_alpha(160),
+    );
+    painter.vline(x, rect.y_range(), Stroke::new(1.5_f32, Color32::LIGHT_BLUE));
+}
+
+/// Log scale and segment selection
+pub fn show_options(ui: &mut Ui, state: &mut State) {
+    let Some(histogram) = &state.histogram else {
+        return;
+    };
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut state.histogram_log_scale, "Log scale")
+            .on_hover_text("Keep small bins visible next to the background peak");
+
+        let selected_text = match state.histogram_segment {
+            Some(label) => histogram.segment_name(label),
+            None => "All voxels".to_string(),
+        };
+        egui::ComboBox::from_id_source("histogram_segment")
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut state.histogram_segment, None, "All voxels");
+                for &label in histogram.segments.keys() {
+                    ui.selectable_value(
+                        &mut state.histogram_segment,
+                        Some(label),
+                        histogram.segment_name(label),
+                    );
+                }
+            });
+    });
+}
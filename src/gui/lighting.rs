@@ -0,0 +1,111 @@
+/// Light and material controls of the Blinn-Phong shading
+use std::path::Path;
+
+use egui::{RichText, Ui};
+
+use crate::lighting::{Light, LightKind, Lighting, MAX_LIGHTS};
+
+const AXES: [&str; 3] = ["X", "Y", "Z"];
+
+pub fn show(ui: &mut Ui, lighting: &mut Lighting) {
+    ui.checkbox(&mut lighting.headlight, RichText::new("Headlight").strong())
+        .on_hover_text("Add a white light coming from the camera, it follows the camera around");
+
+    ui.add_space(4.0);
+    let mut removed = None;
+    for (i, light) in lighting.lights.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut light.enabled, "");
+            egui::ComboBox::from_id_source(("light_kind", i))
+                .selected_text(light.kind.label())
+                .show_ui(ui, |ui| {
+                    for kind in LightKind::ALL {
+                        ui.selectable_value(&mut light.kind, kind, kind.label());
+                    }
+                });
+            ui.color_edit_button_rgb(&mut light.color);
+            if ui.small_button("🗑").on_hover_text("Remove").clicked() {
+                removed = Some(i);
+            }
+        });
+        ui.horizontal(|ui| {
+            for (value, name) in light.vector.iter_mut().zip(AXES) {
+                ui.add(
+                    egui::DragValue::new(value)
+                        .speed(0.01)
+                        .range(-2.0..=2.0)
+                        .prefix(format!("{}: ", name)),
+                );
+            }
+        })
+        .response
+        .on_hover_text(match light.kind {
+            LightKind::Directional => "Direction the light comes from",
+            LightKind::Point => "Position of the light, in volume coordinates",
+        });
+        ui.add(egui::Slider::new(&mut light.intensity, 0.0..=3.0).text("Intensity"));
+    }
+    if let Some(i) = removed {
+        lighting.lights.remove(i);
+    }
+
+    if ui
+        .add_enabled(
+            lighting.lights.len() < MAX_LIGHTS,
+            egui::Button::new("Add Light"),
+        )
+        .clicked()
+    {
+        lighting.lights.push(Light::default());
+    }
+
+    ui.add_space(4.0);
+    ui.label(RichText::new("Material").strong());
+    let material = &mut lighting.material;
+    ui.add(egui::Slider::new(&mut material.ambient, 0.0..=1.0).text("Ambient"))
+        .on_hover_text("Light reaching every sample, whatever its orientation");
+    ui.add(egui::Slider::new(&mut material.diffuse, 0.0..=1.0).text("Diffuse"))
+        .on_hover_text("Light reflected by samples facing the lights");
+    ui.add(egui::Slider::new(&mut material.specular, 0.0..=1.0).text("Specular"))
+        .on_hover_text("Strength of the white highlights");
+    ui.add(
+        egui::Slider::new(&mut material.shininess, 1.0..=128.0)
+            .text("Shininess")
+            .logarithmic(true),
+    )
+    .on_hover_text("Higher values give smaller, sharper highlights");
+
+    ui.add_space(4.0);
+    show_file_controls(ui, ui.id().with("lighting_file"), lighting);
+}
+
+/// Path field with buttons to save the lighting to, or replace it with, a JSON file
+fn show_file_controls(ui: &mut Ui, id: egui::Id, lighting: &mut Lighting) {
+    let (mut path, mut status): (String, Option<String>) = ui
+        .data(|d| d.get_temp(id))
+        .unwrap_or(("lighting.json".to_string(), None));
+
+    ui.horizontal(|ui| {
+        ui.add(egui::TextEdit::singleline(&mut path).desired_width(160.0));
+        if ui.button("💾 Save").clicked() {
+            status = Some(match lighting.save_to_json_file(Path::new(&path)) {
+                Ok(()) => format!("Saved to {}", path),
+                Err(e) => format!("Save failed: {}", e),
+            });
+        }
+        if ui.button("📂 Load").clicked() {
+            status = Some(match Lighting::from_file(Path::new(&path)) {
+                Ok(loaded) => {
+                    *lighting = loaded;
+                    format!("Loaded {}", path)
+                }
+                Err(e) => format!("Load failed: {}", e),
+            });
+        }
+    });
+    if let Some(status) = &status {
+        ui.label(RichText::new(status).small());
+    }
+
+    ui.data_mut(|d| d.insert_temp(id, (path, status)));
+}
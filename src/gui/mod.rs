@@ -0,0 +1,557 @@
+use egui::epaint::Shadow;
+use egui::{Context, Visuals};
+use egui_wgpu::Renderer;
+use egui_wgpu::ScreenDescriptor;
+
+use egui_wgpu::wgpu;
+use egui_wgpu::wgpu::{Device, TextureFormat, TextureView};
+use egui_winit::winit::event::WindowEvent;
+use egui_winit::winit::window::Window;
+use egui_winit::State as EguiState;
+
+use crate::demos::{find_demo, DEMOS};
+use crate::gpu_context::GpuContext;
+use crate::slices::SliceViews;
+use crate::state::{
+    Isosurface, RenderMode, State, ToneMapping, MAX_ISOSURFACES, RENDER_SCALE_RANGE,
+};
+
+mod clipping;
+mod histogram;
+mod lighting;
+mod transfer_function_2d_editor;
+mod transfer_function_editor;
+
+pub struct GuiContext {
+    pub egui_ctx: Context,
+    pub state: EguiState,
+    renderer: Renderer,
+}
+
+impl GuiContext {
+    pub fn new(
+        device: &Device,
+        output_color_format: TextureFormat,
+        output_depth_format: Option<TextureFormat>,
+        msaa_samples: u32,
+        window: &Window,
+    ) -> GuiContext {
+        let egui_context = Context::default();
+        let id = egui_context.viewport_id();
+
+        const BORDER_RADIUS: f32 = 2.0;
+
+        let visuals = Visuals {
+            window_rounding: egui::Rounding::same(BORDER_RADIUS),
+            window_shadow: Shadow::NONE,
+            // menu_rounding: todo!(),
+            ..Default::default()
+        };
+
+        egui_context.set_visuals(visuals);
+
+        let egui_state = EguiState::new(egui_context.clone(), id, &window, None, None);
+
+        // egui_state.set_pixels_per_point(window.scale_factor() as f32);
+        let egui_renderer = Renderer::new(
+            device,
+            output_color_format,
+            output_depth_format,
+            msaa_samples,
+        );
+
+        GuiContext {
+            egui_ctx: egui_context,
+            state: egui_state,
+            renderer: egui_renderer,
+        }
+    }
+
+    pub fn process_input(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        let r = self.state.on_window_event(window, event);
+        r.consumed
+    }
+
+    pub fn draw(
+        &mut self,
+        ctx: &GpuContext,
+        state: &mut State,
+        window_surface_view: &TextureView,
+        screen_descriptor: ScreenDescriptor,
+    ) {
+        let mut encoder = ctx
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+        let window = ctx.window();
+        let queue = &ctx.queue;
+        let device = &ctx.device;
+        {
+            let raw_input = self.state.take_egui_input(window);
+            let full_output = self.egui_ctx.run(raw_input, |ui| {
+                egui::Window::new("Volym")
+                    .vscroll(true)
+                    .default_open(true)
+                    .default_width(200.0)
+                    .default_height(300.0)
+                    .resizable(true)
+                    .show(ui, |ui| {
+                        show_ui(state, ui);
+                    });
+                if state.clipping.show_handles {
+                    clipping::show_handles(ui, state);
+                }
+                if !state.shader_errors.is_empty() {
+                    show_shader_errors(ui, state);
+                }
+                if let Some(error) = &state.frame_error {
+                    show_frame_error(ui, error);
+                }
+            });
+            self.state
+                .handle_platform_output(window, full_output.platform_output);
+
+            let tris = self
+                .egui_ctx
+                .tessellate(full_output.shapes, full_output.pixels_per_point);
+            for (id, image_delta) in &full_output.textures_delta.set {
+                self.renderer
+                    .update_texture(device, queue, *id, image_delta);
+            }
+            self.renderer
+                .update_buffers(device, queue, &mut encoder, &tris, &screen_descriptor);
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: window_surface_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                label: Some("egui main render pass"),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.renderer.render(&mut rpass, &tris, &screen_descriptor);
+            drop(rpass);
+            for x in &full_output.textures_delta.free {
+                self.renderer.free_texture(x)
+            }
+        }
+        ctx.queue.submit(Some(encoder.finish()));
+    }
+}
+
+use egui::{Color32, RichText, Ui, Vec2};
+
+fn show_ui(state: &mut State, ui: &mut egui::Ui) {
+    let histogram_heights = state
+        .histogram
+        .as_ref()
+        .map(|h| h.heights(state.histogram_segment, state.histogram_log_scale));
+
+    ui.vertical(|ui| {
+        ui.add_space(4.0);
+        let mut selected = state.demo.name;
+        egui::ComboBox::from_label(RichText::new("Demo").strong())
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for demo in DEMOS {
+                    ui.selectable_value(&mut selected, demo.name, demo.name)
+                        .on_hover_text(demo.description);
+                }
+            })
+            .response
+            .on_hover_text(state.demo.description);
+        if selected != state.demo.name {
+            state.requested_demo = find_demo(selected).ok();
+        }
+
+        // Camera Controls Section
+        ui.add_space(4.0);
+        egui::CollapsingHeader::new(RichText::new("📷 Camera Controls").heading().size(18.0))
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.add_space(8.0);
+
+                // Position controls in a grid
+                egui::Grid::new("camera_position_grid")
+                    .num_columns(2)
+                    .spacing([8.0, 4.0])
+                    .show(ui, |ui| {
+                        let mut pos = [
+                            state.camera.position.x,
+                            state.camera.position.y,
+                            state.camera.position.z,
+                        ];
+
+                        for (i, (axis, val)) in
+                            ["X", "Y", "Z"].iter().zip(pos.iter_mut()).enumerate()
+                        {
+                            ui.label(RichText::new(*axis).strong());
+                            if ui
+                                .add(
+                                    egui::DragValue::new(val)
+                                        .speed(0.1)
+                                        .prefix(format!("{}: ", axis))
+                                        .range(-100.0..=100.0),
+                                )
+                                .changed()
+                            {
+                                state.camera.position[i] = *val;
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                ui.add_space(4.0);
+
+                // Copy button with improved styling
+                if ui
+                    .add(
+                        egui::Button::new(
+                            RichText::new("📋 Copy Position").text_style(egui::TextStyle::Button),
+                        )
+                        .min_size(Vec2::new(120.0, 24.0)),
+                    )
+                    .clicked()
+                {
+                    let position_text = format!(
+                        "{:.3}, {:.3}, {:.3}",
+                        state.camera.position.x, state.camera.position.y, state.camera.position.z
+                    );
+                    ui.output_mut(|o| o.copied_text = position_text);
+                }
+            });
+
+        // Rendering Settings Section
+        ui.add_space(8.0);
+        egui::CollapsingHeader::new(RichText::new("🎨 Rendering Settings").heading().size(18.0))
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.add_space(8.0);
+
+                add_setting_group(ui, "Primary Controls", |ui| {
+                    egui::ComboBox::from_label(RichText::new("Render Mode").strong())
+                        .selected_text(state.render_mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in RenderMode::ALL {
+                                ui.selectable_value(&mut state.render_mode, mode, mode.label());
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Composite through the transfer function, or project the densities \
+                             above the threshold along each ray",
+                        );
+
+                    if state.render_mode == RenderMode::Isosurface {
+                        show_isosurfaces(ui, &mut state.isosurfaces);
+                    }
+
+                    ui.checkbox(
+                        &mut state.use_importance_coloring,
+                        RichText::new("Importance Coloring").strong(),
+                    )
+                    .on_hover_text("Enable coloring based on importance values");
+
+                    let opacity_response = ui
+                        .add_enabled(
+                            !state.use_importance_rendering,
+                            egui::Checkbox::new(
+                                &mut state.use_opacity,
+                                RichText::new("Opacity").strong(),
+                            ),
+                        )
+                        .on_hover_text("Control opacity of rendered elements");
+
+                    if opacity_response.changed() && state.use_importance_rendering {
+                        state.use_opacity = true;
+                    }
+
+                    // Importance rendering with dependencies
+                    let imp_render_response = ui
+                        .checkbox(
+                            &mut state.use_importance_rendering,
+                            RichText::new("Importance Rendering").strong(),
+                        )
+                        .on_hover_text("Enable advanced importance-based rendering");
+
+                    if imp_render_response.changed() && state.use_importance_rendering {
+                        state.use_opacity = true;
+                    }
+                });
+
+                ui.add_space(8.0);
+
+                add_setting_group(ui, "Advanced Controls", |ui| {
+                    ui.add_enabled(
+                        state.use_importance_rendering,
+                        egui::Checkbox::new(
+                            &mut state.use_cone_importance_check,
+                            RichText::new("Cone Importance Check").strong(),
+                        ),
+                    )
+                    .on_hover_text(
+                        "Enable cone-based importance checking (requires Importance Rendering)",
+                    );
+
+                    ui.checkbox(
+                        &mut state.use_gaussian_smoothing,
+                        RichText::new("Gaussian Smoothing").strong(),
+                    )
+                    .on_hover_text("Apply Gaussian smoothing to the rendered output");
+
+                    ui.checkbox(
+                        &mut state.use_precomputed_gradients,
+                        RichText::new("Precomputed Gradients").strong(),
+                    )
+                    .on_hover_text(
+                        "Sample gradients computed at load time instead of six volume samples",
+                    );
+
+                    ui.add_enabled(
+                        !state.use_gaussian_smoothing,
+                        egui::Checkbox::new(
+                            &mut state.use_empty_space_skipping,
+                            RichText::new("Empty Space Skipping").strong(),
+                        ),
+                    )
+                    .on_hover_text(
+                        "Jump over blocks of voxels that are below the threshold or transparent \
+                         (not with Gaussian Smoothing)",
+                    );
+                });
+
+                ui.add_space(8.0);
+
+                // Parameters section
+                add_setting_group(ui, "Parameters", |ui| {
+                    ui.add_enabled(
+                        state.use_importance_rendering,
+                        egui::Slider::new(&mut state.importance_check_ahead_steps, 2..=25)
+                            .text(RichText::new("Look Ahead Steps").strong())
+                            .clamp_to_range(true),
+                    )
+                    .on_hover_text("Number of steps to look ahead for importance rendering");
+
+                    ui.add(
+                        egui::Slider::new(&mut state.raymarching_step_size, 0.001..=0.1)
+                            .text(RichText::new("Raymarching Step Size").strong())
+                            .logarithmic(true),
+                    )
+                    .on_hover_text("Size of steps used in raymarching algorithm");
+
+                    ui.add(
+                        egui::Slider::new(&mut state.render_scale, RENDER_SCALE_RANGE)
+                            .text(RichText::new("Render Scale").strong())
+                            .step_by(0.05),
+                    )
+                    .on_hover_text(
+                        "Resolution of the rendering relative to the window's, scaled to fit it",
+                    );
+                    ui.checkbox(&mut state.dynamic_resolution, "Dynamic Resolution")
+                        .on_hover_text(
+                            "Halve the render scale while the camera moves, back to full once \
+                             it stops",
+                        );
+                    ui.checkbox(&mut state.use_jittering, "Jittered Rays")
+                        .on_hover_text(
+                            "Offset every ray's start by a fraction of the step size, trading \
+                             banding for noise",
+                        );
+                    ui.checkbox(&mut state.use_accumulation, "Accumulate Frames")
+                        .on_hover_text(
+                            "Average the frames rendered while the view doesn't change, which \
+                             smooths the noise of jittered rays",
+                        );
+
+                    if let Some(heights) = &histogram_heights {
+                        histogram::show_with_threshold(ui, heights, state.density_threshold);
+                    }
+                    ui.add(
+                        egui::Slider::new(&mut state.density_threshold, 0.005..=1.0)
+                            .text(RichText::new("Density Threshold").strong()),
+                    )
+                    .on_hover_text("Minimum density threshold for rendering");
+                });
+
+                ui.add_space(8.0);
+
+                // Display section
+                add_setting_group(ui, "Display", |ui| {
+                    egui::ComboBox::from_label(RichText::new("Tone Mapping").strong())
+                        .selected_text(state.tone_mapping.label())
+                        .show_ui(ui, |ui| {
+                            for tone_mapping in ToneMapping::ALL {
+                                ui.selectable_value(
+                                    &mut state.tone_mapping,
+                                    tone_mapping,
+                                    tone_mapping.label(),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text("Curve bringing colors above 1 back to the display's range");
+                    ui.add(
+                        egui::Slider::new(&mut state.exposure, -4.0..=4.0)
+                            .text(RichText::new("Exposure").strong())
+                            .step_by(0.1),
+                    )
+                    .on_hover_text("In stops, each one doubles the brightness");
+                    ui.add(
+                        egui::Slider::new(&mut state.gamma, 0.5..=3.0)
+                            .text(RichText::new("Gamma").strong())
+                            .step_by(0.05),
+                    )
+                    .on_hover_text(
+                        "Applied after the tone mapping, 1 keeps the colors as they are",
+                    );
+                });
+            });
+
+        // Clipping Section
+        ui.add_space(8.0);
+        egui::CollapsingHeader::new(RichText::new("✂ Clipping").heading().size(18.0))
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.add_space(8.0);
+                clipping::show(ui, &mut state.clipping);
+            });
+
+        // Lighting Section
+        ui.add_space(8.0);
+        egui::CollapsingHeader::new(RichText::new("💡 Lighting").heading().size(18.0))
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.add_space(8.0);
+                lighting::show(ui, &mut state.lighting);
+            });
+
+        // Slices Section
+        ui.add_space(8.0);
+        egui::CollapsingHeader::new(RichText::new("🔪 Slices").heading().size(18.0))
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.add_space(8.0);
+                show_slices(ui, &mut state.slices);
+            });
+
+        // Transfer Function Section
+        ui.add_space(8.0);
+        egui::CollapsingHeader::new(RichText::new("🌈 Transfer Function").heading().size(18.0))
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.add_space(8.0);
+                ui.checkbox(
+                    &mut state.use_transfer_function_2d,
+                    RichText::new("Density x Gradient (2D)").strong(),
+                )
+                .on_hover_text("Tell material boundaries apart from interiors of similar density");
+                ui.add_space(4.0);
+
+                if state.use_transfer_function_2d {
+                    transfer_function_2d_editor::show(ui, &mut state.transfer_function_2d);
+                } else {
+                    histogram::show_options(ui, state);
+                    transfer_function_editor::show(
+                        ui,
+                        &mut state.transfer_function,
+                        histogram_heights.as_deref(),
+                    );
+                }
+            });
+    });
+}
+
+/// Isovalue and color of every surface, surfaces can be added up to [`MAX_ISOSURFACES`]
+fn show_isosurfaces(ui: &mut Ui, isosurfaces: &mut Vec<Isosurface>) {
+    let mut removed = None;
+    for (i, isosurface) in isosurfaces.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.color_edit_button_rgba_unmultiplied(&mut isosurface.color);
+            ui.add(egui::Slider::new(&mut isosurface.value, 0.0..=1.0).text("Isovalue"));
+            if ui.small_button("🗑").on_hover_text("Remove").clicked() {
+                removed = Some(i);
+            }
+        });
+    }
+    if let Some(i) = removed {
+        isosurfaces.remove(i);
+    }
+
+    if ui
+        .add_enabled(
+            isosurfaces.len() < MAX_ISOSURFACES,
+            egui::Button::new("Add Isosurface"),
+        )
+        .clicked()
+    {
+        let value = isosurfaces
+            .last()
+            .map_or(0.5, |last| (last.value + 0.1).min(1.0));
+        isosurfaces.push(Isosurface::new(value, isosurfaces.len()));
+    }
+}
+
+fn add_setting_group(ui: &mut Ui, title: &str, add_contents: impl FnOnce(&mut Ui)) {
+    ui.group(|ui| {
+        ui.label(RichText::new(title).size(14.0).color(Color32::LIGHT_BLUE));
+        ui.add_space(4.0);
+        add_contents(ui);
+    });
+}
+
+fn show_slices(ui: &mut Ui, slices: &mut SliceViews) {
+    ui.checkbox(&mut slices.enabled, RichText::new("Show Slices").strong())
+        .on_hover_text("Axial, coronal and sagittal slices through the probe, right of the volume");
+    ui.add_enabled_ui(slices.enabled, |ui| {
+        for (value, name) in slices
+            .probe
+            .iter_mut()
+            .zip(["Probe X", "Probe Y", "Probe Z"])
+        {
+            ui.add(egui::Slider::new(value, 0.0..=1.0).text(name));
+        }
+        ui.label("Click or drag in a slice to move the probe, scroll to move through it");
+        ui.checkbox(
+            &mut slices.use_transfer_function,
+            "Transfer Function Overlay",
+        )
+        .on_hover_text("Color the densities with the transfer function");
+        ui.checkbox(&mut slices.show_segments, "Segment Overlay")
+            .on_hover_text("Tint the segments by their importance");
+    });
+}
+
+/// Errors of the shaders edited since start-up, until they build again
+fn show_shader_errors(ctx: &Context, state: &State) {
+    egui::Window::new(RichText::new("⚠ Shader Errors").color(Color32::LIGHT_RED))
+        .default_width(500.0)
+        .resizable(true)
+        .show(ctx, |ui| {
+            ui.label("Still rendering with the last shaders that built");
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (path, error) in &state.shader_errors {
+                    ui.label(RichText::new(path.to_string_lossy()).strong());
+                    ui.label(RichText::new(error).monospace());
+                }
+            });
+        });
+}
+
+/// Error of the demo's last frame, shown until a frame computes again
+fn show_frame_error(ctx: &Context, error: &str) {
+    egui::Window::new(RichText::new("⚠ Frame Skipped").color(Color32::LIGHT_RED))
+        .default_width(500.0)
+        .resizable(true)
+        .show(ctx, |ui| {
+            ui.label("Still showing the last frame that was computed");
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.label(RichText::new(error).monospace());
+            });
+        });
+}
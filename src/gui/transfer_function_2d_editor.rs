@@ -0,0 +1,176 @@
+/// Editor for the widgets of a 2D transfer function, density along x and gradient magnitude along y
+use cgmath::Vector4;
+use egui::{Color32, Pos2, Rect, RichText, Sense, Stroke, Ui, Vec2};
+
+use crate::transfer_function::two_dimensional::{TransferFunction2D, TransferWidget2D};
+
+const HANDLE_SIZE: f32 = 8.0;
+const PREVIEW_COLUMNS: usize = 64;
+const PREVIEW_ROWS: usize = 32;
+
+enum Edit {
+    Move(usize, Vec2),
+    Resize(usize, Pos2),
+    Remove(usize),
+    Add(Pos2),
+}
+
+/// Dragging a widget moves it, dragging its top right corner resizes it.
+/// Double click adds a widget, right click removes it.
+pub fn show(ui: &mut Ui, tf: &mut TransferFunction2D) {
+    let id = ui.id().with("transfer_function_2d_editor");
+    let mut selection: Option<usize> = ui.data(|d| d.get_temp(id)).flatten();
+
+    let width = ui.available_width().max(200.0);
+    let (rect, response) = ui.allocate_exact_size(Vec2::new(width, width / 2.0), Sense::click());
+    let painter = ui.painter().clone();
+
+    // Domain <-> screen, gradient magnitude grows upwards
+    let to_screen = |density: f32, gradient: f32| {
+        Pos2::new(
+            rect.left() + density * rect.width(),
+            rect.bottom() - gradient * rect.height(),
+        )
+    };
+    let to_domain = |pos: Pos2| {
+        (
+            ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0),
+            ((rect.bottom() - pos.y) / rect.height()).clamp(0.0, 1.0),
+        )
+    };
+
+    painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+    let cell = Vec2::new(
+        rect.width() / PREVIEW_COLUMNS as f32,
+        rect.height() / PREVIEW_ROWS as f32,
+    );
+    for row in 0..PREVIEW_ROWS {
+        for column in 0..PREVIEW_COLUMNS {
+            let color = tf.get(
+                (column as f32 + 0.5) / PREVIEW_COLUMNS as f32,
+                (row as f32 + 0.5) / PREVIEW_ROWS as f32,
+            );
+            if color.w <= 0.0 {
+                continue;
+            }
+            let min = Pos2::new(
+                rect.left() + column as f32 * cell.x,
+                rect.bottom() - (row + 1) as f32 * cell.y,
+            );
+            painter.rect_filled(Rect::from_min_size(min, cell), 0.0, to_color32(color));
+        }
+    }
+
+    let mut edits = Vec::new();
+    for (i, widget) in tf.widgets.iter().enumerate() {
+        let widget_rect = Rect::from_two_pos(
+            to_screen(widget.density[0], widget.gradient[0]),
+            to_screen(widget.density[1], widget.gradient[1]),
+        );
+        let handle_rect = Rect::from_center_size(widget_rect.right_top(), Vec2::splat(HANDLE_SIZE));
+
+        let body = ui.interact(widget_rect, id.with(("widget", i)), Sense::click_and_drag());
+        let handle = ui.interact(handle_rect, id.with(("handle", i)), Sense::drag());
+
+        if handle.dragged() {
+            if let Some(pointer) = handle.interact_pointer_pos() {
+                edits.push(Edit::Resize(i, pointer));
+            }
+        } else if body.dragged() {
+            edits.push(Edit::Move(i, body.drag_delta()));
+        }
+        if body.clicked() || body.drag_started() || handle.drag_started() {
+            selection = Some(i);
+        }
+        if body.secondary_clicked() {
+            edits.push(Edit::Remove(i));
+        }
+
+        let stroke = if selection == Some(i) || body.hovered() {
+            Stroke::new(2.0_f32, Color32::LIGHT_BLUE)
+        } else {
+            Stroke::new(1.0_f32, Color32::WHITE)
+        };
+        painter.rect_stroke(widget_rect, 0.0, stroke);
+        painter.rect_filled(handle_rect, 0.0, stroke.color);
+    }
+
+    if response.double_clicked() {
+        if let Some(pointer) = response.interact_pointer_pos() {
+            edits.push(Edit::Add(pointer));
+        }
+    }
+
+    for edit in edits {
+        match edit {
+            Edit::Move(i, delta) => {
+                let widget = &mut tf.widgets[i];
+                let delta_density = delta.x / rect.width();
+                let delta_gradient = -delta.y / rect.height();
+                // Keep the size, stop at the borders of the domain
+                let delta_density =
+                    delta_density.clamp(-widget.density[0], 1.0 - widget.density[1]);
+                let delta_gradient =
+                    delta_gradient.clamp(-widget.gradient[0], 1.0 - widget.gradient[1]);
+                widget.density = widget.density.map(|d| d + delta_density);
+                widget.gradient = widget.gradient.map(|g| g + delta_gradient);
+            }
+            Edit::Resize(i, pointer) => {
+                let widget = &mut tf.widgets[i];
+                let (density, gradient) = to_domain(pointer);
+                widget.density[1] = density.max(widget.density[0]);
+                widget.gradient[1] = gradient.max(widget.gradient[0]);
+            }
+            Edit::Remove(i) => {
+                tf.widgets.remove(i);
+                selection = None;
+            }
+            Edit::Add(pointer) => {
+                let (density, gradient) = to_domain(pointer);
+                let density = density.clamp(0.1, 0.9);
+                let gradient = gradient.clamp(0.1, 0.9);
+                tf.widgets.push(TransferWidget2D::new(
+                    [density - 0.1, density + 0.1],
+                    [gradient - 0.1, gradient + 0.1],
+                    Vector4::new(1.0, 1.0, 1.0, 0.5),
+                ));
+                selection = Some(tf.widgets.len() - 1);
+            }
+        }
+    }
+
+    ui.add_space(4.0);
+    match selection.and_then(|i| tf.widgets.get_mut(i)) {
+        Some(widget) => {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Color").strong());
+                let mut rgb = [widget.color.x, widget.color.y, widget.color.z];
+                if egui::color_picker::color_edit_button_rgb(ui, &mut rgb).changed() {
+                    widget.color = Vector4::new(rgb[0], rgb[1], rgb[2], widget.color.w);
+                }
+                ui.add(egui::Slider::new(&mut widget.color.w, 0.0..=1.0).text("Opacity"));
+            });
+        }
+        None => {
+            ui.label("Double click to add a widget, right click to remove it");
+        }
+    }
+
+    ui.add(
+        egui::Slider::new(&mut tf.max_gradient_magnitude, 0.01..=1.0)
+            .logarithmic(true)
+            .text(RichText::new("Max Gradient").strong()),
+    )
+    .on_hover_text("Gradient magnitude, in density change per voxel, at the top of the editor");
+
+    ui.data_mut(|d| d.insert_temp(id, selection));
+}
+
+fn to_color32(color: Vector4<f32>) -> Color32 {
+    Color32::from_rgba_unmultiplied(
+        (color.x * 255.0) as u8,
+        (color.y * 255.0) as u8,
+        (color.z * 255.0) as u8,
+        (color.w * 255.0) as u8,
+    )
+}
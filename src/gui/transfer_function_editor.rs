@@ -0,0 +1,295 @@
+/// Editor for the control points of a transfer function
+use std::path::Path;
+
+use cgmath::Vector4;
+use egui::{Color32, Pos2, Rect, RichText, Sense, Shape, Stroke, Ui, Vec2};
+
+use super::histogram;
+use crate::transfer_function::{Interpolation, TransferControlPoint, TransferFunction};
+
+const POINT_RADIUS: f32 = 5.0;
+const CURVE_HEIGHT: f32 = 80.0;
+const BAR_HEIGHT: f32 = 14.0;
+const PREVIEW_SEGMENTS: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selection {
+    Rgb(usize),
+    Alpha(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PointKind {
+    Rgb,
+    Alpha,
+}
+
+enum Edit {
+    Move(PointKind, usize, Pos2),
+    Remove(PointKind, usize),
+    Add(PointKind, Pos2),
+}
+
+/// Alpha points are edited on the curve, RGB points on the bar below it.
+/// Double click adds a point, right click removes it, and dragging moves it.
+/// The normalized histogram `heights`, if any, are drawn behind the curve.
+pub fn show(ui: &mut Ui, tf: &mut TransferFunction, histogram_heights: Option<&[f32]>) {
+    let id = ui.id().with("transfer_function_editor");
+    let mut selection: Option<Selection> = ui.data(|d| d.get_temp(id)).flatten();
+
+    let width = ui.available_width().max(200.0);
+    let (curve_rect, curve_response) =
+        ui.allocate_exact_size(Vec2::new(width, CURVE_HEIGHT), Sense::click());
+    let (bar_rect, bar_response) =
+        ui.allocate_exact_size(Vec2::new(width, BAR_HEIGHT), Sense::click());
+    let painter = ui.painter().clone();
+
+    // Preview, colors with their opacity on the curve, and opaque on the bar
+    painter.rect_filled(curve_rect, 0.0, Color32::from_gray(20));
+    for i in 0..PREVIEW_SEGMENTS {
+        let from = i as f32 / PREVIEW_SEGMENTS as f32;
+        let to = (i + 1) as f32 / PREVIEW_SEGMENTS as f32;
+        let color = tf.get((from + to) / 2.0);
+        let x_range = curve_rect.left() + from * width..=curve_rect.left() + to * width;
+        painter.rect_filled(
+            Rect::from_x_y_ranges(x_range.clone(), curve_rect.y_range()),
+            0.0,
+            to_color32(color),
+        );
+        painter.rect_filled(
+            Rect::from_x_y_ranges(x_range, bar_rect.y_range()),
+            0.0,
+            to_color32(Vector4::new(color.x, color.y, color.z, 1.0)),
+        );
+    }
+
+    if let Some(heights) = histogram_heights {
+        histogram::paint(&painter, curve_rect, heights, histogram::BAR_COLOR);
+    }
+
+    let alpha_positions: Vec<Pos2> = tf
+        .alpha_points()
+        .iter()
+        .map(|p| {
+            Pos2::new(
+                curve_rect.left() + p.iso_value * width,
+                curve_rect.bottom() - p.color.w * CURVE_HEIGHT,
+            )
+        })
+        .collect();
+    let rgb_positions: Vec<Pos2> = tf
+        .rgb_points()
+        .iter()
+        .map(|p| Pos2::new(bar_rect.left() + p.iso_value * width, bar_rect.center().y))
+        .collect();
+    painter.add(Shape::line(
+        alpha_positions.clone(),
+        Stroke::new(1.5_f32, Color32::WHITE),
+    ));
+
+    let mut edits = Vec::new();
+    for (kind, positions) in [
+        (PointKind::Alpha, &alpha_positions),
+        (PointKind::Rgb, &rgb_positions),
+    ] {
+        for (i, pos) in positions.iter().enumerate() {
+            let rect = Rect::from_center_size(*pos, Vec2::splat(POINT_RADIUS * 3.0));
+            let response = ui.interact(
+                rect,
+                id.with((kind == PointKind::Rgb, i)),
+                Sense::click_and_drag(),
+            );
+
+            if response.dragged() {
+                if let Some(pointer) = response.interact_pointer_pos() {
+                    edits.push(Edit::Move(kind, i, pointer));
+                }
+            }
+            if response.clicked() || response.drag_started() {
+                selection = Some(match kind {
+                    PointKind::Rgb => Selection::Rgb(i),
+                    PointKind::Alpha => Selection::Alpha(i),
+                });
+            }
+            if response.secondary_clicked() {
+                edits.push(Edit::Remove(kind, i));
+            }
+
+            let is_selected = match (selection, kind) {
+                (Some(Selection::Rgb(s)), PointKind::Rgb) => s == i,
+                (Some(Selection::Alpha(s)), PointKind::Alpha) => s == i,
+                _ => false,
+            };
+            let fill = match kind {
+                PointKind::Rgb => {
+                    let c = tf.rgb_points()[i].color;
+                    to_color32(Vector4::new(c.x, c.y, c.z, 1.0))
+                }
+                PointKind::Alpha => Color32::WHITE,
+            };
+            let stroke = if is_selected || response.hovered() {
+                Stroke::new(2.0_f32, Color32::LIGHT_BLUE)
+            } else {
+                Stroke::new(1.0_f32, Color32::BLACK)
+            };
+            painter.circle(*pos, POINT_RADIUS, fill, stroke);
+        }
+    }
+
+    if curve_response.double_clicked() {
+        if let Some(pointer) = curve_response.interact_pointer_pos() {
+            edits.push(Edit::Add(PointKind::Alpha, pointer));
+        }
+    }
+    if bar_response.double_clicked() {
+        if let Some(pointer) = bar_response.interact_pointer_pos() {
+            edits.push(Edit::Add(PointKind::Rgb, pointer));
+        }
+    }
+
+    let to_iso = |pos: Pos2| ((pos.x - curve_rect.left()) / width).clamp(0.0, 1.0);
+    let to_alpha = |pos: Pos2| ((curve_rect.bottom() - pos.y) / CURVE_HEIGHT).clamp(0.0, 1.0);
+
+    let mut changed = false;
+    for edit in edits {
+        match edit {
+            Edit::Move(kind, i, pointer) => {
+                let points = points_mut(tf, kind);
+                // Points can't cross their neighbours, so they stay sorted
+                let min = if i > 0 { points[i - 1].iso_value } else { 0.0 };
+                let max = points.get(i + 1).map_or(1.0, |p| p.iso_value);
+                points[i].iso_value = to_iso(pointer).clamp(min, max);
+                if kind == PointKind::Alpha {
+                    points[i].color.w = to_alpha(pointer);
+                }
+            }
+            // At least two points are needed to interpolate
+            Edit::Remove(kind, i) => {
+                let points = points_mut(tf, kind);
+                if points.len() <= 2 {
+                    continue;
+                }
+                points.remove(i);
+                selection = None;
+            }
+            Edit::Add(kind, pointer) => {
+                let iso_value = to_iso(pointer);
+                let color = match kind {
+                    PointKind::Rgb => tf.get(iso_value),
+                    PointKind::Alpha => Vector4::new(0.0, 0.0, 0.0, to_alpha(pointer)),
+                };
+                let points = points_mut(tf, kind);
+                let index = points.partition_point(|p| p.iso_value < iso_value);
+                points.insert(index, TransferControlPoint::new(color, iso_value));
+                selection = Some(match kind {
+                    PointKind::Rgb => Selection::Rgb(index),
+                    PointKind::Alpha => Selection::Alpha(index),
+                });
+            }
+        }
+        changed = true;
+    }
+
+    ui.add_space(4.0);
+    match selection {
+        Some(Selection::Rgb(i)) if i < tf.rgb_points().len() => {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Color").strong());
+                let point = &mut tf.rgb_points_mut()[i];
+                let mut rgb = [point.color.x, point.color.y, point.color.z];
+                if egui::color_picker::color_edit_button_rgb(ui, &mut rgb).changed() {
+                    point.color = Vector4::new(rgb[0], rgb[1], rgb[2], point.color.w);
+                    changed = true;
+                }
+                ui.label(format!("at {:.3}", point.iso_value));
+            });
+        }
+        Some(Selection::Alpha(i)) if i < tf.alpha_points().len() => {
+            let point = tf.alpha_points()[i];
+            ui.label(format!(
+                "Opacity {:.3} at {:.3}",
+                point.color.w, point.iso_value
+            ));
+        }
+        _ => {
+            ui.label("Double click to add a point, right click to remove it");
+        }
+    }
+
+    ui.horizontal(|ui| {
+        ui.label("Interpolation");
+        egui::ComboBox::from_id_source(id.with("interpolation"))
+            .selected_text(format!("{:?}", tf.interpolation))
+            .show_ui(ui, |ui| {
+                for interpolation in [Interpolation::Linear, Interpolation::Constant] {
+                    changed |= ui
+                        .selectable_value(
+                            &mut tf.interpolation,
+                            interpolation,
+                            format!("{:?}", interpolation),
+                        )
+                        .changed();
+                }
+            });
+    });
+
+    if changed {
+        tf.build();
+    }
+    ui.data_mut(|d| d.insert_temp(id, selection));
+
+    show_preset_controls(ui, id.with("preset"), tf);
+}
+
+/// Path field with buttons to save the function to, or replace it with, a JSON preset
+fn show_preset_controls(ui: &mut Ui, id: egui::Id, tf: &mut TransferFunction) {
+    let (mut path, mut status): (String, Option<String>) = ui
+        .data(|d| d.get_temp(id))
+        .unwrap_or(("transfer_function.json".to_string(), None));
+
+    ui.horizontal(|ui| {
+        ui.add(egui::TextEdit::singleline(&mut path).desired_width(160.0));
+        if ui.button("💾 Save").clicked() {
+            status = Some(match tf.save_to_json_file(Path::new(&path)) {
+                Ok(()) => format!("Saved to {}", path),
+                Err(e) => format!("Save failed: {}", e),
+            });
+        }
+        if ui.button("📂 Load").clicked() {
+            status = Some(match TransferFunction::from_file(Path::new(&path)) {
+                Ok(loaded) => {
+                    // The demo decides the resolution of the uploaded function, and the
+                    // window its values are in
+                    let loaded = match tf.value_window {
+                        Some(window) => loaded.mapped_to_window(window),
+                        None => loaded,
+                    };
+                    *tf = loaded.with_max_density(tf.max_density);
+                    format!("Loaded {}", path)
+                }
+                Err(e) => format!("Load failed: {}", e),
+            });
+        }
+    });
+    if let Some(status) = &status {
+        ui.label(RichText::new(status).small());
+    }
+
+    ui.data_mut(|d| d.insert_temp(id, (path, status)));
+}
+
+fn points_mut(tf: &mut TransferFunction, kind: PointKind) -> &mut Vec<TransferControlPoint> {
+    match kind {
+        PointKind::Rgb => tf.rgb_points_mut(),
+        PointKind::Alpha => tf.alpha_points_mut(),
+    }
+}
+
+fn to_color32(color: Vector4<f32>) -> Color32 {
+    Color32::from_rgba_unmultiplied(
+        (color.x * 255.0) as u8,
+        (color.y * 255.0) as u8,
+        (color.z * 255.0) as u8,
+        (color.w * 255.0) as u8,
+    )
+}
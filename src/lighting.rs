@@ -0,0 +1,208 @@
+/// Lights and material of the Blinn-Phong shading of the isosurfaces and of the composited
+/// volume. Directions and positions are in volume coordinates, where the volume's bounding
+/// box spans 0..=1 on every axis.
+use std::path::Path;
+
+use color_eyre::eyre::eyre;
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+/// Lights that can be used at once, not counting the headlight
+pub const MAX_LIGHTS: usize = 4;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LightKind {
+    /// Infinitely far away, it lights every sample from the same direction
+    #[default]
+    Directional,
+    /// Lights every sample from its position, without falloff
+    Point,
+}
+
+impl LightKind {
+    pub const ALL: [Self; 2] = [Self::Directional, Self::Point];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Directional => "Directional",
+            Self::Point => "Point",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Light {
+    pub kind: LightKind,
+    /// Direction the light comes from for directional lights, position for point lights
+    pub vector: [f32; 3],
+    pub color: [f32; 3],
+    /// Multiplies the color
+    pub intensity: f32,
+    pub enabled: bool,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            kind: LightKind::Directional,
+            vector: [1.0, 1.0, 1.0],
+            color: [1.0; 3],
+            intensity: 1.0,
+            enabled: true,
+        }
+    }
+}
+
+/// Coefficients of the Blinn-Phong terms, shared by every light
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Material {
+    pub ambient: f32,
+    pub diffuse: f32,
+    pub specular: f32,
+    /// Exponent of the specular term, higher values give smaller highlights
+    pub shininess: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            ambient: 0.2,
+            diffuse: 0.7,
+            specular: 0.4,
+            shininess: 24.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lighting {
+    /// Up to [`MAX_LIGHTS`]
+    pub lights: Vec<Light>,
+    /// A white light coming from the camera, on top of `lights`
+    pub headlight: bool,
+    pub material: Material,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            lights: vec![Light::default()],
+            headlight: false,
+            material: Material::default(),
+        }
+    }
+}
+
+impl Lighting {
+    pub fn validate(&self) -> Result<()> {
+        if self.lights.len() > MAX_LIGHTS {
+            return Err(eyre!(
+                "{} lights given, at most {} can be used",
+                self.lights.len(),
+                MAX_LIGHTS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reads a lighting saved with [`Self::save_to_json_file`], missing fields keep their
+    /// defaults
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let lighting: Self = serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|e| eyre!("Invalid lighting {:?}: {}", path, e))?;
+        lighting.validate()?;
+        Ok(lighting)
+    }
+
+    pub fn save_to_json_file(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_missing_fields_with_defaults() {
+        let lighting: Lighting = serde_json::from_str(
+            r#"{
+                "headlight": true,
+                "lights": [{ "kind": "point", "vector": [0.5, 0.5, 2.0] }, {}],
+                "material": { "shininess": 8.0 }
+            }"#,
+        )
+        .unwrap();
+
+        assert!(lighting.headlight);
+        assert_eq!(
+            lighting.lights,
+            [
+                Light {
+                    kind: LightKind::Point,
+                    vector: [0.5, 0.5, 2.0],
+                    ..Default::default()
+                },
+                Light::default()
+            ]
+        );
+        assert_eq!(
+            lighting.material,
+            Material {
+                shininess: 8.0,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<Lighting>("{}").unwrap(),
+            Lighting::default()
+        );
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let lighting = Lighting {
+            lights: vec![
+                Light {
+                    kind: LightKind::Point,
+                    color: [1.0, 0.8, 0.6],
+                    intensity: 1.5,
+                    ..Default::default()
+                },
+                Light {
+                    enabled: false,
+                    ..Default::default()
+                },
+            ],
+            headlight: true,
+            material: Material {
+                ambient: 0.1,
+                ..Default::default()
+            },
+        };
+        let json = serde_json::to_string(&lighting).unwrap();
+        assert_eq!(serde_json::from_str::<Lighting>(&json).unwrap(), lighting);
+
+        let path = std::env::temp_dir().join(format!("volym_lighting_{}.json", std::process::id()));
+        lighting.save_to_json_file(&path).unwrap();
+        let loaded = Lighting::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), lighting);
+    }
+
+    #[test]
+    fn rejects_too_many_lights() {
+        let lighting = Lighting {
+            lights: vec![Light::default(); MAX_LIGHTS + 1],
+            ..Default::default()
+        };
+        let error = lighting.validate().unwrap_err();
+        assert!(error.to_string().contains("at most 4"), "{}", error);
+    }
+}
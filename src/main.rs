@@ -1,40 +1,43 @@
 use std::time::Duration;
 
 use cgmath::Point3;
-use cli::{Command, Demo};
+use cli::{Command, RenderArgs};
+use clipping::Clipping;
 use csv::Writer;
-use egui::scroll_area::State;
+use demos::DemoEntry;
 use egui_winit::winit::{
-    self, event,
+    self,
     event_loop::{EventLoop, EventLoopBuilder, EventLoopWindowTarget},
     window::{Window, WindowBuilder},
 };
 use event_loop::EventLoopEx;
 use gpu_context::GpuContext;
-use gpu_resources::{parameters, texture::GpuWriteTexture2D};
+use gpu_resources::texture::GpuWriteTexture2D;
+use lighting::Lighting;
 use render_pipeline::RenderPipeline;
 use serde::Serialize;
-use state::StateParameters;
-use std::fs::File;
-use std::io::Write;
-use std::time::Instant;
+use slices::SliceViews;
+use state::{RenderMode, StateParameters};
 use tracing::info;
 use tracing_error::ErrorLayer;
 use tracing_subscriber::{layer::SubscriberExt, EnvFilter};
 
 mod camera;
 mod cli;
+mod clipping;
 mod demos;
 mod event_loop;
 mod gpu_context;
+#[macro_use]
 mod gpu_resources;
 mod gui;
+mod lighting;
 mod render_pipeline;
+mod shader;
+mod slices;
 mod state;
 mod transfer_function;
-
-// Demos
-use demos::simple::Simple;
+mod volume;
 
 pub(crate) type Result<T> = color_eyre::eyre::Result<T>;
 pub(crate) type Error = color_eyre::eyre::Report;
@@ -42,9 +45,19 @@ pub(crate) type Error = color_eyre::eyre::Report;
 fn main() -> Result<()> {
     let args = cli::ParsedArgs::parse_args();
     setup_tracing(args.log_level.to_string())?;
+    let parameters = StateParameters {
+        transfer_function: args.transfer_function,
+        volume: args.volume,
+        labels: args.labels,
+        rescale: args.rescale,
+        window: args.window,
+        ..StateParameters::default()
+    };
     match args.command {
-        Command::Run(Demo::Simple) => run::<Simple>(),
-        Command::Benchmark => benchmark_all(),
+        Command::Run(run_args) => run(run_args.demo, parameters),
+        Command::Benchmark(benchmark_args) => benchmark_all(benchmark_args.demo, parameters),
+        Command::Render(render_args) => render(*render_args, parameters),
+        Command::CheckShaders => check_shaders(),
     }
 }
 
@@ -68,6 +81,9 @@ impl Default for RunSettings {
     }
 }
 
+/// Benchmarks of each configuration, averaged in the results
+const NUM_TRIALS: usize = 3;
+
 #[derive(Serialize)]
 struct BenchmarkResult {
     algorithm: String,
@@ -175,10 +191,14 @@ impl TrialResults {
     }
 }
 
-fn benchmark_all() -> Result<()> {
-    const NUM_TRIALS: usize = 3;
+fn benchmark_all(demo: &'static DemoEntry, parameters: StateParameters) -> Result<()> {
     let base_parameters = StateParameters {
         camera_position: Point3::new(0.5, 0.5, 3.5),
+        render_mode: RenderMode::Composite,
+        isosurfaces: Vec::new(),
+        clipping: Clipping::default(),
+        lighting: Lighting::default(),
+        slices: SliceViews::default(),
         use_opacity: true,
         density_trheshold: 0.15,
         use_cone_importance_check: false,
@@ -187,6 +207,23 @@ fn benchmark_all() -> Result<()> {
         use_gaussian_smoothing: false,
         importance_check_ahead_steps: 15,
         raymarching_step_size: 0.020,
+        use_transfer_function_2d: false,
+        use_precomputed_gradients: false,
+        gradient_filter: parameters.gradient_filter,
+        use_empty_space_skipping: true,
+        transfer_function: parameters.transfer_function,
+        volume: parameters.volume,
+        labels: parameters.labels,
+        rescale: parameters.rescale,
+        window: parameters.window,
+        // Benchmarks measure full resolution frames
+        render_scale: 1.0,
+        dynamic_resolution: false,
+        use_jittering: false,
+        use_accumulation: false,
+        tone_mapping: parameters.tone_mapping,
+        exposure: parameters.exposure,
+        gamma: parameters.gamma,
     };
 
     let step_sizes = [0.0030, 0.0050, 0.0100, 0.0200];
@@ -194,143 +231,72 @@ fn benchmark_all() -> Result<()> {
     let mut results = Vec::new();
     let mut event_loop = EventLoopBuilder::<EventLoopUserMsg>::with_user_event().build()?;
 
+    info!("Benchmarking the {} demo", demo.name);
     info!("Running base algorithm benchmarks");
     for &step_size in &step_sizes {
-        let mut trial_results = TrialResults::new();
-
-        for trial in 0..NUM_TRIALS {
-            info!(
-                "Base algorithm trial {} with step_size {}",
-                trial + 1,
-                step_size
-            );
-            let mut params = base_parameters.clone();
-            params.raymarching_step_size = step_size;
-            let (total_frames, duration) = benchmark::<Simple>(&mut event_loop, params)?;
-            trial_results.add_trial(total_frames, duration);
-        }
+        let params = StateParameters {
+            raymarching_step_size: step_size,
+            ..base_parameters.clone()
+        };
+        results.push(run_trials(demo, &mut event_loop, params, "Base")?);
+    }
 
-        let (
-            avg_frames,
-            avg_time,
-            avg_frame_time,
-            avg_fps,
-            std_frames,
-            std_time,
-            std_frame_time,
-            std_fps,
-        ) = trial_results.calculate_stats();
-
-        results.push(BenchmarkResult {
-            algorithm: "Base".to_string(),
-            step_size,
-            importance_steps: 0,
-            use_cone: false,
-            avg_total_frames: avg_frames,
-            avg_total_time_ms: avg_time,
-            avg_frame_time_ms: avg_frame_time,
-            avg_fps: avg_fps,
-            std_dev_total_frames: std_frames,
-            std_dev_total_time_ms: std_time,
-            std_dev_frame_time_ms: std_frame_time,
-            std_dev_fps: std_fps,
-        });
+    info!("Running base algorithm without empty space skipping benchmarks");
+    for &step_size in &step_sizes {
+        let params = StateParameters {
+            raymarching_step_size: step_size,
+            use_empty_space_skipping: false,
+            ..base_parameters.clone()
+        };
+        results.push(run_trials(
+            demo,
+            &mut event_loop,
+            params,
+            "BaseWithoutEmptySpaceSkipping",
+        )?);
+    }
+    report_speedup(&results, "BaseWithoutEmptySpaceSkipping", "Base");
+
+    info!("Running base algorithm with precomputed gradients benchmarks");
+    for &step_size in &step_sizes {
+        let params = StateParameters {
+            raymarching_step_size: step_size,
+            use_precomputed_gradients: true,
+            ..base_parameters.clone()
+        };
+        results.push(run_trials(
+            demo,
+            &mut event_loop,
+            params,
+            "BasePrecomputedGradients",
+        )?);
     }
+    report_speedup(&results, "Base", "BasePrecomputedGradients");
 
     info!("Running importance rendering benchmarks");
     for &step_size in &step_sizes {
         for &importance_step in &importance_steps {
-            let mut trial_results = TrialResults::new();
-
-            for trial in 0..NUM_TRIALS {
-                info!(
-                    "Importance rendering trial {} with step_size {} and importance_step {}",
-                    trial + 1,
-                    step_size,
-                    importance_step
-                );
-                let mut params = base_parameters.clone();
-                params.raymarching_step_size = step_size;
-                params.importance_check_ahead_steps = importance_step;
-                params.use_importance_rendering = true;
-                let (total_frames, duration) = benchmark::<Simple>(&mut event_loop, params)?;
-                trial_results.add_trial(total_frames, duration);
-            }
-
-            let (
-                avg_frames,
-                avg_time,
-                avg_frame_time,
-                avg_fps,
-                std_frames,
-                std_time,
-                std_frame_time,
-                std_fps,
-            ) = trial_results.calculate_stats();
-
-            results.push(BenchmarkResult {
-                algorithm: "Importance".to_string(),
-                step_size,
-                importance_steps: importance_step,
-                use_cone: false,
-                avg_total_frames: avg_frames,
-                avg_total_time_ms: avg_time,
-                avg_frame_time_ms: avg_frame_time,
-                avg_fps: avg_fps,
-                std_dev_total_frames: std_frames,
-                std_dev_total_time_ms: std_time,
-                std_dev_frame_time_ms: std_frame_time,
-                std_dev_fps: std_fps,
-            });
+            let params = StateParameters {
+                raymarching_step_size: step_size,
+                importance_check_ahead_steps: importance_step,
+                use_importance_rendering: true,
+                ..base_parameters.clone()
+            };
+            results.push(run_trials(demo, &mut event_loop, params, "Importance")?);
         }
     }
 
     info!("Running importance rendering with cone projection benchmarks");
     for &step_size in &step_sizes {
         for &importance_step in &importance_steps {
-            let mut trial_results = TrialResults::new();
-
-            for trial in 0..NUM_TRIALS {
-                info!(
-                    "Importance cone rendering trial {} with step_size {} and importance_step {}",
-                    trial + 1,
-                    step_size,
-                    importance_step
-                );
-                let mut params = base_parameters.clone();
-                params.raymarching_step_size = step_size;
-                params.importance_check_ahead_steps = importance_step;
-                params.use_importance_rendering = true;
-                params.use_cone_importance_check = true;
-                let (total_frames, duration) = benchmark::<Simple>(&mut event_loop, params)?;
-                trial_results.add_trial(total_frames, duration);
-            }
-
-            let (
-                avg_frames,
-                avg_time,
-                avg_frame_time,
-                avg_fps,
-                std_frames,
-                std_time,
-                std_frame_time,
-                std_fps,
-            ) = trial_results.calculate_stats();
-
-            results.push(BenchmarkResult {
-                algorithm: "ImportanceCone".to_string(),
-                step_size,
-                importance_steps: importance_step,
-                use_cone: true,
-                avg_total_frames: avg_frames,
-                avg_total_time_ms: avg_time,
-                avg_frame_time_ms: avg_frame_time,
-                avg_fps: avg_fps,
-                std_dev_total_frames: std_frames,
-                std_dev_total_time_ms: std_time,
-                std_dev_frame_time_ms: std_frame_time,
-                std_dev_fps: std_fps,
-            });
+            let params = StateParameters {
+                raymarching_step_size: step_size,
+                importance_check_ahead_steps: importance_step,
+                use_importance_rendering: true,
+                use_cone_importance_check: true,
+                ..base_parameters.clone()
+            };
+            results.push(run_trials(demo, &mut event_loop, params, "ImportanceCone")?);
         }
     }
 
@@ -344,7 +310,84 @@ fn benchmark_all() -> Result<()> {
     Ok(())
 }
 
-fn benchmark<ComputeDemo: demos::ComputeDemo>(
+/// Runs `NUM_TRIALS` benchmarks of `parameters`, reported under the name `algorithm`
+fn run_trials(
+    demo: &'static DemoEntry,
+    event_loop: &mut EventLoop<EventLoopUserMsg>,
+    parameters: StateParameters,
+    algorithm: &str,
+) -> Result<BenchmarkResult> {
+    let step_size = parameters.raymarching_step_size;
+    let importance_steps = if parameters.use_importance_rendering {
+        parameters.importance_check_ahead_steps
+    } else {
+        0
+    };
+    let use_cone = parameters.use_importance_rendering && parameters.use_cone_importance_check;
+
+    let mut trial_results = TrialResults::new();
+    for trial in 0..NUM_TRIALS {
+        info!(
+            "{} trial {} with step_size {} and importance_steps {}",
+            algorithm,
+            trial + 1,
+            step_size,
+            importance_steps
+        );
+        let (total_frames, duration) = benchmark(demo, event_loop, parameters.clone())?;
+        trial_results.add_trial(total_frames, duration);
+    }
+
+    let (
+        avg_total_frames,
+        avg_total_time_ms,
+        avg_frame_time_ms,
+        avg_fps,
+        std_dev_total_frames,
+        std_dev_total_time_ms,
+        std_dev_frame_time_ms,
+        std_dev_fps,
+    ) = trial_results.calculate_stats();
+
+    Ok(BenchmarkResult {
+        algorithm: algorithm.to_string(),
+        step_size,
+        importance_steps,
+        use_cone,
+        avg_total_frames,
+        avg_total_time_ms,
+        avg_frame_time_ms,
+        avg_fps,
+        std_dev_total_frames,
+        std_dev_total_time_ms,
+        std_dev_frame_time_ms,
+        std_dev_fps,
+    })
+}
+
+/// Logs how much faster `algorithm` is than `baseline`, for every step size both were run with
+fn report_speedup(results: &[BenchmarkResult], baseline: &str, algorithm: &str) {
+    for result in results.iter().filter(|r| r.algorithm == algorithm) {
+        let Some(base) = results
+            .iter()
+            .find(|r| r.algorithm == baseline && r.step_size == result.step_size)
+        else {
+            continue;
+        };
+        info!(
+            "{} vs {} at step_size {}: {:.2} ms -> {:.2} ms per frame, {:.2}x speed-up",
+            algorithm,
+            baseline,
+            result.step_size,
+            base.avg_frame_time_ms,
+            result.avg_frame_time_ms,
+            base.avg_frame_time_ms / result.avg_frame_time_ms
+        );
+    }
+}
+
+fn benchmark(
+    demo: &'static DemoEntry,
     event_loop: &mut EventLoop<EventLoopUserMsg>,
     parameters: StateParameters,
 ) -> Result<(u32, Duration)> {
@@ -353,17 +396,19 @@ fn benchmark<ComputeDemo: demos::ComputeDemo>(
         ..RunSettings::default()
     };
     let event_loop_proxy = event_loop.create_proxy();
+    let size = winit::dpi::PhysicalSize::new(1024, 768);
     let window = WindowBuilder::new()
-        .with_inner_size(winit::dpi::PhysicalSize::new(1024, 768))
+        .with_inner_size(size)
         .with_title("Volym")
-        .build(&event_loop)?;
+        .build(event_loop)?;
+    let mut state =
+        state::State::with_parameters(size.width as f32 / size.height as f32, parameters)?;
 
     let user_event_handler: fn(EventLoopUserMsg, &EventLoopWindowTarget<EventLoopUserMsg>) =
         |event, control_flow| {
-            if let EventLoopUserMsg::Stop = event {
-                info!("Benchmark finished");
-                control_flow.exit();
-            }
+            let EventLoopUserMsg::Stop = event;
+            info!("Benchmark finished");
+            control_flow.exit();
         };
 
     let sleep_t = Duration::from_secs(settings.secs_per_benchmark as u64);
@@ -372,9 +417,10 @@ fn benchmark<ComputeDemo: demos::ComputeDemo>(
         event_loop_proxy.send_event(EventLoopUserMsg::Stop).unwrap();
     });
 
-    let (total_frames, duration) = run_with_event_loop::<Simple>(
+    let (total_frames, duration) = run_with_event_loop(
+        demo,
         window,
-        parameters,
+        &mut state,
         settings,
         event_loop,
         user_event_handler,
@@ -383,27 +429,136 @@ fn benchmark<ComputeDemo: demos::ComputeDemo>(
     Ok((total_frames, duration))
 }
 
-fn run<ComputeDemo: demos::ComputeDemo>() -> Result<()> {
+fn run(mut demo: &'static DemoEntry, parameters: StateParameters) -> Result<()> {
     let mut event_loop = EventLoopBuilder::<EventLoopUserMsg>::with_user_event().build()?;
-    let window = WindowBuilder::new()
-        .with_title("Volym")
-        .with_inner_size(winit::dpi::PhysicalSize::new(1200, 768))
-        .build(&event_loop)?;
+    let size = winit::dpi::PhysicalSize::new(1200, 768);
+    let mut state =
+        state::State::with_parameters(size.width as f32 / size.height as f32, parameters)?;
+
+    // Switching demos from the GUI closes the window, the next demo opens a new one and
+    // keeps the settings
+    loop {
+        let window = WindowBuilder::new()
+            .with_title("Volym")
+            .with_inner_size(size)
+            .build(&event_loop)?;
+
+        let _ = run_with_event_loop(
+            demo,
+            window,
+            &mut state,
+            RunSettings::default(),
+            &mut event_loop,
+            |_, _| {},
+        )?;
+
+        match state.requested_demo.take() {
+            Some(next) => demo = next,
+            None => return Ok(()),
+        }
+    }
+}
 
-    let _ = run_with_event_loop::<ComputeDemo>(
-        window,
-        StateParameters::default(),
-        RunSettings::default(),
-        &mut event_loop,
-        |_, _| {},
-    )?;
+fn render(args: RenderArgs, base_parameters: StateParameters) -> Result<()> {
+    if args.width == 0 || args.height == 0 {
+        return Err(color_eyre::eyre::eyre!(
+            "Invalid render size {}x{}",
+            args.width,
+            args.height
+        ));
+    }
+    if args.accumulate_frames == 0 {
+        return Err(color_eyre::eyre::eyre!(
+            "At least one frame has to be rendered"
+        ));
+    }
+    let mut parameters = args.state_parameters()?;
+    if base_parameters.transfer_function.is_some() {
+        parameters.transfer_function = base_parameters.transfer_function;
+    }
+    if base_parameters.volume.is_some() {
+        parameters.volume = base_parameters.volume;
+    }
+    if base_parameters.labels.is_some() {
+        parameters.labels = base_parameters.labels;
+    }
+    if base_parameters.rescale.is_some() {
+        parameters.rescale = base_parameters.rescale;
+    }
+    if base_parameters.window.is_some() {
+        parameters.window = base_parameters.window;
+    }
 
+    let ctx = pollster::block_on(GpuContext::new_headless(winit::dpi::PhysicalSize::new(
+        args.width,
+        args.height,
+    )))?;
+    let mut state =
+        state::State::with_parameters(args.width as f32 / args.height as f32, parameters)?;
+
+    let compute_output_texture = GpuWriteTexture2D::new(&ctx);
+    let compute_demo = (args.demo.init)(&ctx, &state, &compute_output_texture)?;
+    state.volume_extent = compute_demo.volume_extent();
+    state.apply_value_window(compute_demo.value_window());
+    for _ in 0..args.accumulate_frames {
+        compute_demo.update_gpu_state(&ctx, &state)?;
+        compute_demo.compute_pass(&ctx)?;
+    }
+
+    // EXR keeps the HDR output, other formats get the tone mapped colors the window shows
+    let is_exr = args
+        .output
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("exr"));
+    let texture = if is_exr {
+        compute_output_texture.texture
+    } else {
+        let render_input_texture = compute_output_texture.into_read_texture_2d(&ctx);
+        let render_pipeline = RenderPipeline::init(&ctx, &state, &render_input_texture)?;
+        render_pipeline.render_to_texture(&ctx, render_input_texture.texture.size())?
+    };
+    pollster::block_on(state::save_screenshot(
+        &ctx.device,
+        &ctx.queue,
+        &texture,
+        &args.output,
+    ))?;
+    info!("Render saved to {:?}", args.output);
+
+    Ok(())
+}
+
+fn check_shaders() -> Result<()> {
+    let shaders = demos::DEMOS
+        .iter()
+        .flat_map(|demo| (demo.shaders)())
+        .chain([
+            RenderPipeline::shader(),
+            gpu_resources::gradients::GpuGradients::shader(),
+        ]);
+    let mut failed = 0;
+    for shader in shaders {
+        match shader.validate() {
+            Ok(_) => info!("{:?} matches its bind groups", shader.path()),
+            Err(e) => {
+                tracing::error!("{:#}", e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(color_eyre::eyre::eyre!(
+            "{} shaders failed the checks",
+            failed
+        ));
+    }
     Ok(())
 }
 
-fn run_with_event_loop<ComputeDemo: demos::ComputeDemo>(
+fn run_with_event_loop(
+    demo: &'static DemoEntry,
     window: Window,
-    state_parameters: StateParameters,
+    state: &mut state::State,
     settings: RunSettings,
     event_loop: &mut EventLoop<EventLoopUserMsg>,
     user_event_handler: impl FnMut(EventLoopUserMsg, &EventLoopWindowTarget<EventLoopUserMsg>),
@@ -411,19 +566,22 @@ fn run_with_event_loop<ComputeDemo: demos::ComputeDemo>(
     // Initialize GPU resources
     let ctx = pollster::block_on(GpuContext::new(&window))?;
 
-    // Initialize the state for the camera
-    let mut state = state::State::with_parameters(
-        ctx.surface_config.width as f32 / ctx.surface_config.height as f32,
-        state_parameters,
-    );
+    // The window may not have the requested size
+    state.camera.aspect = ctx.surface_config.width as f32 / ctx.surface_config.height as f32;
+    state.demo = demo;
+    info!("Running the {} demo", demo.name);
 
     // Setup render pipeline
-    let compute_output_texture = GpuWriteTexture2D::new(&ctx);
-    let compute_demo = ComputeDemo::init(&ctx, &state, &compute_output_texture)?;
+    let compute_output_texture =
+        GpuWriteTexture2D::with_size(&ctx, state.output_size(ctx.size.into()));
+    let compute_demo = (demo.init)(&ctx, state, &compute_output_texture)?;
+    state.histogram = compute_demo.histogram().cloned();
+    state.volume_extent = compute_demo.volume_extent();
+    state.apply_value_window(compute_demo.value_window());
 
     // Set up compute pipeline
     let render_input_texture = compute_output_texture.into_read_texture_2d(&ctx);
-    let render_pipeline = RenderPipeline::init(&ctx, &render_input_texture)?;
+    let render_pipeline = RenderPipeline::init(&ctx, state, &render_input_texture)?;
 
     // Initialize GUI
     let mut egui = gui::GuiContext::new(
@@ -437,12 +595,12 @@ fn run_with_event_loop<ComputeDemo: demos::ComputeDemo>(
     let (total_frames, duration) = event_loop.run_volym(
         settings,
         ctx,
-        &mut state,
+        state,
         &render_pipeline,
-        &compute_demo,
+        compute_demo.as_ref(),
         &mut egui,
         user_event_handler,
-        &render_input_texture,
+        render_input_texture,
     )?;
 
     Ok((total_frames, duration))
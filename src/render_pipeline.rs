@@ -1,8 +1,13 @@
+use std::cell::RefCell;
+
 use tracing::debug;
 
 use crate::demos::pipeline::layout_from_unbound_entries;
 use crate::gpu_resources::texture::GpuReadTexture2D;
+use crate::gpu_resources::tone_mapping::GpuToneMapping;
 use crate::gpu_resources::{ToBindGroupEntries, ToGpuResources};
+use crate::shader::{catch_validation_errors, ShaderReload, WatchedShader};
+use crate::state::State;
 /// Render pipeline that displays the texture on the screen
 use crate::Result;
 
@@ -12,76 +17,151 @@ use egui_wgpu::wgpu::{self, TextureView};
 
 #[derive(Debug)]
 pub struct RenderPipeline {
-    pub pipeline: wgpu::RenderPipeline,
-    pub input_texture_group: wgpu::BindGroup,
+    shader: WatchedShader,
+    layout: wgpu::PipelineLayout,
+    /// Replaced when the shader changes and still builds
+    pipeline: RefCell<wgpu::RenderPipeline>,
+    input_texture_layout: wgpu::BindGroupLayout,
+    /// Replaced when the output texture is, after a resize
+    input_texture_group: RefCell<wgpu::BindGroup>,
+    tone_mapping: GpuToneMapping,
+    tone_mapping_group: wgpu::BindGroup,
 }
 
 impl RenderPipeline {
-    pub fn init(ctx: &GpuContext, input_texture: &GpuReadTexture2D) -> Result<Self> {
-        let shader_path = format!("{}/shaders/render.wgsl", env!("CARGO_MANIFEST_DIR"));
-        let shader_contents = std::fs::read_to_string(&shader_path)?;
-        let shader = ctx
-            .device
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some(shader_path.as_str()),
-                source: wgpu::ShaderSource::Wgsl(shader_contents.into()),
-            });
+    /// The shader drawing the output texture, checked against the pipeline's bind group
+    pub fn shader() -> WatchedShader {
+        WatchedShader::new(
+            format!("{}/shaders/render.wgsl", env!("CARGO_MANIFEST_DIR")),
+            &[],
+        )
+        .with_bind_groups(&[
+            &[GpuReadTexture2D::bind_group_layout_entries().as_slice()],
+            &[GpuToneMapping::BIND_GROUP_LAYOUT_ENTRIES],
+        ])
+    }
+
+    pub fn init(ctx: &GpuContext, state: &State, input_texture: &GpuReadTexture2D) -> Result<Self> {
+        let shader = Self::shader();
 
         let render_input_texture_layout = layout_from_unbound_entries(
             ctx,
             "Render Input Texture Group Layout",
             &[GpuReadTexture2D::bind_group_layout_entries().as_slice()],
         );
+        let tone_mapping = GpuToneMapping::new(ctx, state);
+        let tone_mapping_layout = layout_from_unbound_entries(
+            ctx,
+            "Tone Mapping Group Layout",
+            &[GpuToneMapping::BIND_GROUP_LAYOUT_ENTRIES],
+        );
+        let tone_mapping_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Tone Mapping Bind Group"),
+            layout: &tone_mapping_layout,
+            entries: &tone_mapping.to_gpu_resources().to_bind_group_entries(),
+        });
         // Create render pipeline
-        let pipeline_layout = ctx
+        let layout = ctx
             .device
             .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&render_input_texture_layout],
+                bind_group_layouts: &[&render_input_texture_layout, &tone_mapping_layout],
                 push_constant_ranges: &[],
             });
 
-        let pipeline = ctx
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("Render Pipeline"),
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &[],
-                    compilation_options: Default::default(),
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: ctx.surface_config.format,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                    compilation_options: Default::default(),
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    ..Default::default()
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            });
+        let pipeline = Self::create_pipeline(ctx, &shader, &layout)?;
+
+        let input_texture_group =
+            Self::input_texture_group(ctx, &render_input_texture_layout, input_texture);
 
+        Ok(Self {
+            shader,
+            layout,
+            pipeline: RefCell::new(pipeline),
+            input_texture_layout: render_input_texture_layout,
+            input_texture_group: RefCell::new(input_texture_group),
+            tone_mapping,
+            tone_mapping_group,
+        })
+    }
+
+    pub fn update_gpu_state(&self, ctx: &GpuContext, state: &State) {
+        self.tone_mapping.update(ctx, state);
+    }
+
+    fn input_texture_group(
+        ctx: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        input_texture: &GpuReadTexture2D,
+    ) -> wgpu::BindGroup {
         let render_pipeline_resources = input_texture.to_gpu_resources();
 
-        let input_texture_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Render Bind Group"),
-            layout: &render_input_texture_layout,
+            layout,
             entries: &render_pipeline_resources.to_bind_group_entries(),
-        });
+        })
+    }
 
-        Ok(Self {
-            pipeline,
-            input_texture_group,
+    /// Draws a new texture from now on, after the output was resized
+    pub fn set_input_texture(&self, ctx: &GpuContext, input_texture: &GpuReadTexture2D) {
+        self.input_texture_group.replace(Self::input_texture_group(
+            ctx,
+            &self.input_texture_layout,
+            input_texture,
+        ));
+    }
+
+    fn create_pipeline(
+        ctx: &GpuContext,
+        shader: &WatchedShader,
+        layout: &wgpu::PipelineLayout,
+    ) -> Result<wgpu::RenderPipeline> {
+        let shader = shader.load(ctx)?;
+        catch_validation_errors(ctx, || {
+            ctx.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("Render Pipeline"),
+                    layout: Some(layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                        compilation_options: Default::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: ctx.surface_config.format,
+                            blend: Some(wgpu::BlendState::REPLACE),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: Default::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        ..Default::default()
+                    },
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                })
+        })
+    }
+
+    /// Rebuilds the pipeline when the shader file changed, a shader that fails to build
+    /// leaves the last good pipeline in place
+    pub fn reload_if_changed(&self, ctx: &GpuContext) -> Option<ShaderReload> {
+        if !self.shader.changed() {
+            return None;
+        }
+        let result = Self::create_pipeline(ctx, &self.shader, &self.layout).map(|pipeline| {
+            self.pipeline.replace(pipeline);
+        });
+        Some(ShaderReload {
+            path: self.shader.path().to_path_buf(),
+            result,
         })
     }
 
@@ -99,6 +179,8 @@ impl RenderPipeline {
 
         // render pass
         {
+            let pipeline = self.pipeline.borrow();
+            let input_texture_group = self.input_texture_group.borrow();
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[
@@ -117,8 +199,9 @@ impl RenderPipeline {
                 occlusion_query_set: None,
             });
 
-            render_pass.set_pipeline(&self.pipeline);
-            render_pass.set_bind_group(0, &self.input_texture_group, &[]);
+            render_pass.set_pipeline(&pipeline);
+            render_pass.set_bind_group(0, &input_texture_group, &[]);
+            render_pass.set_bind_group(1, &self.tone_mapping_group, &[]);
             debug!(target = "render_pass", "Render bind group set");
             render_pass.draw(0..6, 0..1); // Draw a quad (2*3 vertices)
             debug!(target = "render_pass", "Draw done");
@@ -128,10 +211,27 @@ impl RenderPipeline {
 
         Ok(())
     }
-}
 
-impl AsRef<wgpu::RenderPipeline> for RenderPipeline {
-    fn as_ref(&self) -> &wgpu::RenderPipeline {
-        &self.pipeline
+    /// Draws the input, tone mapped, to a new texture of `size` in the surface's format, for
+    /// screenshots and offscreen renders
+    pub fn render_to_texture(
+        &self,
+        ctx: &GpuContext,
+        size: wgpu::Extent3d,
+    ) -> Result<wgpu::Texture> {
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Tone Mapped Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ctx.surface_config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.render_pass(ctx, &view)?;
+
+        Ok(texture)
     }
 }
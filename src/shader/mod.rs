@@ -0,0 +1,159 @@
+/// WGSL files loaded from disk, preprocessed and validated with naga before they reach the
+/// device, checked against their bind group layouts, and watched so pipelines can be rebuilt
+/// when they change
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use color_eyre::eyre::eyre;
+use egui_wgpu::wgpu;
+
+use crate::{gpu_context::GpuContext, gpu_resources::BindGroupLayoutEntryUnbound, Result};
+
+use preprocessor::preprocess;
+
+pub mod preprocessor;
+pub mod reflection;
+
+/// Outcome of rebuilding a pipeline after its shader changed
+#[derive(Debug)]
+pub struct ShaderReload {
+    pub path: PathBuf,
+    pub result: Result<()>,
+}
+
+/// A shader and the files it includes, see [`preprocessor`]
+#[derive(Debug)]
+pub struct WatchedShader {
+    path: PathBuf,
+    /// Features turned on before the shader is preprocessed
+    defines: Vec<String>,
+    /// Entries of the pipeline's bind groups, the shader's bindings are checked against them
+    bind_groups: Option<Vec<Vec<BindGroupLayoutEntryUnbound>>>,
+    /// Files read by the last load, with their modification times then
+    loaded_files: RefCell<Vec<(PathBuf, Option<SystemTime>)>>,
+}
+
+impl WatchedShader {
+    pub fn new(path: impl Into<PathBuf>, defines: &[&str]) -> Self {
+        Self {
+            path: path.into(),
+            defines: defines.iter().map(|d| d.to_string()).collect(),
+            bind_groups: None,
+            loaded_files: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Checks the shader against the entries of every bind group, given like to
+    /// [`crate::demos::pipeline::layout_from_unbound_entries`], see [`reflection`]
+    pub fn with_bind_groups(mut self, bind_groups: &[&[&[BindGroupLayoutEntryUnbound]]]) -> Self {
+        self.bind_groups = Some(bind_groups.iter().map(|entries| entries.concat()).collect());
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the shader or a file it includes was modified since it was last loaded
+    pub fn changed(&self) -> bool {
+        self.loaded_files
+            .borrow()
+            .iter()
+            .any(|(path, loaded)| modified(path).is_some_and(|m| Some(m) != *loaded))
+    }
+
+    /// Preprocesses and validates the shader. Errors are returned with the offending source
+    /// lines instead of panicking in the device.
+    pub fn load(&self, ctx: &GpuContext) -> Result<wgpu::ShaderModule> {
+        let source = self.validate()?;
+        catch_validation_errors(ctx, || {
+            ctx.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(self.path.to_string_lossy().as_ref()),
+                    source: wgpu::ShaderSource::Wgsl(source.into()),
+                })
+        })
+    }
+
+    /// Preprocesses the shader, validates it and checks it against its bind groups, without
+    /// a device. Returns the preprocessed source.
+    pub fn validate(&self) -> Result<String> {
+        let preprocessed = preprocess(&self.path, &self.defines);
+
+        // Even a failed load waits for the next change before trying again
+        let mut files = match &preprocessed {
+            Ok(preprocessed) => preprocessed.files.clone(),
+            Err(_) => self
+                .loaded_files
+                .borrow()
+                .iter()
+                .map(|(p, _)| p.clone())
+                .collect(),
+        };
+        if !files.contains(&self.path) {
+            files.push(self.path.clone());
+        }
+        *self.loaded_files.borrow_mut() = files
+            .into_iter()
+            .map(|path| {
+                let modified = modified(&path);
+                (path, modified)
+            })
+            .collect();
+
+        let preprocessed = preprocessed?;
+        let source = preprocessed.source.as_str();
+        let file_name = self.path.to_string_lossy();
+        let preprocessed_name = format!("{} (preprocessed)", file_name);
+        // Locations are in the preprocessed source, point at the file they come from
+        let describe = |message: String, location: Option<naga::SourceLocation>| {
+            let origin = location.and_then(|l| preprocessed.origin(l.line_number as usize));
+            match origin {
+                Some(origin) => eyre!("{}\nat {}", message, origin),
+                None => eyre!("{}", message),
+            }
+        };
+
+        let module = naga::front::wgsl::parse_str(source).map_err(|e| {
+            describe(
+                e.emit_to_string_with_path(source, &preprocessed_name),
+                e.location(source),
+            )
+        })?;
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .map_err(|e| {
+            describe(
+                e.emit_to_string_with_path(source, &preprocessed_name),
+                e.location(source),
+            )
+        })?;
+
+        if let Some(bind_groups) = &self.bind_groups {
+            reflection::check_bindings(&module, &info, bind_groups)
+                .map_err(|e| eyre!("{}: {}", file_name, e))?;
+        }
+        Ok(preprocessed.source)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Runs `create` and returns the validation errors the device reports for it, which would
+/// otherwise panic. Catches what naga can't see, like a shader not matching its bind groups.
+pub fn catch_validation_errors<T>(ctx: &GpuContext, create: impl FnOnce() -> T) -> Result<T> {
+    ctx.device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let created = create();
+    match pollster::block_on(ctx.device.pop_error_scope()) {
+        Some(error) => Err(eyre!("{}", error)),
+        None => Ok(created),
+    }
+}
@@ -0,0 +1,318 @@
+/// Preprocessor run on WGSL files before naga sees them, so shaders can share code:
+///
+/// - `#include "file.wgsl"` pastes a file, relative to the one including it. Every file is
+///   included at most once per shader.
+/// - `#define NAME` turns a feature on, pipelines can also turn features on for a shader.
+/// - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` keep lines depending on the features.
+///
+/// Directives take a whole line starting with `#`.
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::eyre;
+
+use crate::Result;
+
+#[derive(Debug)]
+pub struct Preprocessed {
+    pub source: String,
+    /// Every file that was read, the shader first
+    pub files: Vec<PathBuf>,
+    /// File index and line number of every line of `source`
+    origins: Vec<(usize, usize)>,
+}
+
+impl Preprocessed {
+    /// Where a line of the preprocessed source comes from, as `file:line`
+    pub fn origin(&self, line_number: usize) -> Option<String> {
+        let &(file, line) = self.origins.get(line_number.checked_sub(1)?)?;
+        Some(format!("{}:{}", self.files[file].display(), line))
+    }
+}
+
+pub fn preprocess(path: &Path, defines: &[String]) -> Result<Preprocessed> {
+    let mut preprocessor = Preprocessor {
+        defines: defines.iter().cloned().collect(),
+        output: Preprocessed {
+            source: String::new(),
+            files: Vec::new(),
+            origins: Vec::new(),
+        },
+    };
+    preprocessor.process_file(path)?;
+    Ok(preprocessor.output)
+}
+
+struct Preprocessor {
+    defines: HashSet<String>,
+    output: Preprocessed,
+}
+
+/// An `#ifdef` or `#ifndef` block being read
+struct Condition {
+    /// Whether the lines are kept
+    active: bool,
+    /// Whether the lines around the block are kept
+    parent_active: bool,
+    seen_else: bool,
+}
+
+impl Preprocessor {
+    fn process_file(&mut self, path: &Path) -> Result<()> {
+        let source =
+            std::fs::read_to_string(path).map_err(|e| eyre!("Failed to read {:?}: {}", path, e))?;
+        // Paths like `include/../include/a.wgsl` still name the same file
+        let path = &std::fs::canonicalize(path)?;
+        if self.output.files.contains(path) {
+            return Ok(());
+        }
+        let file_index = self.output.files.len();
+        self.output.files.push(path.to_path_buf());
+
+        let mut conditions: Vec<Condition> = Vec::new();
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i + 1;
+            let active = conditions.last().is_none_or(|c| c.active);
+            let Some(directive) = line.trim().strip_prefix('#') else {
+                if active {
+                    self.output.source.push_str(line);
+                    self.output.source.push('\n');
+                    self.output.origins.push((file_index, line_number));
+                }
+                continue;
+            };
+
+            let error = |message: &str| eyre!("{}:{}: {}", path.display(), line_number, message);
+            let (name, argument) = directive
+                .split_once(char::is_whitespace)
+                .map_or((directive, ""), |(name, argument)| (name, argument.trim()));
+            match name {
+                "ifdef" | "ifndef" => {
+                    if argument.is_empty() {
+                        return Err(error(&format!("#{} needs a name", name)));
+                    }
+                    let defined = self.defines.contains(argument);
+                    conditions.push(Condition {
+                        active: active && defined == (name == "ifdef"),
+                        parent_active: active,
+                        seen_else: false,
+                    });
+                }
+                "else" => {
+                    let condition = conditions
+                        .last_mut()
+                        .ok_or_else(|| error("#else without #ifdef"))?;
+                    if condition.seen_else {
+                        return Err(error("second #else for the same #ifdef"));
+                    }
+                    condition.seen_else = true;
+                    condition.active = condition.parent_active && !condition.active;
+                }
+                "endif" => {
+                    conditions
+                        .pop()
+                        .ok_or_else(|| error("#endif without #ifdef"))?;
+                }
+                "define" | "include" if !active => {}
+                "define" => {
+                    if argument.is_empty() || argument.contains(char::is_whitespace) {
+                        return Err(error("#define needs a single name"));
+                    }
+                    self.defines.insert(argument.to_string());
+                }
+                "include" => {
+                    let file = argument
+                        .strip_prefix('"')
+                        .and_then(|a| a.strip_suffix('"'))
+                        .ok_or_else(|| error("#include needs a quoted path"))?;
+                    let directory = path.parent().unwrap_or(Path::new(""));
+                    self.process_file(&directory.join(file))?;
+                }
+                _ => return Err(error(&format!("unknown directive #{}", name))),
+            }
+        }
+
+        if !conditions.is_empty() {
+            return Err(eyre!("{}: #ifdef without #endif", path.display()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `files`, paths relative to a fresh directory for `test`, and returns that directory
+    fn write_shaders(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "volym_preprocessor_{}_{}",
+            test,
+            std::process::id()
+        ));
+        for (path, source) in files {
+            let path = directory.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+        directory
+    }
+
+    /// Preprocesses `main.wgsl` of `files` with `defines`
+    fn run(test: &str, files: &[(&str, &str)], defines: &[&str]) -> Result<Preprocessed> {
+        let directory = write_shaders(test, files);
+        let defines: Vec<String> = defines.iter().map(|d| d.to_string()).collect();
+        let result = preprocess(&directory.join("main.wgsl"), &defines);
+        std::fs::remove_dir_all(&directory).unwrap();
+        result
+    }
+
+    fn assert_error(test: &str, files: &[(&str, &str)], message: &str) {
+        let error = run(test, files, &[]).unwrap_err();
+        assert!(error.to_string().contains(message), "{}", error);
+    }
+
+    #[test]
+    fn includes_relative_files_once() {
+        let preprocessed = run(
+            "include",
+            &[
+                (
+                    "main.wgsl",
+                    "#include \"include/a.wgsl\"\n#include \"include/../include/b.wgsl\"\nmain",
+                ),
+                ("include/a.wgsl", "#include \"b.wgsl\"\na"),
+                ("include/b.wgsl", "b"),
+            ],
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(preprocessed.source, "b\na\nmain\n");
+        assert_eq!(preprocessed.files.len(), 3);
+    }
+
+    #[test]
+    fn keeps_lines_of_nested_conditions() {
+        let source = "\
+#ifdef A
+a
+#ifndef B
+a_not_b
+#else
+a_b
+#endif
+#else
+not_a
+#ifdef B
+not_a_b
+#endif
+#endif
+#define C
+#ifdef C
+c
+#endif
+";
+        let lines = |defines: &[&str]| {
+            run(&defines.concat(), &[("main.wgsl", source)], defines)
+                .unwrap()
+                .source
+        };
+
+        assert_eq!(lines(&[]), "not_a\nc\n");
+        assert_eq!(lines(&["A"]), "a\na_not_b\nc\n");
+        assert_eq!(lines(&["A", "B"]), "a\na_b\nc\n");
+        assert_eq!(lines(&["B"]), "not_a\nnot_a_b\nc\n");
+    }
+
+    #[test]
+    fn ignores_directives_of_dropped_lines() {
+        let preprocessed = run(
+            "dropped",
+            &[(
+                "main.wgsl",
+                "#ifdef A\n#include \"missing.wgsl\"\n#define B\n#endif\n#ifdef B\nb\n#endif",
+            )],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(preprocessed.source, "");
+    }
+
+    #[test]
+    fn rejects_second_else() {
+        assert_error(
+            "second_else",
+            &[("main.wgsl", "#ifdef A\n#else\n#else\n#endif")],
+            "main.wgsl:3: second #else",
+        );
+    }
+
+    #[test]
+    fn rejects_endif_without_ifdef() {
+        assert_error(
+            "endif",
+            &[("main.wgsl", "a\n#endif")],
+            "main.wgsl:2: #endif without #ifdef",
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_ifdef() {
+        assert_error(
+            "unterminated",
+            &[("main.wgsl", "#ifdef A\na")],
+            "#ifdef without #endif",
+        );
+    }
+
+    #[test]
+    fn rejects_missing_include() {
+        assert_error(
+            "missing",
+            &[("main.wgsl", "#include \"missing.wgsl\"")],
+            "Failed to read",
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_directive() {
+        assert_error(
+            "unknown",
+            &[("main.wgsl", "a\n#pragma once")],
+            "main.wgsl:2: unknown directive #pragma",
+        );
+    }
+
+    #[test]
+    fn maps_lines_to_their_files() {
+        let directory = write_shaders(
+            "origin",
+            &[
+                ("main.wgsl", "main_1\n#include \"lib.wgsl\"\nmain_3"),
+                ("lib.wgsl", "#ifdef A\nskipped\n#endif\nlib_4"),
+            ],
+        );
+        let preprocessed = preprocess(&directory.join("main.wgsl"), &[]);
+        let main = std::fs::canonicalize(directory.join("main.wgsl")).unwrap();
+        let lib = std::fs::canonicalize(directory.join("lib.wgsl")).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        let preprocessed = preprocessed.unwrap();
+
+        assert_eq!(preprocessed.source, "main_1\nlib_4\nmain_3\n");
+        assert_eq!(preprocessed.files, vec![main.clone(), lib.clone()]);
+        assert_eq!(
+            preprocessed.origin(1),
+            Some(format!("{}:1", main.display()))
+        );
+        assert_eq!(preprocessed.origin(2), Some(format!("{}:4", lib.display())));
+        assert_eq!(
+            preprocessed.origin(3),
+            Some(format!("{}:3", main.display()))
+        );
+        assert_eq!(preprocessed.origin(0), None);
+        assert_eq!(preprocessed.origin(4), None);
+    }
+}
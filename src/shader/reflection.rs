@@ -0,0 +1,497 @@
+/// Checks a shader's resources against the bind group layouts its pipeline is created with,
+/// using naga's reflection. A binding edited on one side only fails with the names of both
+/// sides, and a uniform struct whose fields moved in WGSL or in Rust fails instead of silently
+/// reading shifted values.
+use color_eyre::eyre::eyre;
+use egui_wgpu::wgpu;
+use naga::{AddressSpace, ImageClass, ImageDimension, ScalarKind, StorageFormat, TypeInner};
+
+use crate::{
+    gpu_resources::{BindGroupLayoutEntryUnbound, UniformLayout},
+    Result,
+};
+
+/// Checks every resource the entry points use against the entries of the bind groups, indexed
+/// by group then binding
+pub fn check_bindings(
+    module: &naga::Module,
+    info: &naga::valid::ModuleInfo,
+    bind_groups: &[Vec<BindGroupLayoutEntryUnbound>],
+) -> Result<()> {
+    let mut errors: Vec<String> = Vec::new();
+    for (index, entry_point) in module.entry_points.iter().enumerate() {
+        let stage = match entry_point.stage {
+            naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+            naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+            naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+        };
+        let uses = info.get_entry_point(index);
+        for (handle, global) in module.global_variables.iter() {
+            let Some(binding) = &global.binding else {
+                continue;
+            };
+            // Declared but unused resources don't need to be bound
+            if uses[handle].is_empty() {
+                continue;
+            }
+
+            let name = format!(
+                "`{}` at @group({}) @binding({})",
+                global.name.as_deref().unwrap_or("?"),
+                binding.group,
+                binding.binding
+            );
+            let entry = bind_groups
+                .get(binding.group as usize)
+                .and_then(|entries| entries.get(binding.binding as usize));
+            let error = match entry {
+                None => Some(format!("{} has no bind group layout entry", name)),
+                Some(entry) if !entry.visibility.contains(stage) => Some(format!(
+                    "{} isn't visible to the {:?} stage of `{}`",
+                    name, entry_point.stage, entry_point.name
+                )),
+                Some(entry) => check_entry(module, global, entry)
+                    .err()
+                    .map(|e| format!("{}: {}", name, e)),
+            };
+            // Globals used by several entry points are reported once
+            if let Some(error) = error.filter(|e| !errors.contains(e)) {
+                errors.push(error);
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "Shader doesn't match its bind group layouts:\n{}",
+            errors.join("\n")
+        ))
+    }
+}
+
+fn check_entry(
+    module: &naga::Module,
+    global: &naga::GlobalVariable,
+    entry: &BindGroupLayoutEntryUnbound,
+) -> std::result::Result<(), String> {
+    let inner = &module.types[global.ty].inner;
+    match (global.space, entry.ty) {
+        (
+            AddressSpace::Uniform,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                ..
+            },
+        ) => match entry.uniform_layout {
+            Some(layout) => check_uniform_layout(module, global.ty, &layout),
+            None => Ok(()),
+        },
+        (
+            AddressSpace::Storage { .. },
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { .. },
+                ..
+            },
+        ) => Ok(()),
+        (AddressSpace::Handle, ty) => check_handle(inner, ty),
+        (space, ty) => Err(format!("declared in {:?}, bound as {:?}", space, ty)),
+    }
+}
+
+/// Samplers, textures and storage textures
+fn check_handle(inner: &TypeInner, ty: wgpu::BindingType) -> std::result::Result<(), String> {
+    match (inner, ty) {
+        (TypeInner::Sampler { comparison }, wgpu::BindingType::Sampler(sampler)) => {
+            if *comparison == (sampler == wgpu::SamplerBindingType::Comparison) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "comparison sampler mismatch, bound as {:?}",
+                    sampler
+                ))
+            }
+        }
+        (
+            &TypeInner::Image {
+                dim,
+                arrayed,
+                class,
+            },
+            wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension,
+                multisampled,
+            },
+        ) => {
+            check_view_dimension(dim, arrayed, view_dimension)?;
+            let matches = match (class, sample_type) {
+                (
+                    ImageClass::Sampled {
+                        kind: ScalarKind::Float,
+                        multi,
+                    },
+                    wgpu::TextureSampleType::Float { .. },
+                )
+                | (
+                    ImageClass::Sampled {
+                        kind: ScalarKind::Sint,
+                        multi,
+                    },
+                    wgpu::TextureSampleType::Sint,
+                )
+                | (
+                    ImageClass::Sampled {
+                        kind: ScalarKind::Uint,
+                        multi,
+                    },
+                    wgpu::TextureSampleType::Uint,
+                )
+                | (ImageClass::Depth { multi }, wgpu::TextureSampleType::Depth) => {
+                    multi == multisampled
+                }
+                _ => false,
+            };
+            if matches {
+                Ok(())
+            } else {
+                Err(format!(
+                    "declared as {:?}, bound as {:?} with multisampled: {}",
+                    class, sample_type, multisampled
+                ))
+            }
+        }
+        (
+            &TypeInner::Image {
+                dim,
+                arrayed,
+                class: ImageClass::Storage { format, .. },
+            },
+            wgpu::BindingType::StorageTexture {
+                format: bound_format,
+                view_dimension,
+                ..
+            },
+        ) => {
+            check_view_dimension(dim, arrayed, view_dimension)?;
+            if texture_format(format) == bound_format {
+                Ok(())
+            } else {
+                Err(format!(
+                    "declared as {:?}, bound as {:?}",
+                    format, bound_format
+                ))
+            }
+        }
+        (inner, ty) => Err(format!("declared as {:?}, bound as {:?}", inner, ty)),
+    }
+}
+
+fn check_view_dimension(
+    dim: ImageDimension,
+    arrayed: bool,
+    view_dimension: wgpu::TextureViewDimension,
+) -> std::result::Result<(), String> {
+    let declared = match (dim, arrayed) {
+        (ImageDimension::D1, false) => Some(wgpu::TextureViewDimension::D1),
+        (ImageDimension::D2, false) => Some(wgpu::TextureViewDimension::D2),
+        (ImageDimension::D2, true) => Some(wgpu::TextureViewDimension::D2Array),
+        (ImageDimension::D3, false) => Some(wgpu::TextureViewDimension::D3),
+        (ImageDimension::Cube, false) => Some(wgpu::TextureViewDimension::Cube),
+        (ImageDimension::Cube, true) => Some(wgpu::TextureViewDimension::CubeArray),
+        (ImageDimension::D1 | ImageDimension::D3, true) => None,
+    };
+    if declared == Some(view_dimension) {
+        Ok(())
+    } else {
+        Err(format!(
+            "declared as a {:?} texture{}, bound as {:?}",
+            dim,
+            if arrayed { " array" } else { "" },
+            view_dimension
+        ))
+    }
+}
+
+/// Compares the WGSL struct of a uniform buffer with the Rust struct filling it, field by
+/// field by name: offsets, sizes and scalar kinds. Fields only declared on one side are
+/// mismatches too.
+fn check_uniform_layout(
+    module: &naga::Module,
+    ty: naga::Handle<naga::Type>,
+    layout: &UniformLayout,
+) -> std::result::Result<(), String> {
+    let ty = &module.types[ty];
+    let wgsl_name = ty.name.as_deref().unwrap_or("?");
+    let TypeInner::Struct { members, span } = &ty.inner else {
+        return Err(format!(
+            "filled with the struct {}, declared as {:?}",
+            layout.name, ty.inner
+        ));
+    };
+
+    let mut mismatches = Vec::new();
+    if *span as usize != layout.size {
+        mismatches.push(format!("{} bytes in WGSL, {} in Rust", span, layout.size));
+    }
+    for member in members {
+        let name = member.name.as_deref().unwrap_or("?");
+        let Some(field) = layout.fields.iter().find(|field| field.name == name) else {
+            mismatches.push(format!(
+                "`{}` at offset {} is missing in Rust",
+                name, member.offset
+            ));
+            continue;
+        };
+        if field.offset != member.offset as usize {
+            mismatches.push(format!(
+                "`{}` is at offset {} in WGSL, {} in Rust",
+                name, member.offset, field.offset
+            ));
+        }
+        let inner = &module.types[member.ty].inner;
+        let size = inner.size(module.to_ctx());
+        if field.size != size as usize {
+            mismatches.push(format!(
+                "`{}` is {} bytes in WGSL, {} in Rust",
+                name, size, field.size
+            ));
+        }
+        let kind = scalar_kind(module, inner);
+        if kind != Some(field.kind) {
+            mismatches.push(format!(
+                "`{}` is made of {} in WGSL, {:?} in Rust",
+                name,
+                kind.map_or_else(|| "no scalars".to_string(), |kind| format!("{:?}", kind)),
+                field.kind
+            ));
+        }
+    }
+    for field in layout.fields {
+        if !members
+            .iter()
+            .any(|m| m.name.as_deref() == Some(field.name))
+        {
+            mismatches.push(format!(
+                "`{}` at offset {} is missing in WGSL",
+                field.name, field.offset
+            ));
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "struct {} doesn't match {}: {}",
+            wgsl_name,
+            layout.name,
+            mismatches.join(", ")
+        ))
+    }
+}
+
+/// Kind of the scalars a vector, matrix or array is made of
+fn scalar_kind(module: &naga::Module, inner: &TypeInner) -> Option<ScalarKind> {
+    match *inner {
+        TypeInner::Scalar(scalar)
+        | TypeInner::Vector { scalar, .. }
+        | TypeInner::Matrix { scalar, .. }
+        | TypeInner::Atomic(scalar) => Some(scalar.kind),
+        TypeInner::Array { base, .. } => scalar_kind(module, &module.types[base].inner),
+        _ => None,
+    }
+}
+
+/// The texture format of a storage texture declared in WGSL
+fn texture_format(format: StorageFormat) -> wgpu::TextureFormat {
+    use wgpu::TextureFormat as Tf;
+    match format {
+        StorageFormat::R8Unorm => Tf::R8Unorm,
+        StorageFormat::R8Snorm => Tf::R8Snorm,
+        StorageFormat::R8Uint => Tf::R8Uint,
+        StorageFormat::R8Sint => Tf::R8Sint,
+        StorageFormat::R16Uint => Tf::R16Uint,
+        StorageFormat::R16Sint => Tf::R16Sint,
+        StorageFormat::R16Float => Tf::R16Float,
+        StorageFormat::Rg8Unorm => Tf::Rg8Unorm,
+        StorageFormat::Rg8Snorm => Tf::Rg8Snorm,
+        StorageFormat::Rg8Uint => Tf::Rg8Uint,
+        StorageFormat::Rg8Sint => Tf::Rg8Sint,
+        StorageFormat::R32Uint => Tf::R32Uint,
+        StorageFormat::R32Sint => Tf::R32Sint,
+        StorageFormat::R32Float => Tf::R32Float,
+        StorageFormat::Rg16Uint => Tf::Rg16Uint,
+        StorageFormat::Rg16Sint => Tf::Rg16Sint,
+        StorageFormat::Rg16Float => Tf::Rg16Float,
+        StorageFormat::Rgba8Unorm => Tf::Rgba8Unorm,
+        StorageFormat::Rgba8Snorm => Tf::Rgba8Snorm,
+        StorageFormat::Rgba8Uint => Tf::Rgba8Uint,
+        StorageFormat::Rgba8Sint => Tf::Rgba8Sint,
+        StorageFormat::Bgra8Unorm => Tf::Bgra8Unorm,
+        StorageFormat::Rgb10a2Uint => Tf::Rgb10a2Uint,
+        StorageFormat::Rgb10a2Unorm => Tf::Rgb10a2Unorm,
+        StorageFormat::Rg11b10Float => Tf::Rg11b10Float,
+        StorageFormat::Rg32Uint => Tf::Rg32Uint,
+        StorageFormat::Rg32Sint => Tf::Rg32Sint,
+        StorageFormat::Rg32Float => Tf::Rg32Float,
+        StorageFormat::Rgba16Uint => Tf::Rgba16Uint,
+        StorageFormat::Rgba16Sint => Tf::Rgba16Sint,
+        StorageFormat::Rgba16Float => Tf::Rgba16Float,
+        StorageFormat::Rgba32Uint => Tf::Rgba32Uint,
+        StorageFormat::Rgba32Sint => Tf::Rgba32Sint,
+        StorageFormat::Rgba32Float => Tf::Rgba32Float,
+        StorageFormat::R16Unorm => Tf::R16Unorm,
+        StorageFormat::R16Snorm => Tf::R16Snorm,
+        StorageFormat::Rg16Unorm => Tf::Rg16Unorm,
+        StorageFormat::Rg16Snorm => Tf::Rg16Snorm,
+        StorageFormat::Rgba16Unorm => Tf::Rgba16Unorm,
+        StorageFormat::Rgba16Snorm => Tf::Rgba16Snorm,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        demos::DEMOS, gpu_resources::gradients::GpuGradients,
+        gpu_resources::texture::GpuWriteTexture2D, render_pipeline::RenderPipeline,
+    };
+
+    #[test]
+    fn every_shader_matches_its_bind_groups() {
+        let shaders = DEMOS
+            .iter()
+            .flat_map(|demo| (demo.shaders)())
+            .chain([RenderPipeline::shader(), GpuGradients::shader()]);
+        for shader in shaders {
+            if let Err(e) = shader.validate() {
+                panic!("{:#}", e);
+            }
+        }
+    }
+
+    #[repr(C)]
+    struct TestUniforms {
+        scale: f32,
+        count: u32,
+        _padding: [u32; 2],
+        color: [f32; 4],
+    }
+
+    impl TestUniforms {
+        uniform_layout!(TestUniforms {
+            scale,
+            count,
+            color
+        });
+    }
+
+    const UNIFORMS_ENTRY: BindGroupLayoutEntryUnbound = BindGroupLayoutEntryUnbound {
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+        uniform_layout: Some(TestUniforms::LAYOUT),
+    };
+
+    /// Checks a compute shader reading `uniforms`, declared as `Uniforms { fields }`
+    fn check_uniforms(fields: &str) -> Result<()> {
+        check(
+            &format!(
+                "struct Uniforms {{ {} }}
+                @group(0) @binding(0) var<uniform> uniforms: Uniforms;
+                @compute @workgroup_size(1)
+                fn main() {{ let uniforms = uniforms; }}",
+                fields
+            ),
+            &[vec![UNIFORMS_ENTRY]],
+        )
+    }
+
+    fn check(source: &str, bind_groups: &[Vec<BindGroupLayoutEntryUnbound>]) -> Result<()> {
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .unwrap();
+        check_bindings(&module, &info, bind_groups)
+    }
+
+    #[test]
+    fn accepts_matching_uniform_struct() {
+        check_uniforms("scale: f32, count: u32, color: vec4<f32>").unwrap();
+    }
+
+    #[test]
+    fn reports_shifted_uniform_field() {
+        let error = check_uniforms("scale: f32, color: vec4<f32>, count: u32")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("`uniforms` at @group(0) @binding(0)"),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains("struct Uniforms doesn't match TestUniforms"),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains("`count` is at offset 32 in WGSL, 4 in Rust"),
+            "{}",
+            error
+        );
+        assert!(error.contains("48 bytes in WGSL, 32 in Rust"), "{}", error);
+    }
+
+    #[test]
+    fn reports_uniform_field_of_another_type() {
+        let error = check_uniforms("scale: f32, count: f32, color: vec4<f32>")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("`count` is made of Float in WGSL, Uint in Rust"),
+            "{}",
+            error
+        );
+
+        let error = check_uniforms("scale: f32, count: u32, color: vec3<f32>")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("`color` is 12 bytes in WGSL, 16 in Rust"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn reports_wrong_storage_format() {
+        let error = check(
+            "@group(0) @binding(0) var output: texture_storage_2d<rgba8unorm, write>;
+            @compute @workgroup_size(1)
+            fn main() { textureStore(output, vec2<i32>(0, 0), vec4<f32>(1.0)); }",
+            &[GpuWriteTexture2D::BIND_GROUP_LAYOUT_ENTRIES.to_vec()],
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            error.contains("`output` at @group(0) @binding(0)"),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains("declared as Rgba8Unorm, bound as Rgba16Float"),
+            "{}",
+            error
+        );
+    }
+}
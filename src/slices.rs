@@ -0,0 +1,170 @@
+/// Axial, coronal and sagittal slices through a probe position, drawn in a column of square
+/// panes on the right of the 3D view. Positions are volume coordinates, where the bounding box
+/// spans 0..=1 on every axis.
+use serde::{Deserialize, Serialize};
+
+/// Axis each pane holds constant, from the top pane down: axial (z), coronal (y), sagittal (x)
+pub const PANE_AXES: [usize; 3] = [2, 1, 0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SliceViews {
+    pub enabled: bool,
+    /// Where the three planes cross, marked in every pane and in the 3D view
+    pub probe: [f32; 3],
+    /// Color the densities with the transfer function instead of gray levels
+    pub use_transfer_function: bool,
+    /// Tint the voxels of the segments by their importance
+    pub show_segments: bool,
+}
+
+impl Default for SliceViews {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            probe: [0.5; 3],
+            use_transfer_function: true,
+            show_segments: true,
+        }
+    }
+}
+
+impl SliceViews {
+    /// Side of the panes, in pixels, for an output of `width` x `height`
+    pub fn pane_size(width: u32, height: u32) -> u32 {
+        (height / 3).min(width / 3)
+    }
+
+    /// Width of the 3D view left of the panes, in pixels
+    pub fn view_width(&self, width: u32, height: u32) -> u32 {
+        if self.enabled {
+            width - Self::pane_size(width, height)
+        } else {
+            width
+        }
+    }
+
+    /// Index of the pane under a pixel and the volume position shown there, `None` outside of
+    /// the panes or of the volume. Must match `render_slice_pane` in the shader.
+    pub fn position_at(
+        &self,
+        extent: [f32; 3],
+        (width, height): (u32, u32),
+        (x, y): (f64, f64),
+    ) -> Option<(usize, [f32; 3])> {
+        if !self.enabled {
+            return None;
+        }
+        let size = Self::pane_size(width, height) as f64;
+        let left = (width as f64) - size;
+        if size == 0.0 || x < left || y >= 3.0 * size {
+            return None;
+        }
+        let pane = (y / size) as usize;
+        let u = ((x - left) / size) as f32;
+        let v = ((y - pane as f64 * size) / size) as f32;
+        self.pane_position(PANE_AXES[pane], extent, u, v)
+            .map(|position| (pane, position))
+    }
+
+    /// Volume position at `u, v` (0..=1, from the top left) of the pane holding `axis` constant.
+    /// The slice keeps its proportions, centered in the pane.
+    fn pane_position(&self, axis: usize, extent: [f32; 3], u: f32, v: f32) -> Option<[f32; 3]> {
+        let (horizontal, vertical, flip_vertical) = match axis {
+            2 => (0, 1, true),
+            1 => (0, 2, false),
+            _ => (2, 1, true),
+        };
+        let scale = extent[horizontal].max(extent[vertical]);
+        let h = (u - 0.5) * scale / extent[horizontal] + 0.5;
+        let mut w = (v - 0.5) * scale / extent[vertical] + 0.5;
+        if !(0.0..=1.0).contains(&h) || !(0.0..=1.0).contains(&w) {
+            return None;
+        }
+        if flip_vertical {
+            w = 1.0 - w;
+        }
+
+        let mut position = self.probe;
+        position[horizontal] = h;
+        position[vertical] = w;
+        Some(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 900x600 output: 200 pixel panes from x = 700 down
+    const SIZE: (u32, u32) = (900, 600);
+    const CUBE: [f32; 3] = [1.0; 3];
+
+    fn slices() -> SliceViews {
+        SliceViews {
+            enabled: true,
+            probe: [0.1, 0.2, 0.3],
+            ..SliceViews::default()
+        }
+    }
+
+    #[test]
+    fn panes_take_a_third_of_the_height() {
+        assert_eq!(SliceViews::pane_size(900, 600), 200);
+        assert_eq!(SliceViews::pane_size(450, 1200), 150);
+        assert_eq!(slices().view_width(900, 600), 700);
+        assert_eq!(SliceViews::default().view_width(900, 600), 900);
+    }
+
+    #[test]
+    fn maps_pane_pixels_to_probe_positions() {
+        let slices = slices();
+
+        // Axial: x to the right, y up, at the probe's z
+        assert_eq!(
+            slices.position_at(CUBE, SIZE, (750.0, 150.0)),
+            Some((0, [0.25, 0.25, 0.3]))
+        );
+        // Coronal: x to the right, z down, at the probe's y
+        assert_eq!(
+            slices.position_at(CUBE, SIZE, (750.0, 350.0)),
+            Some((1, [0.25, 0.2, 0.75]))
+        );
+        // Sagittal: z to the right, y up, at the probe's x
+        assert_eq!(
+            slices.position_at(CUBE, SIZE, (750.0, 550.0)),
+            Some((2, [0.1, 0.25, 0.25]))
+        );
+    }
+
+    #[test]
+    fn keeps_slice_proportions() {
+        let slices = slices();
+        let extent = [1.0, 0.5, 1.0];
+
+        // The axial slice is half as high as the pane, centered
+        assert_eq!(
+            slices.position_at(extent, SIZE, (750.0, 125.0)),
+            Some((0, [0.25, 0.25, 0.3]))
+        );
+        assert_eq!(slices.position_at(extent, SIZE, (750.0, 20.0)), None);
+        assert_eq!(slices.position_at(extent, SIZE, (750.0, 180.0)), None);
+        // The coronal slice fills its pane
+        assert_eq!(
+            slices.position_at(extent, SIZE, (750.0, 250.0)),
+            Some((1, [0.25, 0.2, 0.25]))
+        );
+    }
+
+    #[test]
+    fn ignores_pixels_outside_the_panes() {
+        let slices = slices();
+        assert_eq!(slices.position_at(CUBE, SIZE, (699.0, 100.0)), None);
+        assert_eq!(slices.position_at(CUBE, SIZE, (750.0, 600.0)), None);
+        assert_eq!(
+            SliceViews::default().position_at(CUBE, SIZE, (750.0, 100.0)),
+            None
+        );
+        assert_eq!(slices.position_at(CUBE, (2, 2), (1.0, 1.0)), None);
+    }
+}
@@ -1,12 +1,137 @@
 use cgmath::Point3;
-use egui_wgpu::wgpu::{self, Buffer, BufferUsages, Texture};
+use color_eyre::eyre::eyre;
+use egui_wgpu::wgpu::{self, Texture};
 use egui_winit::winit::{
-    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
     keyboard::{KeyCode, PhysicalKey},
 };
-use tracing::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info};
 
 use crate::camera::{Camera, CameraController};
+use crate::clipping::{Clipping, MAX_CLIP_PLANES};
+use crate::demos::{default_demo, DemoEntry};
+use crate::gpu_resources::gradients::GradientFilter;
+use crate::lighting::Lighting;
+use crate::render_pipeline::RenderPipeline;
+use crate::slices::{SliceViews, PANE_AXES};
+use crate::transfer_function::{two_dimensional::TransferFunction2D, TransferFunction};
+use crate::volume::{histogram::Histogram, Rescale, ValueWindow};
+use crate::Result;
+
+/// How the samples along a ray are combined into a pixel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderMode {
+    /// Front-to-back compositing through the transfer function
+    #[default]
+    Composite,
+    /// Maximum intensity projection
+    Mip,
+    /// Minimum intensity projection
+    #[value(name = "minip")]
+    #[serde(rename = "minip")]
+    MinIp,
+    /// Average intensity projection
+    Average,
+    /// Maximum intensity projection over the important segments only
+    ImportanceMip,
+    /// Shaded surfaces where the density crosses the isovalues
+    Isosurface,
+}
+
+impl RenderMode {
+    pub const ALL: [Self; 6] = [
+        Self::Composite,
+        Self::Mip,
+        Self::MinIp,
+        Self::Average,
+        Self::ImportanceMip,
+        Self::Isosurface,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Composite => "Composite",
+            Self::Mip => "MIP",
+            Self::MinIp => "MinIP",
+            Self::Average => "Average",
+            Self::ImportanceMip => "Importance MIP",
+            Self::Isosurface => "Isosurface",
+        }
+    }
+
+    /// Projections show the density in gray levels, without the transfer function
+    pub fn is_projection(self) -> bool {
+        matches!(
+            self,
+            Self::Mip | Self::MinIp | Self::Average | Self::ImportanceMip
+        )
+    }
+}
+
+/// Curve compressing the HDR output to the display range, applied by the render pipeline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMapping {
+    /// Colors above 1 are clipped
+    #[default]
+    None,
+    /// `c / (1 + c)`, keeps the hues but flattens the highlights
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve
+    Aces,
+}
+
+impl ToneMapping {
+    pub const ALL: [Self; 3] = [Self::None, Self::Reinhard, Self::Aces];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::Reinhard => "Reinhard",
+            Self::Aces => "ACES",
+        }
+    }
+}
+
+/// Surfaces drawn by [`RenderMode::Isosurface`], at most this many
+pub const MAX_ISOSURFACES: usize = 4;
+
+/// Render scales that can be set, from a quarter of the window's resolution to supersampling
+pub const RENDER_SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.25..=2.0;
+/// With dynamic resolution, the render scale is multiplied by this while the camera moves
+const INTERACTION_RENDER_SCALE: f32 = 0.5;
+/// Time without camera input after which dynamic resolution goes back to the full scale
+const INTERACTION_IDLE_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Isosurface {
+    /// Density of the surface, in the 0..=1 window
+    pub value: f32,
+    /// Unmultiplied RGBA, the alpha lets the surfaces behind show through
+    pub color: [f32; 4],
+}
+
+impl Isosurface {
+    /// Colors given to new surfaces, from the outermost to the innermost
+    pub const PALETTE: [[f32; 4]; MAX_ISOSURFACES] = [
+        [0.9, 0.75, 0.6, 0.3],
+        [0.85, 0.2, 0.15, 0.6],
+        [0.95, 0.95, 0.9, 1.0],
+        [0.3, 0.5, 0.9, 1.0],
+    ];
+
+    pub fn new(value: f32, index: usize) -> Self {
+        Self {
+            value,
+            color: Self::PALETTE[index % MAX_ISOSURFACES],
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct State {
@@ -14,7 +139,51 @@ pub struct State {
     pub camera_controller: CameraController,
     mouse_pressed: bool,
     last_mouse_position: Option<(f64, f64)>,
-    pub transfer_points: Vec<(f32, egui::Color32)>,
+    cursor_position: Option<(f64, f64)>,
+    /// The left button was pressed over a slice pane, the probe follows the cursor
+    dragging_probe: bool,
+    /// Last time the camera was rotated or zoomed
+    last_camera_input: Option<Instant>,
+    /// Edited from the GUI, demos upload it again when its control points change
+    pub transfer_function: TransferFunction,
+    /// Used instead of `transfer_function` when `use_transfer_function_2d` is set
+    pub transfer_function_2d: TransferFunction2D,
+    pub use_transfer_function_2d: bool,
+    pub use_precomputed_gradients: bool,
+    /// Jump over the cells of the occupancy grid that can't contribute to the image
+    pub use_empty_space_skipping: bool,
+    /// Read when the demo loads its volume
+    pub gradient_filter: GradientFilter,
+    /// Volume the demos open, their default one when `None`
+    pub volume: Option<PathBuf>,
+    /// Segment labels of `volume`
+    pub labels: Option<PathBuf>,
+    /// Replaces the rescale of the volume's file
+    pub rescale: Option<Rescale>,
+    /// Replaces the volume's value range as the window of the transfer function
+    pub window: Option<ValueWindow>,
+    /// Set by the demo once its volume is loaded
+    pub histogram: Option<Histogram>,
+    pub histogram_log_scale: bool,
+    /// Label of the segment whose histogram is shown, the whole volume when `None`
+    pub histogram_segment: Option<u8>,
+    /// Why the shaders that changed on disk couldn't be used, by path
+    pub shader_errors: BTreeMap<PathBuf, String>,
+    /// Why the demo couldn't update or compute the last frame, cleared once it can again
+    pub frame_error: Option<String>,
+    /// Demo being run
+    pub demo: &'static DemoEntry,
+    /// Demo picked in the GUI, run in place of the current one once the frame is done
+    pub requested_demo: Option<&'static DemoEntry>,
+    pub render_mode: RenderMode,
+    /// Up to [`MAX_ISOSURFACES`], in any order
+    pub isosurfaces: Vec<Isosurface>,
+    pub clipping: Clipping,
+    pub lighting: Lighting,
+    pub slices: SliceViews,
+    /// Size of the volume's bounding box, the largest side is 1. Set by the demo once its
+    /// volume is loaded, to draw the clip box handles.
+    pub volume_extent: [f32; 3],
     pub density_threshold: f32,
     pub use_importance_coloring: bool,
     pub use_cone_importance_check: bool,
@@ -23,11 +192,33 @@ pub struct State {
     pub use_gaussian_smoothing: bool,
     pub importance_check_ahead_steps: u32,
     pub raymarching_step_size: f32,
+    /// Resolution of the compute output relative to the window's, the render pipeline scales
+    /// it to the window
+    pub render_scale: f32,
+    /// Lower the resolution while the camera moves, see [`State::output_size`]
+    pub dynamic_resolution: bool,
+    /// Start every ray at a random fraction of a step, trading banding for noise
+    pub use_jittering: bool,
+    /// Average the frames while nothing changes, which removes the noise of the jittering
+    pub use_accumulation: bool,
+    pub tone_mapping: ToneMapping,
+    /// In stops, the output is multiplied by `2^exposure` before the tone mapping
+    pub exposure: f32,
+    /// The output is raised to `1 / gamma` after the tone mapping. The transfer functions
+    /// already hold display colors, 1 keeps them.
+    pub gamma: f32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct StateParameters {
     pub camera_position: Point3<f32>,
+    pub render_mode: RenderMode,
+    pub isosurfaces: Vec<Isosurface>,
+    pub clipping: Clipping,
+    pub lighting: Lighting,
+    pub slices: SliceViews,
+    #[serde(alias = "density_threshold")]
     pub density_trheshold: f32,
     pub use_cone_importance_check: bool,
     pub use_importance_coloring: bool,
@@ -36,12 +227,44 @@ pub struct StateParameters {
     pub use_gaussian_smoothing: bool,
     pub importance_check_ahead_steps: u32,
     pub raymarching_step_size: f32,
+    pub use_transfer_function_2d: bool,
+    pub use_precomputed_gradients: bool,
+    pub gradient_filter: GradientFilter,
+    pub use_empty_space_skipping: bool,
+    /// JSON preset to start from, the built-in transfer function is used when missing
+    pub transfer_function: Option<PathBuf>,
+    /// Volume to open, the demo's default one when missing
+    pub volume: Option<PathBuf>,
+    /// Segment labels of `volume`, with their segments described by the JSON file of the
+    /// same name. The default volume comes with its labels, other volumes have none when
+    /// missing.
+    pub labels: Option<PathBuf>,
+    /// Maps the stored voxels to real values, read from the volume's file when missing
+    pub rescale: Option<Rescale>,
+    /// Real values the transfer function spans, the volume's value range when missing
+    pub window: Option<ValueWindow>,
+    /// Only used in a window, `render` always renders at the requested size
+    pub render_scale: f32,
+    pub dynamic_resolution: bool,
+    pub use_jittering: bool,
+    pub use_accumulation: bool,
+    pub tone_mapping: ToneMapping,
+    pub exposure: f32,
+    pub gamma: f32,
 }
 
 impl Default for StateParameters {
     fn default() -> Self {
         Self {
             camera_position: Point3::new(0.5, 0.5, 0.5),
+            render_mode: RenderMode::default(),
+            isosurfaces: vec![Isosurface {
+                value: 0.3,
+                color: [0.95, 0.95, 0.9, 1.0],
+            }],
+            clipping: Clipping::default(),
+            lighting: Lighting::default(),
+            slices: SliceViews::default(),
             use_cone_importance_check: false,
             use_importance_coloring: false,
             use_opacity: true,
@@ -50,20 +273,110 @@ impl Default for StateParameters {
             use_gaussian_smoothing: true,
             importance_check_ahead_steps: 12,
             raymarching_step_size: 0.010,
+            use_transfer_function_2d: false,
+            use_precomputed_gradients: false,
+            gradient_filter: GradientFilter::default(),
+            use_empty_space_skipping: true,
+            transfer_function: None,
+            volume: None,
+            labels: None,
+            rescale: None,
+            window: None,
+            render_scale: 1.0,
+            dynamic_resolution: false,
+            use_jittering: false,
+            use_accumulation: true,
+            tone_mapping: ToneMapping::default(),
+            exposure: 0.0,
+            gamma: 1.0,
         }
     }
 }
 
 impl State {
-    pub fn with_parameters(aspect: f32, parameters: StateParameters) -> Self {
+    pub fn with_parameters(aspect: f32, parameters: StateParameters) -> Result<Self> {
+        if parameters.isosurfaces.len() > MAX_ISOSURFACES {
+            return Err(eyre!(
+                "{} isosurfaces given, at most {} can be drawn",
+                parameters.isosurfaces.len(),
+                MAX_ISOSURFACES
+            ));
+        }
+        if parameters.clipping.planes.len() > MAX_CLIP_PLANES {
+            return Err(eyre!(
+                "{} clipping planes given, at most {} can be used",
+                parameters.clipping.planes.len(),
+                MAX_CLIP_PLANES
+            ));
+        }
+        parameters.lighting.validate()?;
+        let slab = &parameters.clipping.slab;
+        if slab.enabled && slab.thickness <= 0.0 {
+            return Err(eyre!(
+                "The slab needs a positive thickness, got {}",
+                slab.thickness
+            ));
+        }
+        if !RENDER_SCALE_RANGE.contains(&parameters.render_scale) {
+            return Err(eyre!(
+                "Invalid render scale {}, expected {} to {}",
+                parameters.render_scale,
+                RENDER_SCALE_RANGE.start(),
+                RENDER_SCALE_RANGE.end()
+            ));
+        }
+        if parameters.gamma <= 0.0 {
+            return Err(eyre!(
+                "The gamma has to be positive, got {}",
+                parameters.gamma
+            ));
+        }
+        if let Some(rescale) = &parameters.rescale {
+            rescale.validate()?;
+        }
+        if let Some(window) = &parameters.window {
+            window.validate()?;
+        }
         let camera =
             crate::camera::Camera::default_with_aspect_and_pos(aspect, parameters.camera_position);
-        Self {
+        let transfer_function = match &parameters.transfer_function {
+            Some(path) => {
+                info!("Loading transfer function preset {:?}", path);
+                TransferFunction::from_file(path)?
+            }
+            None => TransferFunction::default(),
+        };
+        Ok(Self {
             camera,
             camera_controller: CameraController::new(0.2, 0.2),
             mouse_pressed: false,
             last_mouse_position: None,
-            transfer_points: Vec::new(),
+            cursor_position: None,
+            dragging_probe: false,
+            last_camera_input: None,
+            transfer_function,
+            transfer_function_2d: TransferFunction2D::default(),
+            use_transfer_function_2d: parameters.use_transfer_function_2d,
+            use_precomputed_gradients: parameters.use_precomputed_gradients,
+            gradient_filter: parameters.gradient_filter,
+            volume: parameters.volume,
+            labels: parameters.labels,
+            rescale: parameters.rescale,
+            window: parameters.window,
+            use_empty_space_skipping: parameters.use_empty_space_skipping,
+            histogram: None,
+            histogram_log_scale: true,
+            histogram_segment: None,
+            shader_errors: BTreeMap::new(),
+            frame_error: None,
+            demo: default_demo(),
+            requested_demo: None,
+            render_mode: parameters.render_mode,
+            isosurfaces: parameters.isosurfaces,
+            clipping: parameters.clipping,
+            lighting: parameters.lighting,
+            slices: parameters.slices,
+            volume_extent: [1.0; 3],
             density_threshold: parameters.density_trheshold,
             use_cone_importance_check: parameters.use_cone_importance_check,
             use_importance_coloring: parameters.use_importance_coloring,
@@ -72,12 +385,44 @@ impl State {
             use_gaussian_smoothing: parameters.use_gaussian_smoothing,
             importance_check_ahead_steps: parameters.importance_check_ahead_steps,
             raymarching_step_size: parameters.raymarching_step_size,
+            render_scale: parameters.render_scale,
+            dynamic_resolution: parameters.dynamic_resolution,
+            use_jittering: parameters.use_jittering,
+            use_accumulation: parameters.use_accumulation,
+            tone_mapping: parameters.tone_mapping,
+            exposure: parameters.exposure,
+            gamma: parameters.gamma,
+        })
+    }
+
+    /// Moves the transfer function to the window of the volume the demo loaded, so that the
+    /// GUI edits it in the volume's values
+    pub fn apply_value_window(&mut self, window: Option<ValueWindow>) {
+        if let Some(window) = window {
+            self.transfer_function = self.transfer_function.clone().mapped_to_window(window);
         }
     }
 
+    /// Size of the compute output for a window of `window_size`. With dynamic resolution it
+    /// drops while the camera is dragged or zoomed, and goes back up once it stops.
+    pub fn output_size(&self, window_size: (u32, u32)) -> (u32, u32) {
+        let interacting = self.mouse_pressed
+            || self
+                .last_camera_input
+                .is_some_and(|input| input.elapsed() < INTERACTION_IDLE_DELAY);
+        let scale = if self.dynamic_resolution && interacting {
+            self.render_scale * INTERACTION_RENDER_SCALE
+        } else {
+            self.render_scale
+        };
+        let scaled = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+        (scaled(window_size.0), scaled(window_size.1))
+    }
+
     pub fn process_input(
         &mut self,
         ctx: &super::gpu_context::GpuContext,
+        render_pipeline: &RenderPipeline,
         texture_to_copy: &Texture,
         event: &WindowEvent,
     ) -> bool {
@@ -91,28 +436,42 @@ impl State {
                     },
                 ..
             } => {
-                let output = ctx.surface.get_current_texture().unwrap();
                 let now = std::time::SystemTime::now()
                     .duration_since(std::time::UNIX_EPOCH)
                     .unwrap()
                     .as_secs();
-                let screenshot_path = format!("screenshot_{}.png", now);
-
-                pollster::block_on(save_screenshot(
-                    &ctx.device,
-                    &ctx.queue,
-                    &output.texture,
-                    output.texture.size().width,
-                    output.texture.size().height,
-                    &screenshot_path,
-                    texture_to_copy,
-                ));
-
-                info!("Screenshot saved to {}", screenshot_path);
+                // What the window shows, and the HDR output before the tone mapping
+                let png_path = format!("screenshot_{}.png", now);
+                let exr_path = format!("screenshot_{}.exr", now);
+                let saved = render_pipeline
+                    .render_to_texture(ctx, texture_to_copy.size())
+                    .and_then(|texture| {
+                        pollster::block_on(save_screenshot(
+                            &ctx.device,
+                            &ctx.queue,
+                            &texture,
+                            png_path.as_ref(),
+                        ))
+                    })
+                    .and_then(|()| {
+                        pollster::block_on(save_screenshot(
+                            &ctx.device,
+                            &ctx.queue,
+                            texture_to_copy,
+                            exr_path.as_ref(),
+                        ))
+                    });
+                match saved {
+                    Ok(()) => info!("Screenshots saved to {} and {}", png_path, exr_path),
+                    Err(e) => error!("Failed to save screenshot: {}", e),
+                }
                 true
             }
             WindowEvent::CursorMoved { position, .. } => {
-                if self.mouse_pressed {
+                self.cursor_position = Some((position.x, position.y));
+                if self.dragging_probe {
+                    self.move_probe_to_cursor(ctx);
+                } else if self.mouse_pressed {
                     let current_pos = (position.x, position.y);
 
                     // Calculate delta movement when mouse is pressed
@@ -122,6 +481,7 @@ impl State {
 
                         // Use the existing process_mouse method
                         self.camera_controller.process_mouse(dx, dy);
+                        self.last_camera_input = Some(Instant::now());
                     }
 
                     // Update last mouse position
@@ -130,7 +490,22 @@ impl State {
                 true
             }
             WindowEvent::MouseWheel { delta, .. } => {
-                self.camera_controller.process_scroll(delta);
+                match self.pane_under_cursor(ctx) {
+                    // Scroll through the slices of the pane
+                    Some((pane, _)) => {
+                        let lines = match delta {
+                            MouseScrollDelta::LineDelta(_, y) => *y,
+                            MouseScrollDelta::PixelDelta(p) => p.y as f32 / 20.0,
+                        };
+                        let axis = PANE_AXES[pane];
+                        self.slices.probe[axis] =
+                            (self.slices.probe[axis] + lines * 0.005).clamp(0.0, 1.0);
+                    }
+                    None => {
+                        self.camera_controller.process_scroll(delta);
+                        self.last_camera_input = Some(Instant::now());
+                    }
+                }
                 true
             }
             WindowEvent::MouseInput {
@@ -138,7 +513,16 @@ impl State {
                 state,
                 ..
             } => {
-                self.mouse_pressed = *state == ElementState::Pressed;
+                let pressed = *state == ElementState::Pressed;
+                if pressed && self.pane_under_cursor(ctx).is_some() {
+                    self.dragging_probe = true;
+                    self.move_probe_to_cursor(ctx);
+                } else {
+                    self.mouse_pressed = pressed;
+                }
+                if !pressed {
+                    self.dragging_probe = false;
+                }
                 true
             }
             _ => false,
@@ -150,24 +534,51 @@ impl State {
         r
     }
 
+    fn pane_under_cursor(&self, ctx: &super::gpu_context::GpuContext) -> Option<(usize, [f32; 3])> {
+        self.slices.position_at(
+            self.volume_extent,
+            (ctx.size.width, ctx.size.height),
+            self.cursor_position?,
+        )
+    }
+
+    fn move_probe_to_cursor(&mut self, ctx: &super::gpu_context::GpuContext) {
+        if let Some((_, position)) = self.pane_under_cursor(ctx) {
+            self.slices.probe = position;
+        }
+    }
+
     pub fn update(&mut self) {
         self.camera_controller.update_camera(&mut self.camera);
     }
 }
 
+use half::f16;
 use image::{ImageBuffer, Rgba};
-use wgpu::util::DeviceExt;
 
-async fn save_screenshot(
+/// Reads an Rgba8Unorm or Rgba16Float texture back from the GPU and saves it as an image.
+/// Float textures keep their range and precision, they are saved to formats with float
+/// channels like EXR.
+pub async fn save_screenshot(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     texture: &wgpu::Texture,
-    width: u32,
-    height: u32,
-    filename: &str,
-    texture_to_copy: &Texture,
-) {
-    let buffer_size = (width * height * 4) as wgpu::BufferAddress;
+    path: &Path,
+) -> Result<()> {
+    let width = texture.width();
+    let height = texture.height();
+    let bytes_per_pixel = match texture.format() {
+        wgpu::TextureFormat::Rgba8Unorm => 4,
+        wgpu::TextureFormat::Rgba16Float => 8,
+        format => return Err(eyre!("Can't save a {:?} texture", format)),
+    };
+
+    // Buffer rows must be aligned, they are unpadded after the readback
+    let unpadded_bytes_per_row = bytes_per_pixel * width;
+    let bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer_size = (bytes_per_row * height) as wgpu::BufferAddress;
     let buffer_desc = wgpu::BufferDescriptor {
         label: Some("Screenshot Buffer"),
         size: buffer_size,
@@ -191,7 +602,7 @@ async fn save_screenshot(
         buffer: &buffer,
         layout: wgpu::ImageDataLayout {
             offset: 0,
-            bytes_per_row: Some(4 * width),
+            bytes_per_row: Some(bytes_per_row),
             rows_per_image: Some(height),
         },
     };
@@ -210,11 +621,86 @@ async fn save_screenshot(
     buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
 
     device.poll(wgpu::Maintain::Wait);
-    receiver.receive().await.unwrap().unwrap();
+    receiver
+        .receive()
+        .await
+        .ok_or(eyre!("Screenshot buffer mapping was cancelled"))??;
 
     let data = buffer_slice.get_mapped_range();
-    let buffer = ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, data.to_vec()).unwrap();
-    buffer.save(filename).unwrap();
+    let pixels = data
+        .chunks_exact(bytes_per_row as usize)
+        .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+        .copied();
+    let too_small = || eyre!("Screenshot buffer is smaller than the image");
+    if texture.format() == wgpu::TextureFormat::Rgba16Float {
+        let bytes: Vec<u8> = pixels.collect();
+        let channels = bytes
+            .chunks_exact(2)
+            .map(|c| f16::from_le_bytes([c[0], c[1]]).to_f32())
+            .collect();
+        ImageBuffer::<Rgba<f32>, Vec<f32>>::from_raw(width, height, channels)
+            .ok_or_else(too_small)?
+            .save(path)?;
+    } else {
+        ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, pixels.collect())
+            .ok_or_else(too_small)?
+            .save(path)?;
+    }
 
     drop(data);
+    buffer.unmap();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(render_scale: f32, dynamic_resolution: bool) -> State {
+        State::with_parameters(
+            1.0,
+            StateParameters {
+                render_scale,
+                dynamic_resolution,
+                ..StateParameters::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn scales_the_output_size() {
+        assert_eq!(state(1.0, false).output_size((800, 600)), (800, 600));
+        assert_eq!(state(0.5, false).output_size((800, 600)), (400, 300));
+        assert_eq!(state(2.0, false).output_size((800, 600)), (1600, 1200));
+        // Rounded to the nearest pixel
+        assert_eq!(state(0.25, false).output_size((10, 6)), (3, 2));
+    }
+
+    #[test]
+    fn output_is_at_least_one_pixel() {
+        assert_eq!(state(0.25, false).output_size((1, 1)), (1, 1));
+        assert_eq!(state(1.0, false).output_size((0, 0)), (1, 1));
+    }
+
+    #[test]
+    fn halves_the_output_while_interacting() {
+        let mut state = state(0.5, true);
+        assert_eq!(state.output_size((800, 600)), (400, 300));
+
+        state.mouse_pressed = true;
+        assert_eq!(state.output_size((800, 600)), (200, 150));
+
+        state.mouse_pressed = false;
+        state.last_camera_input = Some(Instant::now());
+        assert_eq!(state.output_size((800, 600)), (200, 150));
+
+        // Back to the full scale once the camera stops
+        state.last_camera_input = Some(Instant::now() - 2 * INTERACTION_IDLE_DELAY);
+        assert_eq!(state.output_size((800, 600)), (400, 300));
+
+        state.dynamic_resolution = false;
+        state.mouse_pressed = true;
+        assert_eq!(state.output_size((800, 600)), (400, 300));
+    }
 }
@@ -0,0 +1,374 @@
+/// Importers for transfer functions of other tools, ParaView JSON colormaps and 3D Slicer
+/// volume property (`.vp`) presets.
+///
+/// Both store control points in data values. They are kept as such through the function's
+/// `value_window`, the extent of the preset's own points, and moved to the volume's window
+/// when the function is applied to it. Without a volume, the preset spans its own extent.
+use color_eyre::eyre::eyre;
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::{volume::ValueWindow, Result};
+
+use super::{AlphaPoint, Interpolation, RgbPoint, TransferFunction, TransferFunctionPreset};
+
+/// Resolution until the function is applied to a volume, which sets it from its window, see
+/// [`ValueWindow::max_density`]
+const UNAPPLIED_MAX_DENSITY: u32 = 255;
+
+/// ParaView colormaps have `RGBPoints`, files exported from it hold a list of them
+pub fn is_paraview(json: &Value) -> bool {
+    match json {
+        Value::Array(presets) => presets.iter().any(|p| p.get("RGBPoints").is_some()),
+        Value::Object(preset) => preset.contains_key("RGBPoints"),
+        _ => false,
+    }
+}
+
+/// `RGBPoints` is a flat `x, r, g, b` list, and the optional opacity `Points` a flat
+/// `x, opacity, midpoint, sharpness` list. Colormaps without opacity get a linear ramp.
+pub fn from_paraview(json: &Value) -> Result<TransferFunction> {
+    let preset = match json {
+        Value::Array(presets) => {
+            let mut colormaps = presets.iter().filter(|p| p.get("RGBPoints").is_some());
+            let preset = colormaps
+                .next()
+                .ok_or(eyre!("No ParaView colormap with RGBPoints found"))?;
+            if colormaps.next().is_some() {
+                info!("File contains several ParaView colormaps, using the first one");
+            }
+            preset
+        }
+        preset => preset,
+    };
+    if let Some(name) = preset.get("Name").and_then(Value::as_str) {
+        info!("Importing ParaView colormap {:?}", name);
+    }
+    match preset.get("ColorSpace").and_then(Value::as_str) {
+        Some("RGB") | None => {}
+        Some(color_space) => warn!(
+            "ParaView colormap uses the {:?} color space, it will be interpolated in RGB",
+            color_space
+        ),
+    }
+
+    let rgb = number_list(preset, "RGBPoints")?;
+    if !rgb.len().is_multiple_of(4) {
+        return Err(eyre!(
+            "ParaView RGBPoints must be x, r, g, b quadruples, got {} values",
+            rgb.len()
+        ));
+    }
+    let rgb_points: Vec<(f32, [f32; 3])> =
+        rgb.chunks(4).map(|c| (c[0], [c[1], c[2], c[3]])).collect();
+
+    let alpha_points: Vec<(f32, f32)> = match preset.get("Points") {
+        Some(_) => {
+            let alpha = number_list(preset, "Points")?;
+            if !alpha.len().is_multiple_of(4) {
+                return Err(eyre!(
+                    "ParaView opacity Points must be x, opacity, midpoint, sharpness quadruples, got {} values",
+                    alpha.len()
+                ));
+            }
+            if alpha.chunks(4).any(|c| c[2] != 0.5 || c[3] != 0.0) {
+                warn!("ParaView opacity midpoint and sharpness are ignored, opacity is linear");
+            }
+            alpha.chunks(4).map(|c| (c[0], c[1])).collect()
+        }
+        None => {
+            let first = rgb_points.first().map_or(0.0, |p| p.0);
+            let last = rgb_points.last().map_or(1.0, |p| p.0);
+            vec![(first, 0.0), (last, 1.0)]
+        }
+    };
+
+    in_data_values(Interpolation::Linear, rgb_points, alpha_points)
+}
+
+/// A `.vp` file is written by Slicer's `vtkMRMLVolumePropertyStorageNode`, one value per line:
+///
+/// ```text
+/// interpolation (0 nearest, 1 linear)
+/// shade
+/// ambient
+/// diffuse
+/// specular
+/// specular power
+/// scalar opacity: count, then x, opacity pairs
+/// gradient opacity: count, then x, opacity pairs
+/// color: count, then x, r, g, b quadruples
+/// ```
+///
+/// Only the interpolation, scalar opacity and color lines are used.
+pub fn from_slicer_vp(contents: &str) -> Result<TransferFunction> {
+    let lines: Vec<&str> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    if lines.len() < 9 {
+        return Err(eyre!(
+            "Slicer volume property needs 9 lines, got {}",
+            lines.len()
+        ));
+    }
+
+    let interpolation = match lines[0] {
+        "0" => Interpolation::Constant,
+        "1" => Interpolation::Linear,
+        other => return Err(eyre!("Unknown Slicer interpolation type {:?}", other)),
+    };
+
+    let opacity = counted_list(lines[6], 2, "scalar opacity")?;
+    let alpha_points = opacity.chunks(2).map(|c| (c[0], c[1])).collect();
+    let color = counted_list(lines[8], 4, "color")?;
+    let rgb_points = color
+        .chunks(4)
+        .map(|c| (c[0], [c[1], c[2], c[3]]))
+        .collect();
+
+    in_data_values(interpolation, rgb_points, alpha_points)
+}
+
+fn number_list(preset: &Value, key: &str) -> Result<Vec<f32>> {
+    preset
+        .get(key)
+        .and_then(Value::as_array)
+        .ok_or(eyre!("ParaView {} must be a list of numbers", key))?
+        .iter()
+        .map(|v| {
+            v.as_f64().map(|v| v as f32).ok_or(eyre!(
+                "ParaView {} contains {}, not a number",
+                key,
+                v
+            ))
+        })
+        .collect()
+}
+
+/// `8 -3024 0 -16 0 641 0.7 3071 0.7` - the leading count is the number of values that follow
+fn counted_list(line: &str, values_per_point: usize, name: &str) -> Result<Vec<f32>> {
+    let mut values = line.split_whitespace().map(str::parse::<f32>);
+    let count = values
+        .next()
+        .ok_or(eyre!("Slicer {} line is empty", name))?? as usize;
+    let values = values.collect::<std::result::Result<Vec<_>, _>>()?;
+    if values.len() != count || !count.is_multiple_of(values_per_point) {
+        return Err(eyre!(
+            "Slicer {} line announces {} values in groups of {}, got {}",
+            name,
+            count,
+            values_per_point,
+            values.len()
+        ));
+    }
+    Ok(values)
+}
+
+/// Keeps the data values of the points as the function's window, the extent of all the points
+fn in_data_values(
+    interpolation: Interpolation,
+    rgb_points: Vec<(f32, [f32; 3])>,
+    alpha_points: Vec<(f32, f32)>,
+) -> Result<TransferFunction> {
+    let (min, max) = rgb_points
+        .iter()
+        .map(|p| p.0)
+        .chain(alpha_points.iter().map(|p| p.0))
+        .fold((f32::MAX, f32::MIN), |(min, max), x| {
+            (min.min(x), max.max(x))
+        });
+    if max <= min {
+        return Err(eyre!(
+            "Transfer function points must span a value range, got {}..={}",
+            min,
+            max
+        ));
+    }
+    let normalize = |x: f32| (x - min) / (max - min);
+    info!("Transfer function points span the values {}..={}", min, max);
+
+    TransferFunctionPreset {
+        max_density: UNAPPLIED_MAX_DENSITY,
+        interpolation,
+        value_window: Some(ValueWindow::new(min, max)),
+        rgb_points: rgb_points
+            .into_iter()
+            .map(|(x, color)| RgbPoint {
+                iso_value: normalize(x),
+                color,
+            })
+            .collect(),
+        alpha_points: alpha_points
+            .into_iter()
+            .map(|(x, alpha)| AlphaPoint {
+                iso_value: normalize(x),
+                alpha,
+            })
+            .collect(),
+    }
+    .try_into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn preset(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("presets")
+            .join(name)
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    /// `(iso_value, alpha)` of every alpha point
+    fn alphas(tf: &TransferFunction) -> Vec<(f32, f32)> {
+        tf.alpha_points()
+            .iter()
+            .map(|p| (p.iso_value, p.color.w))
+            .collect()
+    }
+
+    #[test]
+    fn imports_paraview_colormap() {
+        let tf = TransferFunction::from_file(&preset("cool_to_warm.json")).unwrap();
+
+        assert_eq!(tf.interpolation, Interpolation::Linear);
+        assert_eq!(tf.value_window, Some(ValueWindow::new(-1.0, 1.0)));
+        let expected_rgb = [
+            (0.0, [0.231373, 0.298039, 0.752941]),
+            (0.5, [0.865, 0.865, 0.865]),
+            (1.0, [0.705882, 0.015686, 0.149020]),
+        ];
+        assert_eq!(tf.rgb_points().len(), expected_rgb.len());
+        for (point, (iso, color)) in tf.rgb_points().iter().zip(expected_rgb) {
+            assert_close(point.iso_value, iso);
+            for (actual, expected) in [point.color.x, point.color.y, point.color.z]
+                .into_iter()
+                .zip(color)
+            {
+                assert_close(actual, expected);
+            }
+        }
+        assert_eq!(alphas(&tf), vec![(0.0, 0.0), (1.0, 1.0)]);
+    }
+
+    #[test]
+    fn imports_slicer_volume_property() {
+        let tf = TransferFunction::from_file(&preset("ct_bone.vp")).unwrap();
+
+        assert_eq!(tf.interpolation, Interpolation::Linear);
+        assert_eq!(tf.value_window, Some(ValueWindow::new(-3024.0, 3071.0)));
+        let iso_value = |value: f32| (value + 3024.0) / 6095.0;
+        let expected_alphas = [
+            (-3024.0, 0.0),
+            (-16.4458, 0.0),
+            (641.385, 0.715686),
+            (3071.0, 0.705882),
+        ];
+        for ((iso, alpha), (value, expected)) in alphas(&tf).into_iter().zip(expected_alphas) {
+            assert_close(iso, iso_value(value));
+            assert_close(alpha, expected);
+        }
+        let rgb = tf.rgb_points();
+        assert_eq!(rgb.len(), 4);
+        assert_close(rgb[1].iso_value, iso_value(-16.4458));
+        assert_close(rgb[1].color.x, 0.729412);
+        assert_close(rgb[2].color.z, 0.552941);
+        assert_eq!(rgb[3].color.truncate(), cgmath::Vector3::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn maps_data_values_to_the_volume_window() {
+        let tf = TransferFunction::from_file(&preset("ct_bone.vp")).unwrap();
+        let window = ValueWindow::new(-1000.0, 1000.0);
+        let mapped = tf.clone().mapped_to_window(window);
+
+        assert_eq!(mapped.value_window, Some(window));
+        let alphas = alphas(&mapped);
+        assert_eq!(alphas.len(), 4);
+        // Cut where the function leaves the window, the inner points keep their values
+        assert_close(alphas[0].0, 0.0);
+        assert_close(alphas[1].0, (-16.4458 + 1000.0) / 2000.0);
+        assert_close(alphas[2].0, (641.385 + 1000.0) / 2000.0);
+        assert_close(alphas[3].0, 1.0);
+        assert_close(alphas[0].1, 0.0);
+        assert_close(alphas[3].1, tf.get((1000.0 + 3024.0) / 6095.0).w);
+
+        // Relative functions adopt the window without moving
+        let relative = TransferFunction::default().mapped_to_window(window);
+        assert_eq!(relative.value_window, Some(window));
+        assert_eq!(
+            relative.rgb_points(),
+            TransferFunction::default().rgb_points()
+        );
+    }
+
+    /// Imports `name`, saves it as a volym preset and loads that back
+    fn round_trip(name: &str) -> (TransferFunction, TransferFunction) {
+        let imported = TransferFunction::from_file(&preset(name)).unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "volym_{}_{}.json",
+            name.replace('.', "_"),
+            std::process::id()
+        ));
+        imported.save_to_json_file(&path).unwrap();
+        let loaded = TransferFunction::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        (imported, loaded.unwrap())
+    }
+
+    #[test]
+    fn saved_imports_load_back_the_same() {
+        for name in ["cool_to_warm.json", "ct_bone.vp"] {
+            let (imported, loaded) = round_trip(name);
+
+            assert_eq!(loaded.rgb_points(), imported.rgb_points(), "{}", name);
+            assert_eq!(loaded.alpha_points(), imported.alpha_points(), "{}", name);
+            assert_eq!(loaded.interpolation, imported.interpolation, "{}", name);
+            assert_eq!(loaded.value_window, imported.value_window, "{}", name);
+            assert!(loaded.value_window.is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_partial_paraview_quadruples() {
+        let error = from_paraview(&json!({ "RGBPoints": [0, 1, 1, 1, 1, 0, 0] })).unwrap_err();
+        assert!(error.to_string().contains("quadruples"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_wrong_slicer_count() {
+        let contents = std::fs::read_to_string(preset("ct_bone.vp")).unwrap();
+        let contents = contents.replace("8 -3024", "6 -3024");
+        let error = from_slicer_vp(&contents).unwrap_err();
+        assert!(
+            error.to_string().contains("announces 6 values"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn rejects_degenerate_range() {
+        let error = from_paraview(&json!({
+            "RGBPoints": [0.5, 1, 0, 0, 0.5, 0, 0, 1],
+            "Points": [0.5, 0, 0.5, 0, 0.5, 1, 0.5, 0]
+        }))
+        .unwrap_err();
+        assert!(error.to_string().contains("value range"), "{}", error);
+    }
+}
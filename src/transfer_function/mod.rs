@@ -0,0 +1,545 @@
+use crate::{volume::ValueWindow, Result};
+use cgmath::Vector4;
+use color_eyre::eyre::eyre;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+mod import;
+pub mod two_dimensional;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransferControlPoint {
+    pub color: Vector4<f32>,
+    pub iso_value: f32,
+}
+
+impl TransferControlPoint {
+    pub fn new(color: Vector4<f32>, iso_value: f32) -> Self {
+        Self { color, iso_value }
+    }
+}
+
+/// How the function is filled between two control points
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Keeps the value of the previous control point, for crisp material boundaries
+    Constant,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TransferFunction {
+    pub max_density: u32,
+    pub interpolation: Interpolation,
+    rgb_points: Vec<TransferControlPoint>,
+    alpha_points: Vec<TransferControlPoint>,
+    function_vec: Vec<Vector4<f32>>,
+    /// Real values the iso values 0 and 1 stand for. Imported presets keep their data values
+    /// this way, functions without one span whatever window they're applied to.
+    pub value_window: Option<ValueWindow>,
+}
+
+impl Default for TransferFunction {
+    fn default() -> Self {
+        let mut tf = Self::new(255);
+        // RGB Control Points - normalized iso_values (0.0 to 1.0)
+        tf.add_rgb_control_point(TransferControlPoint {
+            color: Vector4::new(0.0, 1.0, 0.0, 1.0), // Green
+            iso_value: 0.0,
+        });
+        tf.add_rgb_control_point(TransferControlPoint {
+            color: Vector4::new(0.0, 1.0, 1.0, 1.0), // Cyan
+            iso_value: 0.2,                          // ~102/255
+        });
+        tf.add_rgb_control_point(TransferControlPoint {
+            color: Vector4::new(1.0, 1.0, 0.0, 1.0), // Yellow
+            iso_value: 0.4,                          // ~153/255
+        });
+        tf.add_rgb_control_point(TransferControlPoint {
+            color: Vector4::new(1.0, 0.0, 1.0, 1.0), // Red
+            iso_value: 0.6,
+        });
+        tf.add_rgb_control_point(TransferControlPoint {
+            color: Vector4::new(1.0, 0.0, 0.0, 1.0), // Red
+            iso_value: 1.0,
+        });
+
+        // Alpha Control Points
+        tf.add_alpha_control_point(TransferControlPoint {
+            color: Vector4::new(0.0, 0.0, 0.0, 0.0), // Transparent
+            iso_value: 0.0,
+        });
+        tf.add_alpha_control_point(TransferControlPoint {
+            color: Vector4::new(0.0, 0.0, 0.0, 1.0), // Opaque
+            iso_value: 1.0,
+        });
+        tf.build();
+        tf
+    }
+}
+
+impl TransferFunction {
+    pub fn new(max_density: u32) -> Self {
+        Self {
+            max_density,
+            interpolation: Interpolation::default(),
+            rgb_points: Vec::new(),
+            alpha_points: Vec::new(),
+            function_vec: vec![Vector4::new(0.0, 0.0, 0.0, 0.0); (max_density + 1) as usize],
+            value_window: None,
+        }
+    }
+
+    /// Rebuilds the function with `max_density + 1` entries, keeping its control points
+    pub fn with_max_density(mut self, max_density: u32) -> Self {
+        self.max_density = max_density;
+        self.function_vec = vec![Vector4::new(0.0, 0.0, 0.0, 0.0); (max_density + 1) as usize];
+        self.build();
+        self
+    }
+
+    /// Moves the points to the real values they stand for in `window`, the volume's. Points
+    /// falling outside of it are cut at its ends. Functions without a window of their own just
+    /// adopt this one.
+    pub fn mapped_to_window(mut self, window: ValueWindow) -> Self {
+        let Some(from) = self.value_window.replace(window) else {
+            return self;
+        };
+        if from == window {
+            return self;
+        }
+        let remap = |points: &[TransferControlPoint]| {
+            let moved: Vec<_> = points
+                .iter()
+                .map(|p| {
+                    let value = from.min + p.iso_value * from.width();
+                    TransferControlPoint::new(p.color, (value - window.min) / window.width())
+                })
+                .collect();
+            clipped_to_window(&moved, self.interpolation)
+        };
+        self.rgb_points = remap(&self.rgb_points);
+        self.alpha_points = remap(&self.alpha_points);
+        self.build();
+        self
+    }
+
+    pub fn add_rgb_control_point(&mut self, point: TransferControlPoint) {
+        self.rgb_points.push(point);
+        self.rgb_points
+            .sort_by(|a, b| a.iso_value.partial_cmp(&b.iso_value).unwrap());
+    }
+
+    pub fn add_alpha_control_point(&mut self, point: TransferControlPoint) {
+        self.alpha_points.push(point);
+        self.alpha_points
+            .sort_by(|a, b| a.iso_value.partial_cmp(&b.iso_value).unwrap());
+    }
+
+    pub fn rgb_points(&self) -> &[TransferControlPoint] {
+        &self.rgb_points
+    }
+
+    pub fn alpha_points(&self) -> &[TransferControlPoint] {
+        &self.alpha_points
+    }
+
+    /// Points must be kept sorted by iso_value, call `build` after editing them
+    pub fn rgb_points_mut(&mut self) -> &mut Vec<TransferControlPoint> {
+        &mut self.rgb_points
+    }
+
+    /// Points must be kept sorted by iso_value, call `build` after editing them
+    pub fn alpha_points_mut(&mut self) -> &mut Vec<TransferControlPoint> {
+        &mut self.alpha_points
+    }
+
+    /// Same control points, interpolation and window, `max_density` is not compared
+    pub fn has_same_control_points(&self, other: &TransferFunction) -> bool {
+        self.interpolation == other.interpolation
+            && self.value_window == other.value_window
+            && self.rgb_points == other.rgb_points
+            && self.alpha_points == other.alpha_points
+    }
+
+    pub fn build(&mut self) {
+        match self.interpolation {
+            Interpolation::Linear => self.build_linear(),
+            Interpolation::Constant => self.build_constant(),
+        }
+    }
+
+    pub fn build_linear(&mut self) {
+        // Start from scratch, points might have been moved or removed
+        self.function_vec.fill(Vector4::new(0.0, 0.0, 0.0, 0.0));
+
+        // RGB interpolation
+        for window in self.rgb_points.windows(2) {
+            let start = &window[0];
+            let end = &window[1];
+
+            let start_idx = (start.iso_value * self.max_density as f32) as u32;
+            let end_idx = (end.iso_value * self.max_density as f32) as u32;
+
+            for x in start_idx..=end_idx {
+                let k = if end_idx == start_idx {
+                    0.0
+                } else {
+                    (x - start_idx) as f32 / (end_idx - start_idx) as f32
+                };
+
+                // Linear interpolation for RGB components
+                self.function_vec[x as usize] = Vector4::new(
+                    start.color.x + (end.color.x - start.color.x) * k,
+                    start.color.y + (end.color.y - start.color.y) * k,
+                    start.color.z + (end.color.z - start.color.z) * k,
+                    self.function_vec[x as usize].w, // Preserve existing alpha
+                );
+            }
+        }
+
+        // Alpha interpolation
+        for window in self.alpha_points.windows(2) {
+            let start = &window[0];
+            let end = &window[1];
+
+            let start_idx = (start.iso_value * self.max_density as f32) as u32;
+            let end_idx = (end.iso_value * self.max_density as f32) as u32;
+
+            for x in start_idx..=end_idx {
+                let k = if end_idx == start_idx {
+                    0.0
+                } else {
+                    (x - start_idx) as f32 / (end_idx - start_idx) as f32
+                };
+
+                // Linear interpolation for alpha component
+                self.function_vec[x as usize].w = start.color.w + (end.color.w - start.color.w) * k;
+            }
+        }
+    }
+
+    pub fn build_constant(&mut self) {
+        self.function_vec.fill(Vector4::new(0.0, 0.0, 0.0, 0.0));
+        let max_density = self.max_density as f32;
+        let index_range = |start: &TransferControlPoint, end: Option<&TransferControlPoint>| {
+            let start_idx = (start.iso_value * max_density) as usize;
+            let end_idx = end.map_or(start_idx + 1, |end| (end.iso_value * max_density) as usize);
+            start_idx..end_idx.max(start_idx + 1)
+        };
+
+        for (i, point) in self.rgb_points.iter().enumerate() {
+            for x in index_range(point, self.rgb_points.get(i + 1)) {
+                let value = &mut self.function_vec[x];
+                *value = Vector4::new(point.color.x, point.color.y, point.color.z, value.w);
+            }
+        }
+        for (i, point) in self.alpha_points.iter().enumerate() {
+            for x in index_range(point, self.alpha_points.get(i + 1)) {
+                self.function_vec[x].w = point.color.w;
+            }
+        }
+    }
+
+    pub fn get(&self, value: f32) -> Vector4<f32> {
+        let idx = (value * self.max_density as f32).clamp(0.0, self.max_density as f32);
+        let idx_floor = idx.floor() as usize;
+        let idx_ceil = (idx_floor + 1).min(self.max_density as usize);
+
+        let t = idx.fract();
+
+        // Linear interpolation between neighboring values
+        let v1 = self.function_vec[idx_floor];
+        let v2 = self.function_vec[idx_ceil];
+
+        Vector4::new(
+            v1.x + (v2.x - v1.x) * t,
+            v1.y + (v2.y - v1.y) * t,
+            v1.z + (v2.z - v1.z) * t,
+            v1.w + (v2.w - v1.w) * t,
+        )
+    }
+
+    /// Opacity of each of the `max_density + 1` texels the function is uploaded as
+    pub fn texel_opacities(&self) -> Vec<f32> {
+        self.function_vec.iter().map(|texel| texel.w).collect()
+    }
+
+    /// Saves the function as a one pixel high image, to look at it while debugging
+    #[allow(dead_code)]
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        let mut imgbuf = image::ImageBuffer::new(self.max_density + 1, 1);
+        for (x, _, pixel) in imgbuf.enumerate_pixels_mut() {
+            let color = self.get(x as f32 / self.max_density as f32); // Normalize input value
+            *pixel = image::Rgba([
+                (color.x * 255.0) as u8,
+                (color.y * 255.0) as u8,
+                (color.z * 255.0) as u8,
+                (color.w * 255.0) as u8,
+            ]);
+        }
+        imgbuf.save(path)?;
+        Ok(())
+    }
+
+    /// Loads a volym JSON preset (see `TransferFunctionPreset`), a ParaView JSON colormap
+    /// or a 3D Slicer `.vp` volume property
+    pub fn from_file(path: &Path) -> Result<Self> {
+        if path.extension().and_then(|e| e.to_str()) == Some("vp") {
+            return import::from_slicer_vp(&std::fs::read_to_string(path)?);
+        }
+        let json: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)?;
+        if import::is_paraview(&json) {
+            return import::from_paraview(&json);
+        }
+        let preset: TransferFunctionPreset = serde_json::from_value(json)
+            .map_err(|e| eyre!("Invalid transfer function preset {:?}: {}", path, e))?;
+        preset.try_into()
+    }
+
+    pub fn save_to_json_file(&self, path: &Path) -> Result<()> {
+        let preset = TransferFunctionPreset::from(self);
+        std::fs::write(path, serde_json::to_string_pretty(&preset)?)?;
+        Ok(())
+    }
+}
+
+/// Keeps the points within `0.0..=1.0`. Points are added at the ends where the function
+/// crosses them, a function that lies entirely outside is empty, as it is outside of its points.
+fn clipped_to_window(
+    points: &[TransferControlPoint],
+    interpolation: Interpolation,
+) -> Vec<TransferControlPoint> {
+    let value_at = |iso_value: f32| {
+        let next = points.iter().position(|p| p.iso_value > iso_value)?;
+        let previous = points[..next].last()?;
+        Some(match interpolation {
+            Interpolation::Constant => previous.color,
+            Interpolation::Linear => {
+                let next = &points[next];
+                let k = (iso_value - previous.iso_value) / (next.iso_value - previous.iso_value);
+                previous.color + (next.color - previous.color) * k
+            }
+        })
+    };
+
+    let mut clipped = Vec::with_capacity(points.len() + 2);
+    if let Some(color) = value_at(0.0).filter(|_| points[0].iso_value < 0.0) {
+        clipped.push(TransferControlPoint::new(color, 0.0));
+    }
+    clipped.extend(
+        points
+            .iter()
+            .filter(|p| (0.0..=1.0).contains(&p.iso_value))
+            .copied(),
+    );
+    let crosses_end = points.last().is_some_and(|p| p.iso_value > 1.0);
+    if let Some(color) = value_at(1.0).filter(|_| crosses_end) {
+        clipped.push(TransferControlPoint::new(color, 1.0));
+    }
+    clipped
+}
+
+/// On-disk format of a transfer function, iso values are normalized to `0.0..=1.0`
+///
+/// ```json
+/// {
+///   "max_density": 255,
+///   "interpolation": "linear",
+///   "value_window": { "min": -1000.0, "max": 3000.0 },
+///   "rgb_points": [{ "iso_value": 0.0, "color": [0.0, 1.0, 0.0] }],
+///   "alpha_points": [{ "iso_value": 0.0, "alpha": 0.0 }]
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TransferFunctionPreset {
+    max_density: u32,
+    #[serde(default)]
+    interpolation: Interpolation,
+    /// Real values of the iso values 0 and 1, the function spans the volume's window without
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value_window: Option<ValueWindow>,
+    rgb_points: Vec<RgbPoint>,
+    alpha_points: Vec<AlphaPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RgbPoint {
+    iso_value: f32,
+    color: [f32; 3],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AlphaPoint {
+    iso_value: f32,
+    alpha: f32,
+}
+
+impl From<&TransferFunction> for TransferFunctionPreset {
+    fn from(tf: &TransferFunction) -> Self {
+        Self {
+            max_density: tf.max_density,
+            interpolation: tf.interpolation,
+            value_window: tf.value_window,
+            rgb_points: tf
+                .rgb_points
+                .iter()
+                .map(|p| RgbPoint {
+                    iso_value: p.iso_value,
+                    color: [p.color.x, p.color.y, p.color.z],
+                })
+                .collect(),
+            alpha_points: tf
+                .alpha_points
+                .iter()
+                .map(|p| AlphaPoint {
+                    iso_value: p.iso_value,
+                    alpha: p.color.w,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<TransferFunctionPreset> for TransferFunction {
+    type Error = crate::Error;
+
+    fn try_from(preset: TransferFunctionPreset) -> Result<Self> {
+        if preset.max_density == 0 {
+            return Err(eyre!("Transfer function max_density must be at least 1"));
+        }
+        if preset.rgb_points.len() < 2 || preset.alpha_points.len() < 2 {
+            return Err(eyre!(
+                "Transfer function needs at least 2 rgb and 2 alpha points, got {} and {}",
+                preset.rgb_points.len(),
+                preset.alpha_points.len()
+            ));
+        }
+        let mut iso_values = (preset.rgb_points.iter().map(|p| p.iso_value))
+            .chain(preset.alpha_points.iter().map(|p| p.iso_value));
+        if let Some(iso_value) = iso_values.find(|v| !(0.0..=1.0).contains(v)) {
+            return Err(eyre!(
+                "Transfer function iso values must be within 0..=1, got {}",
+                iso_value
+            ));
+        }
+
+        if let Some(window) = &preset.value_window {
+            window.validate()?;
+        }
+
+        let mut tf = TransferFunction::new(preset.max_density);
+        tf.interpolation = preset.interpolation;
+        tf.value_window = preset.value_window;
+        for p in preset.rgb_points {
+            let [r, g, b] = p.color;
+            tf.add_rgb_control_point(TransferControlPoint::new(
+                Vector4::new(r, g, b, 1.0),
+                p.iso_value,
+            ));
+        }
+        for p in preset.alpha_points {
+            tf.add_alpha_control_point(TransferControlPoint::new(
+                Vector4::new(0.0, 0.0, 0.0, p.alpha),
+                p.iso_value,
+            ));
+        }
+        tf.build();
+        Ok(tf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn from_json(json: serde_json::Value) -> Result<TransferFunction> {
+        serde_json::from_value::<TransferFunctionPreset>(json)?.try_into()
+    }
+
+    fn valid_preset() -> serde_json::Value {
+        json!({
+            "max_density": 4,
+            "rgb_points": [
+                { "iso_value": 0.0, "color": [0.0, 1.0, 0.0] },
+                { "iso_value": 1.0, "color": [1.0, 0.0, 0.0] }
+            ],
+            "alpha_points": [
+                { "iso_value": 0.0, "alpha": 0.0 },
+                { "iso_value": 1.0, "alpha": 1.0 }
+            ]
+        })
+    }
+
+    fn assert_rejected(json: serde_json::Value, message: &str) {
+        let error = from_json(json).unwrap_err();
+        assert!(error.to_string().contains(message), "{}", error);
+    }
+
+    #[test]
+    fn saved_function_loads_back_the_same() {
+        let mut tf = TransferFunction::default().with_max_density(1000);
+        tf.interpolation = Interpolation::Constant;
+        tf.value_window = Some(ValueWindow::new(-1000.0, 3000.0));
+        tf.build();
+
+        let path = std::env::temp_dir().join(format!("volym_preset_{}.json", std::process::id()));
+        tf.save_to_json_file(&path).unwrap();
+        let loaded = TransferFunction::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert!(loaded.has_same_control_points(&tf));
+        assert_eq!(loaded.max_density, 1000);
+        assert_eq!(loaded.texel_opacities(), tf.texel_opacities());
+    }
+
+    #[test]
+    fn loads_defaults_of_optional_fields() {
+        let tf = from_json(valid_preset()).unwrap();
+
+        assert_eq!(tf.interpolation, Interpolation::Linear);
+        assert_eq!(tf.value_window, None);
+        assert_eq!(tf.get(0.5), Vector4::new(0.5, 0.5, 0.0, 0.5));
+    }
+
+    #[test]
+    fn rejects_zero_max_density() {
+        let mut json = valid_preset();
+        json["max_density"] = json!(0);
+        assert_rejected(json, "max_density");
+    }
+
+    #[test]
+    fn rejects_single_points() {
+        let mut json = valid_preset();
+        json["rgb_points"].as_array_mut().unwrap().pop();
+        assert_rejected(json, "at least 2");
+
+        let mut json = valid_preset();
+        json["alpha_points"].as_array_mut().unwrap().pop();
+        assert_rejected(json, "at least 2");
+    }
+
+    #[test]
+    fn rejects_iso_values_outside_the_unit_range() {
+        let mut json = valid_preset();
+        json["rgb_points"][1]["iso_value"] = json!(1.5);
+        assert_rejected(json, "1.5");
+
+        let mut json = valid_preset();
+        json["alpha_points"][0]["iso_value"] = json!(-0.25);
+        assert_rejected(json, "-0.25");
+    }
+
+    #[test]
+    fn rejects_inverted_value_window() {
+        let mut json = valid_preset();
+        json["value_window"] = json!({ "min": 3000.0, "max": -1000.0 });
+        assert_rejected(json, "value window");
+    }
+}
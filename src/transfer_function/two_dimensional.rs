@@ -0,0 +1,117 @@
+/// Transfer function indexed by density and gradient magnitude, which tells material boundaries
+/// (high gradient) apart from interiors of a similar density (low gradient)
+use cgmath::Vector4;
+
+/// Resolution of the texture the function is uploaded as, density along x, gradient along y
+pub const WIDTH: u32 = 256;
+pub const HEIGHT: u32 = 128;
+
+/// A colored rectangle of the density x gradient magnitude domain, both normalized to `0..=1`.
+/// Opacity peaks at the center density and falls off linearly towards the density edges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransferWidget2D {
+    pub density: [f32; 2],
+    pub gradient: [f32; 2],
+    pub color: Vector4<f32>,
+}
+
+impl TransferWidget2D {
+    pub fn new(density: [f32; 2], gradient: [f32; 2], color: Vector4<f32>) -> Self {
+        Self {
+            density,
+            gradient,
+            color,
+        }
+    }
+
+    /// Opacity of the widget at a point, 0 outside of it
+    pub fn opacity(&self, density: f32, gradient: f32) -> f32 {
+        let [d_min, d_max] = self.density;
+        let [g_min, g_max] = self.gradient;
+        if density < d_min || density > d_max || gradient < g_min || gradient > g_max {
+            return 0.0;
+        }
+        let half_width = (d_max - d_min) / 2.0;
+        if half_width <= 0.0 {
+            return self.color.w;
+        }
+        let center = (d_min + d_max) / 2.0;
+        self.color.w * (1.0 - (density - center).abs() / half_width)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TransferFunction2D {
+    pub widgets: Vec<TransferWidget2D>,
+    /// Gradient magnitude, in density change per voxel, mapped to the top of the function
+    pub max_gradient_magnitude: f32,
+}
+
+impl Default for TransferFunction2D {
+    fn default() -> Self {
+        Self {
+            widgets: vec![
+                // Boundaries of anything above the background
+                TransferWidget2D::new([0.1, 1.0], [0.15, 1.0], Vector4::new(1.0, 0.85, 0.6, 0.9)),
+                // Faint interiors
+                TransferWidget2D::new([0.3, 1.0], [0.0, 0.15], Vector4::new(0.8, 0.2, 0.1, 0.1)),
+            ],
+            max_gradient_magnitude: 0.25,
+        }
+    }
+}
+
+impl TransferFunction2D {
+    /// Widgets are blended by their opacity, their opacities accumulate as if stacked
+    pub fn get(&self, density: f32, gradient: f32) -> Vector4<f32> {
+        let mut color = Vector4::new(0.0, 0.0, 0.0, 0.0);
+        let mut transparency = 1.0;
+        let mut opacity_sum = 0.0;
+        for widget in &self.widgets {
+            let opacity = widget.opacity(density, gradient);
+            if opacity <= 0.0 {
+                continue;
+            }
+            color += widget.color * opacity;
+            opacity_sum += opacity;
+            transparency *= 1.0 - opacity;
+        }
+        if opacity_sum == 0.0 {
+            return color;
+        }
+        color /= opacity_sum;
+        color.w = 1.0 - transparency;
+        color
+    }
+
+    /// Rgba8 texels, row by row from the lowest gradient magnitude
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        (0..HEIGHT)
+            .flat_map(|y| (0..WIDTH).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                let color = self.get(
+                    x as f32 / (WIDTH - 1) as f32,
+                    y as f32 / (HEIGHT - 1) as f32,
+                );
+                [color.x, color.y, color.z, color.w].map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8)
+            })
+            .collect()
+    }
+
+    /// Highest opacity of each density column of the texture, over all gradient magnitudes
+    pub fn texel_opacities(&self) -> Vec<f32> {
+        (0..WIDTH)
+            .map(|x| {
+                (0..HEIGHT)
+                    .map(|y| {
+                        self.get(
+                            x as f32 / (WIDTH - 1) as f32,
+                            y as f32 / (HEIGHT - 1) as f32,
+                        )
+                        .w
+                    })
+                    .fold(0.0, f32::max)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,198 @@
+/// Distribution of the voxel values, used to place transfer function points and thresholds
+use std::collections::BTreeMap;
+
+use color_eyre::eyre::eyre;
+
+use crate::Result;
+
+use super::{ValueWindow, Volume};
+
+pub const BIN_COUNT: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    /// Voxel counts of `BIN_COUNT` equal ranges of the window, bin 0 starts at density 0
+    pub bins: Vec<u32>,
+    /// Same bins, counting only the voxels of each label
+    pub segments: BTreeMap<u8, Vec<u32>>,
+    /// Display names of the labels, when known
+    pub segment_names: BTreeMap<u8, String>,
+}
+
+impl Histogram {
+    /// Bins the real values of the volume over the window, values outside of it go to the first
+    /// or last bin. When labels are given, they must have the dimensions of the volume.
+    pub fn new(volume: &Volume, window: ValueWindow, labels: Option<&Volume>) -> Result<Self> {
+        let rescale = volume.descriptor.rescale;
+        let to_bin = |value: f32| {
+            let density = (rescale.apply(value) - window.min) / window.width();
+            ((density * BIN_COUNT as f32) as usize).min(BIN_COUNT - 1)
+        };
+
+        let mut bins = vec![0; BIN_COUNT];
+        let mut segments = BTreeMap::new();
+        match labels {
+            Some(labels) => {
+                if labels.descriptor.dims != volume.descriptor.dims {
+                    return Err(eyre!(
+                        "Labels have dimensions {:?}, but the volume has {:?}",
+                        labels.descriptor.dims,
+                        volume.descriptor.dims
+                    ));
+                }
+                for (value, label) in volume.values().zip(labels.values()) {
+                    let bin = to_bin(value);
+                    bins[bin] += 1;
+                    segments
+                        .entry(label as u8)
+                        .or_insert_with(|| vec![0; BIN_COUNT])[bin] += 1;
+                }
+            }
+            None => volume.values().for_each(|value| bins[to_bin(value)] += 1),
+        }
+
+        Ok(Self {
+            bins,
+            segments,
+            segment_names: BTreeMap::new(),
+        })
+    }
+
+    /// Bins of one segment, or of the whole volume
+    pub fn bins(&self, segment: Option<u8>) -> &[u32] {
+        segment
+            .and_then(|label| self.segments.get(&label))
+            .unwrap_or(&self.bins)
+    }
+
+    /// Bin heights normalized to the highest bin, a log scale keeps small bins visible
+    /// next to the background peak
+    pub fn heights(&self, segment: Option<u8>, log_scale: bool) -> Vec<f32> {
+        let bins = self.bins(segment);
+        let scale = |count: u32| {
+            if log_scale {
+                (count as f32).ln_1p()
+            } else {
+                count as f32
+            }
+        };
+        let max = bins.iter().copied().map(scale).fold(0.0, f32::max);
+        if max == 0.0 {
+            return vec![0.0; bins.len()];
+        }
+        bins.iter().map(|&count| scale(count) / max).collect()
+    }
+
+    pub fn segment_name(&self, label: u8) -> String {
+        self.segment_names
+            .get(&label)
+            .cloned()
+            .unwrap_or_else(|| format!("Label {}", label))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::volume::{Rescale, VolumeDescriptor, VoxelType};
+
+    fn int16_volume(values: &[i16]) -> Volume {
+        let descriptor = VolumeDescriptor::new((values.len() as u32, 1, 1), VoxelType::Int16);
+        let data = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        Volume::from_bytes(data, descriptor).unwrap()
+    }
+
+    fn labels(values: &[u8]) -> Volume {
+        let descriptor = VolumeDescriptor::new((values.len() as u32, 1, 1), VoxelType::Uint8);
+        Volume::from_bytes(values.to_vec(), descriptor).unwrap()
+    }
+
+    /// Non-empty bins as `(bin, count)`
+    fn counts(bins: &[u32]) -> Vec<(usize, u32)> {
+        bins.iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, count)| count > 0)
+            .collect()
+    }
+
+    #[test]
+    fn bins_rescaled_values_over_the_window() {
+        let mut volume = int16_volume(&[0, 1, 1, 128, 255]);
+        volume.descriptor.rescale = Rescale::new(2.0, -100.0).unwrap();
+        // Two real units per bin
+        let histogram = Histogram::new(&volume, ValueWindow::new(-100.0, 412.0), None).unwrap();
+
+        assert_eq!(histogram.bins.len(), BIN_COUNT);
+        assert_eq!(
+            counts(&histogram.bins),
+            vec![(0, 1), (1, 2), (128, 1), (255, 1)]
+        );
+        assert!(histogram.segments.is_empty());
+    }
+
+    #[test]
+    fn clamps_values_outside_the_window_to_the_end_bins() {
+        let volume = int16_volume(&[-1000, -1, 0, 99, 100, 1000]);
+        let histogram = Histogram::new(&volume, ValueWindow::new(0.0, 100.0), None).unwrap();
+
+        assert_eq!(counts(&histogram.bins), vec![(0, 3), (253, 1), (255, 2)]);
+    }
+
+    #[test]
+    fn bins_every_label_separately() {
+        let volume = int16_volume(&[0, 0, 255, 255, 128]);
+        let labels = labels(&[0, 1, 1, 2, 1]);
+        let histogram =
+            Histogram::new(&volume, ValueWindow::new(0.0, 256.0), Some(&labels)).unwrap();
+
+        assert_eq!(counts(&histogram.bins), vec![(0, 2), (128, 1), (255, 2)]);
+        assert_eq!(
+            histogram.segments.keys().copied().collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert_eq!(counts(histogram.bins(Some(0))), vec![(0, 1)]);
+        assert_eq!(
+            counts(histogram.bins(Some(1))),
+            vec![(0, 1), (128, 1), (255, 1)]
+        );
+        assert_eq!(counts(histogram.bins(Some(2))), vec![(255, 1)]);
+        // Unknown labels fall back to the whole volume
+        assert_eq!(histogram.bins(Some(7)), &histogram.bins[..]);
+        assert_eq!(histogram.bins(None), &histogram.bins[..]);
+    }
+
+    #[test]
+    fn rejects_labels_of_other_dimensions() {
+        let volume = int16_volume(&[0, 1, 2]);
+        let error = Histogram::new(&volume, ValueWindow::new(0.0, 2.0), Some(&labels(&[0, 1])))
+            .unwrap_err();
+        assert!(error.to_string().contains("dimensions"), "{}", error);
+    }
+
+    #[test]
+    fn normalizes_heights_on_a_linear_or_log_scale() {
+        let mut values = vec![0; 99];
+        values.push(255);
+        let histogram =
+            Histogram::new(&int16_volume(&values), ValueWindow::new(0.0, 256.0), None).unwrap();
+
+        let linear = histogram.heights(None, false);
+        assert_eq!(linear[0], 1.0);
+        assert_eq!(linear[255], 1.0 / 99.0);
+        assert_eq!(linear[1], 0.0);
+
+        let log = histogram.heights(None, true);
+        assert_eq!(log[0], 1.0);
+        assert_eq!(log[255], 2f32.ln() / 100f32.ln());
+        assert_eq!(log[1], 0.0);
+
+        // Empty segments don't divide by zero
+        let empty = Histogram {
+            bins: vec![0; BIN_COUNT],
+            segments: BTreeMap::new(),
+            segment_names: BTreeMap::new(),
+        };
+        assert!(empty.heights(None, true).iter().all(|&h| h == 0.0));
+    }
+}
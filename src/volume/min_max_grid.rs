@@ -0,0 +1,201 @@
+/// Coarse grid of the density range of every block of voxels, to find empty space quickly
+use super::{ValueWindow, Volume};
+
+/// Voxels along each side of a cell
+pub const CELL_SIZE: u32 = 8;
+
+/// Densities within this distance of the threshold count as above it, samples on the GPU
+/// may be slightly off after quantization and filtering
+const TOLERANCE: f32 = 1.0 / 512.0;
+
+#[derive(Debug, Clone)]
+pub struct MinMaxGrid {
+    /// Cells along each axis
+    pub dims: (u32, u32, u32),
+    /// Lowest and highest density, in the 0..=1 window, any sample within a cell can have.
+    /// Cells include the bordering voxels of their neighbors, which linear filtering blends in.
+    ranges: Vec<[f32; 2]>,
+}
+
+impl MinMaxGrid {
+    /// `flip_y` lays the cells out like a volume texture that was uploaded upside down
+    pub fn new(volume: &Volume, window: ValueWindow, flip_y: bool) -> Self {
+        let (width, height, depth) = volume.descriptor.dims;
+        let dims = (
+            width.div_ceil(CELL_SIZE),
+            height.div_ceil(CELL_SIZE),
+            depth.div_ceil(CELL_SIZE),
+        );
+        let rescale = volume.descriptor.rescale;
+        let window_width = if window.width() != 0.0 {
+            window.width()
+        } else {
+            1.0
+        };
+
+        // Cells a voxel belongs to along one axis: its own, and the neighbor it borders
+        let cells_of = |voxel: u32, cells: u32| {
+            let cell = voxel / CELL_SIZE;
+            match voxel % CELL_SIZE {
+                0 => cell.saturating_sub(1)..=cell,
+                offset if offset == CELL_SIZE - 1 => cell..=(cell + 1).min(cells - 1),
+                _ => cell..=cell,
+            }
+        };
+
+        let mut ranges = vec![[f32::MAX, f32::MIN]; (dims.0 * dims.1 * dims.2) as usize];
+        let mut values = volume.values();
+        for z in 0..depth {
+            for y in 0..height {
+                let y = if flip_y { height - 1 - y } else { y };
+                for x in 0..width {
+                    let Some(value) = values.next() else {
+                        return Self { dims, ranges };
+                    };
+                    let density =
+                        ((rescale.apply(value) - window.min) / window_width).clamp(0.0, 1.0);
+                    for cz in cells_of(z, dims.2) {
+                        for cy in cells_of(y, dims.1) {
+                            for cx in cells_of(x, dims.0) {
+                                let range =
+                                    &mut ranges[(cx + dims.0 * (cy + dims.1 * cz)) as usize];
+                                range[0] = range[0].min(density);
+                                range[1] = range[1].max(density);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Self { dims, ranges }
+    }
+
+    /// One byte per cell, 255 when a sample within it can be visible, 0 when it can be skipped.
+    /// A cell is empty when all its densities are below `threshold`, or when `opacities`,
+    /// the texels of the transfer function's opacity, are all zero over its densities.
+    pub fn occupancy(&self, threshold: f32, opacities: Option<&[f32]>) -> Vec<u8> {
+        self.ranges
+            .iter()
+            .map(|&[min, max]| {
+                if max + TOLERANCE < threshold {
+                    return 0;
+                }
+                let visible = opacities.is_none_or(|opacities| {
+                    max_opacity(opacities, min.max(threshold) - TOLERANCE, max + TOLERANCE) > 0.0
+                });
+                if visible {
+                    u8::MAX
+                } else {
+                    0
+                }
+            })
+            .collect()
+    }
+}
+
+/// Highest opacity linear filtering can return for densities in `min..=max`, from the texels
+/// of a transfer function texture
+fn max_opacity(opacities: &[f32], min: f32, max: f32) -> f32 {
+    let texel = |density: f32| {
+        ((density.clamp(0.0, 1.0) * opacities.len() as f32 - 0.5).floor() as isize)
+            .clamp(0, opacities.len() as isize - 1) as usize
+    };
+    let first = texel(min);
+    let last = (texel(max) + 1).min(opacities.len() - 1);
+    opacities[first..=last].iter().copied().fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::volume::{VolumeDescriptor, VoxelType};
+
+    /// 16x16x8 volume, two by two by one cells, zero apart from `hot` voxels at 255
+    fn volume_with(hot: &[(u32, u32, u32)]) -> Volume {
+        let descriptor = VolumeDescriptor::new((16, 16, 8), VoxelType::Uint8);
+        let mut data = vec![0; descriptor.len_in_bytes()];
+        for &(x, y, z) in hot {
+            data[(x + 16 * (y + 16 * z)) as usize] = 255;
+        }
+        Volume::from_bytes(data, descriptor).unwrap()
+    }
+
+    fn occupied_cells(grid: &MinMaxGrid, threshold: f32, opacities: Option<&[f32]>) -> Vec<u32> {
+        grid.occupancy(threshold, opacities)
+            .iter()
+            .enumerate()
+            .filter(|(_, &occupied)| occupied == u8::MAX)
+            .map(|(cell, _)| cell as u32)
+            .collect()
+    }
+
+    const WINDOW: ValueWindow = ValueWindow {
+        min: 0.0,
+        max: 255.0,
+    };
+
+    #[test]
+    fn skips_cells_below_threshold() {
+        let grid = MinMaxGrid::new(&volume_with(&[(3, 3, 3)]), WINDOW, false);
+
+        assert_eq!(grid.dims, (2, 2, 1));
+        assert_eq!(occupied_cells(&grid, 0.5, None), vec![0]);
+        assert_eq!(occupied_cells(&grid, 0.0, None), vec![0, 1, 2, 3]);
+        // Within the tolerance of the threshold still counts as above it
+        assert_eq!(occupied_cells(&grid, 1.0 + TOLERANCE / 2.0, None), vec![0]);
+    }
+
+    #[test]
+    fn border_voxels_belong_to_both_cells() {
+        let grid = MinMaxGrid::new(&volume_with(&[(7, 3, 3)]), WINDOW, false);
+        assert_eq!(occupied_cells(&grid, 0.5, None), vec![0, 1]);
+
+        let grid = MinMaxGrid::new(&volume_with(&[(8, 8, 3)]), WINDOW, false);
+        assert_eq!(occupied_cells(&grid, 0.5, None), vec![0, 1, 2, 3]);
+
+        let grid = MinMaxGrid::new(&volume_with(&[(9, 3, 3)]), WINDOW, false);
+        assert_eq!(occupied_cells(&grid, 0.5, None), vec![1]);
+    }
+
+    #[test]
+    fn flip_y_mirrors_rows() {
+        let volume = volume_with(&[(3, 2, 3)]);
+
+        let grid = MinMaxGrid::new(&volume, WINDOW, false);
+        assert_eq!(occupied_cells(&grid, 0.5, None), vec![0]);
+
+        let grid = MinMaxGrid::new(&volume, WINDOW, true);
+        assert_eq!(occupied_cells(&grid, 0.5, None), vec![2]);
+    }
+
+    #[test]
+    fn skips_cells_the_transfer_function_hides() {
+        let grid = MinMaxGrid::new(&volume_with(&[(3, 3, 3)]), WINDOW, false);
+
+        let mut opacities = vec![0.0; 256];
+        opacities[255] = 1.0;
+        assert_eq!(occupied_cells(&grid, 0.0, Some(&opacities)), vec![0]);
+
+        opacities[255] = 0.0;
+        opacities[0] = 1.0;
+        assert_eq!(
+            occupied_cells(&grid, 0.0, Some(&opacities)),
+            vec![0, 1, 2, 3]
+        );
+        // Above the threshold, only the transparent densities are left
+        assert!(occupied_cells(&grid, 0.5, Some(&opacities)).is_empty());
+    }
+
+    #[test]
+    fn max_opacity_includes_filtered_neighbors() {
+        let opacities = [0.0, 0.0, 1.0, 0.0];
+
+        // Densities map to texel centers at 0.125, 0.375, 0.625 and 0.875
+        assert_eq!(max_opacity(&opacities, 0.1, 0.1), 0.0);
+        assert_eq!(max_opacity(&opacities, 0.4, 0.4), 1.0);
+        assert_eq!(max_opacity(&opacities, 0.9, 0.9), 0.0);
+        assert_eq!(max_opacity(&opacities, 0.0, 1.0), 1.0);
+        assert_eq!(max_opacity(&opacities, -1.0, 0.2), 0.0);
+    }
+}
@@ -0,0 +1,349 @@
+/// CPU side description and loading of volumetric data
+use color_eyre::eyre::eyre;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::info;
+
+use crate::Result;
+
+pub mod histogram;
+pub mod min_max_grid;
+pub mod nrrd;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VoxelType {
+    Uint8,
+    Uint16,
+    Int16,
+    Float32,
+}
+
+impl VoxelType {
+    pub fn size_in_bytes(&self) -> usize {
+        match self {
+            VoxelType::Uint8 => 1,
+            VoxelType::Uint16 | VoxelType::Int16 => 2,
+            VoxelType::Float32 => 4,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "uint8" | "u8" => Some(VoxelType::Uint8),
+            "uint16" | "u16" => Some(VoxelType::Uint16),
+            "int16" | "i16" => Some(VoxelType::Int16),
+            "float32" | "float" | "f32" => Some(VoxelType::Float32),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+/// Describes the layout of the voxels of a volume file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolumeDescriptor {
+    /// Number of voxels along x, y and z
+    pub dims: (u32, u32, u32),
+    pub voxel_type: VoxelType,
+    /// Byte order of the voxels in the file. In memory, voxels are always little endian.
+    pub byte_order: ByteOrder,
+    /// Physical size of a voxel along x, y and z
+    pub spacing: [f32; 3],
+    /// Maps stored voxels to real values, i.e. CT scanners store Hounsfield units this way
+    pub rescale: Rescale,
+}
+
+/// `real = stored * slope + intercept`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rescale {
+    pub slope: f32,
+    pub intercept: f32,
+}
+
+impl Default for Rescale {
+    fn default() -> Self {
+        Self {
+            slope: 1.0,
+            intercept: 0.0,
+        }
+    }
+}
+
+impl Rescale {
+    pub fn new(slope: f32, intercept: f32) -> Result<Self> {
+        let rescale = Self { slope, intercept };
+        rescale.validate()?;
+        Ok(rescale)
+    }
+
+    /// The slope can't be zero, every stored value would map to the intercept
+    pub fn validate(&self) -> Result<()> {
+        if self.slope == 0.0 || !self.slope.is_finite() || !self.intercept.is_finite() {
+            return Err(eyre!(
+                "Invalid rescale slope {} and intercept {}, the slope has to be finite and non-zero",
+                self.slope,
+                self.intercept
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, stored: f32) -> f32 {
+        stored * self.slope + self.intercept
+    }
+}
+
+/// Range of real values that is mapped to the 0..=1 densities the transfer function is indexed with
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ValueWindow {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl ValueWindow {
+    pub fn new(min: f32, max: f32) -> Self {
+        Self { min, max }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.min >= self.max || !self.min.is_finite() || !self.max.is_finite() {
+            return Err(eyre!(
+                "Invalid value window {} to {}, the minimum has to be below the maximum",
+                self.min,
+                self.max
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn width(&self) -> f32 {
+        self.max - self.min
+    }
+
+    /// Transfer function resolution for this window: one entry per stored integer value,
+    /// float volumes get a fixed resolution
+    pub fn max_density(&self, descriptor: &VolumeDescriptor) -> u32 {
+        match descriptor.voxel_type {
+            VoxelType::Float32 => 1023,
+            _ => ((self.width() / descriptor.rescale.slope.abs()).round() as u32).max(1),
+        }
+    }
+
+    /// Covers every real value of the volume
+    pub fn from_volume(volume: &Volume) -> Self {
+        let (min, max) = volume.value_range();
+        let (min, max) = (
+            volume.descriptor.rescale.apply(min),
+            volume.descriptor.rescale.apply(max),
+        );
+        Self::new(min.min(max), min.max(max))
+    }
+}
+
+impl VolumeDescriptor {
+    pub fn new(dims: (u32, u32, u32), voxel_type: VoxelType) -> Self {
+        Self {
+            dims,
+            voxel_type,
+            byte_order: ByteOrder::Little,
+            spacing: [1.0; 3],
+            rescale: Rescale::default(),
+        }
+    }
+
+    /// Parses the usual `<name>_<x>x<y>x<z>_<type>.raw` naming scheme,
+    /// i.e. `boston_teapot_256x256x178_uint8.raw`
+    pub fn from_file_name(path: &Path) -> Result<Self> {
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or(eyre!("Volume path {:?} has no file name", path))?;
+
+        let mut parts = stem.rsplit('_');
+        let voxel_type = parts
+            .next()
+            .and_then(VoxelType::from_name)
+            .ok_or(eyre!("Could not find the voxel type in {:?}", path))?;
+        let dims = parts
+            .next()
+            .map(|dims| {
+                dims.split('x')
+                    .map(|d| d.parse::<u32>())
+                    .collect::<std::result::Result<Vec<_>, _>>()
+            })
+            .and_then(|dims| dims.ok())
+            .filter(|dims| dims.len() == 3)
+            .ok_or(eyre!("Could not find the dimensions in {:?}", path))?;
+
+        Ok(Self::new((dims[0], dims[1], dims[2]), voxel_type))
+    }
+
+    pub fn voxel_count(&self) -> usize {
+        self.dims.0 as usize * self.dims.1 as usize * self.dims.2 as usize
+    }
+
+    pub fn len_in_bytes(&self) -> usize {
+        self.voxel_count() * self.voxel_type.size_in_bytes()
+    }
+
+    /// Size of the volume's bounding box, normalized so that its largest side is 1
+    pub fn extent(&self) -> [f32; 3] {
+        let (x, y, z) = (
+            self.dims.0 as f32 * self.spacing[0],
+            self.dims.1 as f32 * self.spacing[1],
+            self.dims.2 as f32 * self.spacing[2],
+        );
+        let max = x.max(y).max(z);
+        [x / max, y / max, z / max]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Volume {
+    pub descriptor: VolumeDescriptor,
+    /// Little endian voxels, `descriptor.voxel_type` each
+    pub data: Vec<u8>,
+}
+
+impl Volume {
+    /// Opens NRRD files, and raw files named following [`VolumeDescriptor::from_file_name`]
+    pub fn from_file(path: &Path) -> Result<Self> {
+        if nrrd::is_nrrd(path) {
+            Ok(nrrd::Nrrd::from_file(path)?.volume)
+        } else {
+            Self::from_raw_file(path, VolumeDescriptor::from_file_name(path)?)
+        }
+    }
+
+    pub fn from_raw_file(path: &Path, descriptor: VolumeDescriptor) -> Result<Self> {
+        info!("Loading raw volume {:?} ({:?})", path, descriptor);
+        Self::from_bytes(std::fs::read(path)?, descriptor)
+    }
+
+    pub fn from_bytes(mut data: Vec<u8>, descriptor: VolumeDescriptor) -> Result<Self> {
+        if data.len() != descriptor.len_in_bytes() {
+            return Err(eyre!(
+                "Volume has {} bytes, but {:?} requires {}",
+                data.len(),
+                descriptor,
+                descriptor.len_in_bytes()
+            ));
+        }
+
+        let voxel_size = descriptor.voxel_type.size_in_bytes();
+        if descriptor.byte_order == ByteOrder::Big && voxel_size > 1 {
+            for voxel in data.chunks_exact_mut(voxel_size) {
+                voxel.reverse();
+            }
+        }
+
+        Ok(Self { descriptor, data })
+    }
+
+    /// Value of every voxel, as f32
+    pub fn values(&self) -> impl Iterator<Item = f32> + '_ {
+        let voxel_type = self.descriptor.voxel_type;
+        self.data
+            .chunks_exact(voxel_type.size_in_bytes())
+            .map(move |v| match voxel_type {
+                VoxelType::Uint8 => v[0] as f32,
+                VoxelType::Uint16 => u16::from_le_bytes([v[0], v[1]]) as f32,
+                VoxelType::Int16 => i16::from_le_bytes([v[0], v[1]]) as f32,
+                VoxelType::Float32 => f32::from_le_bytes([v[0], v[1], v[2], v[3]]),
+            })
+    }
+
+    /// Minimum and maximum stored voxel values
+    pub fn value_range(&self) -> (f32, f32) {
+        self.values().fold((f32::MAX, f32::MIN), |(min, max), v| {
+            (min.min(v), max.max(v))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dims_and_type_from_file_names() {
+        let descriptor = VolumeDescriptor::from_file_name(Path::new(
+            "assets/boston_teapot_256x256x178_uint8.raw",
+        ))
+        .unwrap();
+        assert_eq!(descriptor.dims, (256, 256, 178));
+        assert_eq!(descriptor.voxel_type, VoxelType::Uint8);
+        assert_eq!(descriptor.byte_order, ByteOrder::Little);
+        assert_eq!(descriptor.len_in_bytes(), 256 * 256 * 178);
+
+        for (name, voxel_type) in [
+            ("ct_512x512x100_int16.raw", VoxelType::Int16),
+            ("a_b_1x2x3_u16.raw", VoxelType::Uint16),
+            ("sim_64x64x64_float.raw", VoxelType::Float32),
+        ] {
+            let descriptor = VolumeDescriptor::from_file_name(Path::new(name)).unwrap();
+            assert_eq!(descriptor.voxel_type, voxel_type, "{}", name);
+        }
+        let descriptor = VolumeDescriptor::from_file_name(Path::new("a_b_1x2x3_u16.raw")).unwrap();
+        assert_eq!(descriptor.dims, (1, 2, 3));
+    }
+
+    #[test]
+    fn rejects_malformed_file_names() {
+        for (name, message) in [
+            ("teapot_256x256x178_uint12.raw", "voxel type"),
+            ("teapot.raw", "voxel type"),
+            ("teapot_uint8.raw", "dimensions"),
+            ("teapot_256x256_uint8.raw", "dimensions"),
+            ("teapot_256x256x178x2_uint8.raw", "dimensions"),
+            ("teapot_256xfoox178_uint8.raw", "dimensions"),
+            ("teapot_-1x256x178_uint8.raw", "dimensions"),
+        ] {
+            let error = VolumeDescriptor::from_file_name(Path::new(name)).unwrap_err();
+            assert!(error.to_string().contains(message), "{}: {}", name, error);
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_byte_count() {
+        let descriptor = VolumeDescriptor::new((2, 2, 2), VoxelType::Uint16);
+        let error = Volume::from_bytes(vec![0; 15], descriptor).unwrap_err();
+        assert!(error.to_string().contains("15 bytes"), "{}", error);
+        assert!(Volume::from_bytes(vec![0; 16], descriptor).is_ok());
+    }
+
+    #[test]
+    fn swaps_big_endian_voxels() {
+        let mut descriptor = VolumeDescriptor::new((2, 1, 1), VoxelType::Int16);
+        descriptor.byte_order = ByteOrder::Big;
+        let volume = Volume::from_bytes(vec![0x01, 0x02, 0xff, 0xfe], descriptor).unwrap();
+        assert_eq!(volume.data, vec![0x02, 0x01, 0xfe, 0xff]);
+        assert_eq!(volume.values().collect::<Vec<_>>(), vec![258.0, -2.0]);
+
+        descriptor.voxel_type = VoxelType::Float32;
+        descriptor.dims = (1, 1, 1);
+        let volume = Volume::from_bytes(1.5f32.to_be_bytes().to_vec(), descriptor).unwrap();
+        assert_eq!(volume.values().collect::<Vec<_>>(), vec![1.5]);
+
+        // Single bytes have no order
+        descriptor.voxel_type = VoxelType::Uint8;
+        descriptor.dims = (2, 1, 1);
+        let volume = Volume::from_bytes(vec![1, 2], descriptor).unwrap();
+        assert_eq!(volume.data, vec![1, 2]);
+    }
+
+    #[test]
+    fn normalizes_extent_to_the_largest_side() {
+        let mut descriptor = VolumeDescriptor::new((256, 128, 64), VoxelType::Uint8);
+        assert_eq!(descriptor.extent(), [1.0, 0.5, 0.25]);
+
+        // The spacing counts, not the number of voxels
+        descriptor.spacing = [0.5, 1.0, 4.0];
+        assert_eq!(descriptor.extent(), [0.5, 0.5, 1.0]);
+    }
+}
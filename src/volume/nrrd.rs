@@ -0,0 +1,289 @@
+/// Reader for NRRD volumes (https://teem.sourceforge.net/nrrd/format.html), as exported by 3D Slicer
+use std::{collections::HashMap, io::Read, path::Path};
+
+use color_eyre::eyre::eyre;
+use flate2::read::GzDecoder;
+use tracing::{info, warn};
+
+use crate::Result;
+
+use super::{ByteOrder, Rescale, Volume, VolumeDescriptor, VoxelType};
+
+#[derive(Debug, Clone)]
+pub struct Nrrd {
+    pub volume: Volume,
+    /// `key:=value` pairs of the header, i.e. Slicer's `Segment0_LabelValue:=1`
+    pub key_values: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Encoding {
+    Raw,
+    Gzip,
+}
+
+pub fn is_nrrd(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("nrrd") | Some("nhdr")
+    )
+}
+
+impl Nrrd {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        info!("Loading NRRD volume {:?}", path);
+        let contents = std::fs::read(path)?;
+        let header_len = find_header_end(&contents)
+            .ok_or(eyre!("{:?} does not contain a complete NRRD header", path))?;
+        let header = std::str::from_utf8(&contents[..header_len])?;
+
+        let mut lines = header.lines();
+        let magic = lines.next().unwrap_or_default();
+        if !magic.starts_with("NRRD") {
+            return Err(eyre!("{:?} is not a NRRD file, magic is {:?}", path, magic));
+        }
+
+        let mut fields = HashMap::new();
+        let mut key_values = HashMap::new();
+        for line in lines {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // `key:=value` must be checked first, `field: value` would also match it
+            if let Some((key, value)) = line.split_once(":=") {
+                key_values.insert(key.to_string(), value.to_string());
+            } else if let Some((field, value)) = line.split_once(": ") {
+                fields.insert(field.trim().to_lowercase(), value.trim().to_string());
+            } else {
+                warn!("Ignoring malformed NRRD header line {:?}", line);
+            }
+        }
+        let field = |name: &str| {
+            fields.get(name).map(String::as_str).ok_or(eyre!(
+                "NRRD header of {:?} is missing the {:?} field",
+                path,
+                name
+            ))
+        };
+
+        let dimension: usize = field("dimension")?.parse()?;
+        if dimension != 3 {
+            return Err(eyre!(
+                "Only 3D NRRD volumes are supported, got {}",
+                dimension
+            ));
+        }
+
+        let sizes = field("sizes")?
+            .split_whitespace()
+            .map(str::parse::<u32>)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if sizes.len() != 3 {
+            return Err(eyre!("Expected 3 sizes, got {:?}", sizes));
+        }
+
+        let voxel_type = parse_type(field("type")?)?;
+        let byte_order = match fields.get("endian").map(String::as_str) {
+            Some("big") => ByteOrder::Big,
+            Some("little") | None => ByteOrder::Little,
+            Some(other) => return Err(eyre!("Unknown NRRD endian {:?}", other)),
+        };
+        let encoding = match field("encoding")? {
+            "raw" => Encoding::Raw,
+            "gzip" | "gz" => Encoding::Gzip,
+            other => return Err(eyre!("Unsupported NRRD encoding {:?}", other)),
+        };
+        let spacing = match (fields.get("space directions"), fields.get("spacings")) {
+            (Some(directions), _) => parse_space_directions(directions)?,
+            (None, Some(spacings)) => parse_spacings(spacings)?,
+            (None, None) => [1.0; 3],
+        };
+
+        let data = match fields.get("data file").or(fields.get("datafile")) {
+            Some(data_file) => {
+                let data_path = path.with_file_name(data_file);
+                info!("Reading detached NRRD data from {:?}", data_path);
+                std::fs::read(data_path)?
+            }
+            None => contents[header_len..].to_vec(),
+        };
+        let data = match encoding {
+            Encoding::Raw => data,
+            Encoding::Gzip => {
+                let mut decoded = Vec::new();
+                GzDecoder::new(data.as_slice()).read_to_end(&mut decoded)?;
+                decoded
+            }
+        };
+
+        let descriptor = VolumeDescriptor {
+            dims: (sizes[0], sizes[1], sizes[2]),
+            voxel_type,
+            byte_order,
+            spacing,
+            rescale: parse_rescale(&key_values)?,
+        };
+        let volume = Volume::from_bytes(data, descriptor)?;
+
+        Ok(Self { volume, key_values })
+    }
+}
+
+/// `RescaleSlope:=0.5` and `RescaleIntercept:=-1024`, as written by DICOM converters. The
+/// keys are matched ignoring case and separators, i.e. `rescale_slope` or `DICOM_RescaleSlope`.
+fn parse_rescale(key_values: &HashMap<String, String>) -> Result<Rescale> {
+    let value = |name: &str| {
+        key_values
+            .iter()
+            .find(|(key, _)| {
+                key.chars()
+                    .filter(|c| c.is_ascii_alphanumeric())
+                    .collect::<String>()
+                    .to_lowercase()
+                    .ends_with(name)
+            })
+            .map(|(key, value)| {
+                value
+                    .trim()
+                    .parse::<f32>()
+                    .map_err(|e| eyre!("Invalid NRRD {:?} {:?}: {}", key, value, e))
+            })
+            .transpose()
+    };
+    let default = Rescale::default();
+    Rescale::new(
+        value("rescaleslope")?.unwrap_or(default.slope),
+        value("rescaleintercept")?.unwrap_or(default.intercept),
+    )
+}
+
+/// Position right after the blank line that ends the header
+fn find_header_end(contents: &[u8]) -> Option<usize> {
+    contents.windows(2).enumerate().find_map(|(i, w)| match w {
+        b"\n\n" => Some(i + 2),
+        b"\r\n" if contents[i + 2..].starts_with(b"\r\n") => Some(i + 4),
+        _ => None,
+    })
+}
+
+fn parse_type(name: &str) -> Result<VoxelType> {
+    match name {
+        "uchar" | "unsigned char" | "uint8" | "uint8_t" => Ok(VoxelType::Uint8),
+        "ushort" | "unsigned short" | "unsigned short int" | "uint16" | "uint16_t" => {
+            Ok(VoxelType::Uint16)
+        }
+        "short" | "short int" | "signed short" | "signed short int" | "int16" | "int16_t" => {
+            Ok(VoxelType::Int16)
+        }
+        "float" => Ok(VoxelType::Float32),
+        other => Err(eyre!("Unsupported NRRD type {:?}", other)),
+    }
+}
+
+/// `(1.5,0,0) (0,1.5,0) (0,0,2)` - the length of each vector is the spacing along that axis
+fn parse_space_directions(directions: &str) -> Result<[f32; 3]> {
+    let spacing = directions
+        .split_whitespace()
+        .filter(|d| *d != "none")
+        .map(|d| {
+            d.trim_matches(|c| c == '(' || c == ')')
+                .split(',')
+                .map(str::parse::<f32>)
+                .try_fold(0.0, |acc, c| c.map(|c| acc + c * c))
+                .map(f32::sqrt)
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    spacing
+        .try_into()
+        .map_err(|s| eyre!("Expected 3 space directions, got {:?}", s))
+}
+
+/// `1.5 1.5 2` - `nan` is used by axes without spacing
+fn parse_spacings(spacings: &str) -> Result<[f32; 3]> {
+    let spacing = spacings
+        .split_whitespace()
+        .map(|s| s.parse::<f32>().map(|s| if s.is_nan() { 1.0 } else { s }))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    spacing
+        .try_into()
+        .map_err(|s| eyre!("Expected 3 spacings, got {:?}", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn reads_raw_big_endian_with_space_directions() {
+        let nrrd = Nrrd::from_file(&fixture("uint16_big_endian_raw.nrrd")).unwrap();
+        let descriptor = nrrd.volume.descriptor;
+
+        assert_eq!(descriptor.dims, (2, 3, 2));
+        assert_eq!(descriptor.voxel_type, VoxelType::Uint16);
+        assert_eq!(descriptor.byte_order, ByteOrder::Big);
+        assert_eq!(descriptor.spacing, [0.5, 2.0, 1.5]);
+        assert_eq!(
+            nrrd.volume.values().collect::<Vec<_>>(),
+            (0..12).map(|i| i as f32 * 100.0).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            nrrd.key_values.get("Segment0_Name").map(String::as_str),
+            Some("Bone")
+        );
+    }
+
+    #[test]
+    fn reads_gzip_big_endian_with_spacings() {
+        let nrrd = Nrrd::from_file(&fixture("int16_big_endian_gzip.nrrd")).unwrap();
+        let descriptor = nrrd.volume.descriptor;
+
+        assert_eq!(descriptor.dims, (3, 2, 2));
+        assert_eq!(descriptor.voxel_type, VoxelType::Int16);
+        assert_eq!(descriptor.spacing, [1.0, 0.25, 1.0]);
+        assert_eq!(
+            nrrd.volume.values().collect::<Vec<_>>(),
+            (-6..6).map(|i| i as f32).collect::<Vec<_>>()
+        );
+        assert_eq!(nrrd.volume.value_range(), (-6.0, 5.0));
+    }
+
+    #[test]
+    fn opens_nrrd_through_volume() {
+        assert!(is_nrrd(&fixture("int16_big_endian_gzip.nrrd")));
+        let volume = Volume::from_file(&fixture("int16_big_endian_gzip.nrrd")).unwrap();
+        assert_eq!(volume.descriptor.dims, (3, 2, 2));
+    }
+
+    fn key_values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_rescale_from_key_values() {
+        let rescale = parse_rescale(&key_values(&[
+            ("DICOM_RescaleSlope", "0.5"),
+            ("rescale_intercept", " -1024"),
+        ]))
+        .unwrap();
+        assert_eq!(rescale, Rescale::new(0.5, -1024.0).unwrap());
+        assert_eq!(parse_rescale(&HashMap::new()).unwrap(), Rescale::default());
+    }
+
+    #[test]
+    fn rejects_zero_rescale_slope() {
+        let error = parse_rescale(&key_values(&[("RescaleSlope", "0")])).unwrap_err();
+        assert!(error.to_string().contains("slope"), "{}", error);
+        assert!(parse_rescale(&key_values(&[("RescaleSlope", "steep")])).is_err());
+    }
+}